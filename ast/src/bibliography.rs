@@ -0,0 +1,307 @@
+//! `bibliography` is the model of the `.bib` files used by a document. Entries are written back in
+//! the form they were read in until they are edited, so that changes to the files stay small.
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::errors::{AstError, OperationError};
+use crate::latex_constants::*;
+use crate::options::StringificationOptions;
+use crate::reference_index::find_keys;
+use crate::texla_ast::TexlaAst;
+use crate::Ast;
+
+pub mod import;
+pub mod operation;
+mod parser;
+
+/// Abbreviations of the months which BibTeX styles define
+const MONTH_MACROS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const FIELD_INDENTATION: &str = "  ";
+
+/// All `.bib` files used by a document.
+#[derive(Serialize, Debug, Default)]
+pub struct Bibliography {
+    pub files: Vec<BibFile>,
+}
+
+impl Bibliography {
+    pub fn entry(&self, key: &str) -> Option<&BibEntry> {
+        self.files.iter().find_map(|file| file.entry(key))
+    }
+
+    pub fn file(&self, path: &str) -> Option<&BibFile> {
+        self.files.iter().find(|file| file.path == path)
+    }
+
+    /// Modifies the bibliography by applying the provided `BibOperation`.
+    /// Returns the path of the file which was changed.
+    pub fn execute(
+        &mut self,
+        operation: Box<dyn operation::BibOperation>,
+    ) -> Result<String, OperationError> {
+        operation.execute_on(self)
+    }
+
+    fn file_of_entry_mut(&mut self, key: &str) -> Option<&mut BibFile> {
+        self.files.iter_mut().find(|file| file.entry(key).is_some())
+    }
+}
+
+/// A `.bib` file, consisting of entries and the text around them (comments, `@string`s and
+/// `@preamble`s), which is kept as it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BibFile {
+    /// The path as it is used in the LaTeX code, including the `.bib` extension
+    pub path: String,
+    chunks: Vec<BibChunk>,
+    /// The names of the `@string`s defined in the file
+    macros: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum BibChunk {
+    Text(String),
+    Entry(BibEntry),
+}
+
+impl BibFile {
+    pub fn parse(path: String, content: &str) -> Self {
+        let (chunks, macros) = parser::parse_chunks(content);
+        Self {
+            path,
+            chunks,
+            macros,
+        }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &BibEntry> {
+        self.chunks.iter().filter_map(|chunk| match chunk {
+            BibChunk::Entry(entry) => Some(entry),
+            BibChunk::Text(_) => None,
+        })
+    }
+
+    pub fn entry(&self, key: &str) -> Option<&BibEntry> {
+        self.entries().find(|entry| entry.key == key)
+    }
+
+    /// Generates the content of the file.
+    pub fn to_bib(&self) -> String {
+        self.chunks
+            .iter()
+            .map(|chunk| match chunk {
+                BibChunk::Text(text) => text.clone(),
+                BibChunk::Entry(entry) => entry.to_bib(&self.macros),
+            })
+            .collect()
+    }
+
+    fn push_entry(&mut self, entry: BibEntry) {
+        let content = self.to_bib();
+        let separator = match content.len() - content.trim_end_matches('\n').len() {
+            _ if content.is_empty() => "",
+            0 => "\n\n",
+            1 => "\n",
+            _ => "",
+        };
+        self.chunks.push(BibChunk::Text(separator.to_string()));
+        self.chunks.push(BibChunk::Entry(entry));
+        self.chunks.push(BibChunk::Text("\n".to_string()));
+        self.merge_texts();
+    }
+
+    fn replace_entry(&mut self, key: &str, new_entry: BibEntry) {
+        for chunk in &mut self.chunks {
+            if matches!(chunk, BibChunk::Entry(entry) if entry.key == key) {
+                *chunk = BibChunk::Entry(new_entry);
+                return;
+            }
+        }
+    }
+
+    /// Removes the entry together with the blank lines following it.
+    fn remove_entry(&mut self, key: &str) {
+        let Some(index) = self
+            .chunks
+            .iter()
+            .position(|chunk| matches!(chunk, BibChunk::Entry(entry) if entry.key == key))
+        else {
+            return;
+        };
+        self.chunks.remove(index);
+        if let Some(BibChunk::Text(text)) = self.chunks.get_mut(index) {
+            *text = text.trim_start().to_string();
+        }
+        self.merge_texts();
+        if let Some(BibChunk::Text(text)) = self.chunks.last_mut() {
+            // the file still ends with a single line break
+            if text.ends_with("\n\n") {
+                *text = format!("{}\n", text.trim_end());
+            }
+        }
+    }
+
+    fn merge_texts(&mut self) {
+        let mut chunks: Vec<BibChunk> = vec![];
+        for chunk in self.chunks.drain(..) {
+            match (chunks.last_mut(), chunk) {
+                (_, BibChunk::Text(text)) if text.is_empty() => {}
+                (Some(BibChunk::Text(previous)), BibChunk::Text(text)) => *previous += &text,
+                (_, chunk) => chunks.push(chunk),
+            }
+        }
+        self.chunks = chunks;
+    }
+}
+
+impl Serialize for BibFile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("BibFile", 2)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field("entries", &self.entries().collect::<Vec<_>>())?;
+        state.end()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BibEntry {
+    /// The type in lowercase, e.g. `article`
+    pub entry_type: String,
+    pub key: String,
+    pub fields: Vec<BibField>,
+    /// The code of the entry as it was read
+    #[serde(skip)]
+    pub(crate) raw: Option<String>,
+}
+
+impl BibEntry {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+            .map(|field| field.value.as_str())
+    }
+
+    fn to_bib(&self, macros: &[String]) -> String {
+        if let Some(raw) = &self.raw {
+            return raw.clone();
+        }
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|field| {
+                let value = match is_bare(&field.value, macros) {
+                    true => field.value.clone(),
+                    false => format!("{BLOCK_BEGIN}{}{BLOCK_END}", field.value),
+                };
+                format!("{FIELD_INDENTATION}{} = {value}", field.name)
+            })
+            .collect();
+        match fields.is_empty() {
+            true => format!("@{}{BLOCK_BEGIN}{},{BLOCK_END}", self.entry_type, self.key),
+            false => format!(
+                "@{}{BLOCK_BEGIN}{},\n{}\n{BLOCK_END}",
+                self.entry_type,
+                self.key,
+                fields.join(",\n")
+            ),
+        }
+    }
+}
+
+/// A field of an entry. The value is stored without the braces or quotes around it.
+/// Numbers, macros (like `jan`) and concatenations (`tug # " 2023"`) are stored as they are.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BibField {
+    /// The name in lowercase, e.g. `author`
+    pub name: String,
+    pub value: String,
+}
+
+/// Whether the value has to be written without braces.
+fn is_bare(value: &str, macros: &[String]) -> bool {
+    let value = value.trim();
+    (!value.is_empty() && value.chars().all(|c| c.is_ascii_digit()))
+        || parser::is_concatenation(value)
+        || MONTH_MACROS.contains(&value.to_lowercase().as_str())
+        || macros.iter().any(|name| name.eq_ignore_ascii_case(value))
+}
+
+impl TexlaAst {
+    /// Returns the paths of the `.bib` files used by `\bibliography` or `\addbibresource`.
+    pub fn bibliography_paths(&self) -> Result<Vec<String>, AstError> {
+        let latex = self.to_latex(StringificationOptions {
+            include_comments: false,
+            include_metadata: false,
+            ..Default::default()
+        })?;
+        Ok(bibliography_paths(&latex))
+    }
+}
+
+/// Returns the paths of the `.bib` files used in the LaTeX code in document order.
+pub(crate) fn bibliography_paths(latex: &str) -> Vec<String> {
+    let mut paths: Vec<String> = vec![];
+    for key in find_keys(latex, &[BIBLIOGRAPHY, ADD_BIB_RESOURCE]) {
+        let path = &latex[key];
+        let path = match path.ends_with(&format!(".{BIB_FILE_EXTENSION}")) {
+            true => path.to_string(),
+            false => format!("{path}.{BIB_FILE_EXTENSION}"),
+        };
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bibliography::{bibliography_paths, BibField, BibFile};
+
+    const BIB: &str = "% references of the paper\n@string{tug = \"TeX Users Group\"}\n\n@Book{knuth84,\n    title  = {The {\\TeX}book},\n    author = \"Donald E. Knuth\",\n    year   = 1984,\n    month  = feb\n}\n\n@article{lamport94,\n  publisher = tug # \" Press\",\n  title = {\\LaTeX: A Document Preparation System}\n}\n";
+
+    #[test]
+    fn parse_bib_file() {
+        let file = BibFile::parse("refs.bib".to_string(), BIB);
+        let entries: Vec<_> = file.entries().collect();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].entry_type, "book");
+        assert_eq!(entries[0].key, "knuth84");
+        assert_eq!(
+            entries[0].fields,
+            vec![
+                BibField {
+                    name: "title".to_string(),
+                    value: "The {\\TeX}book".to_string()
+                },
+                BibField {
+                    name: "author".to_string(),
+                    value: "Donald E. Knuth".to_string()
+                },
+                BibField {
+                    name: "year".to_string(),
+                    value: "1984".to_string()
+                },
+                BibField {
+                    name: "month".to_string(),
+                    value: "feb".to_string()
+                },
+            ]
+        );
+        assert_eq!(entries[1].field("publisher"), Some("tug # \" Press\""));
+        // unchanged files are written back as they are
+        assert_eq!(file.to_bib(), BIB);
+    }
+
+    #[test]
+    fn find_bibliography_paths() {
+        assert_eq!(
+            bibliography_paths("\\bibliography{refs,more/other.bib}\n\\addbibresource{refs.bib}"),
+            vec!["refs.bib", "more/other.bib"]
+        );
+    }
+}
@@ -0,0 +1,463 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::bibliography::{BibEntry, BibField, BibFile, Bibliography};
+use crate::errors::{AstError, OperationError, ParseError};
+
+/// Prefixes of DOIs given as URL, which are ignored when comparing them
+const DOI_PREFIXES: [&str; 3] = ["https://doi.org/", "http://dx.doi.org/", "doi:"];
+/// Characters which have to be escaped in BibTeX values
+const SPECIAL_CHARACTERS: [char; 5] = ['&', '%', '$', '#', '_'];
+const RIS_SEPARATOR: &str = "  -";
+const DEFAULT_KEY: &str = "entry";
+
+/// The formats in which reference managers export bibliographies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    BibTex,
+    Ris,
+    CslJson,
+}
+
+impl ImportFormat {
+    /// Determines the format by the extension of the file.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        match file_name.rsplit_once('.')?.1.to_lowercase().as_str() {
+            "bib" | "bibtex" => Some(Self::BibTex),
+            "ris" => Some(Self::Ris),
+            "json" => Some(Self::CslJson),
+            _ => None,
+        }
+    }
+}
+
+/// What happened to the entries of an imported file.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct ImportReport {
+    /// The path of the file the entries were added to
+    pub file: String,
+    /// The keys of the added entries
+    pub added: Vec<String>,
+    /// Entries which were skipped because the bibliography already contains them
+    pub duplicates: Vec<Duplicate>,
+    /// Entries which were added with another key because their key was already used
+    pub renamed: Vec<KeyCollision>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Duplicate {
+    pub key: String,
+    /// The key of the entry in the bibliography with the same DOI or title
+    pub existing_key: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct KeyCollision {
+    pub key: String,
+    pub new_key: String,
+}
+
+/// Reads the entries of a file in the given format. Converted entries are written in the
+/// default format, entries of BibTeX files keep their formatting.
+pub fn parse_entries(format: ImportFormat, content: &str) -> Result<Vec<BibEntry>, AstError> {
+    let entries = match format {
+        ImportFormat::BibTex => BibFile::parse(String::new(), content)
+            .entries()
+            .cloned()
+            .collect(),
+        ImportFormat::Ris => parse_ris(content),
+        ImportFormat::CslJson => parse_csl_json(content)?,
+    };
+    Ok(entries)
+}
+
+impl Bibliography {
+    /// Adds the entries to the main (first) file of the bibliography. Entries with the DOI or
+    /// title of an existing entry are skipped, colliding keys are replaced by unused ones.
+    pub fn import(&mut self, entries: Vec<BibEntry>) -> Result<ImportReport, OperationError> {
+        if self.files.is_empty() {
+            return Err(OperationError::InvalidArgument {
+                message: "the document has no bibliography file to import into".to_string(),
+            });
+        }
+        let mut report = ImportReport {
+            file: self.files[0].path.clone(),
+            ..Default::default()
+        };
+
+        for mut entry in entries {
+            let existing = self
+                .files
+                .iter()
+                .flat_map(BibFile::entries)
+                .find(|existing| {
+                    doi(existing).is_some_and(|existing| Some(existing) == doi(&entry))
+                        || title(existing).is_some_and(|existing| Some(existing) == title(&entry))
+                });
+            if let Some(existing) = existing {
+                report.duplicates.push(Duplicate {
+                    key: entry.key,
+                    existing_key: existing.key.clone(),
+                });
+                continue;
+            }
+
+            if self.entry(&entry.key).is_some() {
+                let new_key = ('a'..='z')
+                    .map(|suffix| format!("{}{suffix}", entry.key))
+                    .chain((2..).map(|number| format!("{}-{number}", entry.key)))
+                    .find(|key| self.entry(key).is_none())
+                    .unwrap();
+                report.renamed.push(KeyCollision {
+                    key: entry.key,
+                    new_key: new_key.clone(),
+                });
+                entry.key = new_key;
+                entry.raw = None;
+            }
+            report.added.push(entry.key.clone());
+            self.files[0].push_entry(entry);
+        }
+
+        Ok(report)
+    }
+}
+
+/// The DOI in lowercase without a URL prefix.
+fn doi(entry: &BibEntry) -> Option<String> {
+    let doi = entry.field("doi")?.trim().to_lowercase();
+    let doi = DOI_PREFIXES
+        .iter()
+        .find_map(|prefix| doi.strip_prefix(prefix))
+        .unwrap_or(&doi);
+    Some(doi.to_string()).filter(|doi| !doi.is_empty())
+}
+
+/// The title in lowercase without punctuation, braces and commands.
+fn title(entry: &BibEntry) -> Option<String> {
+    let title: String = entry
+        .field("title")?
+        .split_whitespace()
+        .filter(|word| !word.starts_with('\\'))
+        .flat_map(|word| word.chars().filter(|c| c.is_alphanumeric()))
+        .flat_map(char::to_lowercase)
+        .collect();
+    Some(title).filter(|title| !title.is_empty())
+}
+
+/// Parses the `TY  - ` ... `ER  - ` records of a RIS file.
+fn parse_ris(content: &str) -> Vec<BibEntry> {
+    let mut entries = vec![];
+    let mut record: Vec<(&str, &str)> = vec![];
+    for line in content.lines() {
+        let Some((tag, value)) = line.split_once(RIS_SEPARATOR) else {
+            continue;
+        };
+        let (tag, value) = (tag.trim(), value.trim());
+        match tag {
+            "TY" => record = vec![(tag, value)],
+            "ER" => entries.push(ris_entry(&record)),
+            _ => record.push((tag, value)),
+        }
+    }
+    entries
+}
+
+fn ris_entry(record: &[(&str, &str)]) -> BibEntry {
+    let values = |tags: &[&str]| -> Vec<&str> {
+        record
+            .iter()
+            .filter(|(tag, value)| tags.contains(tag) && !value.is_empty())
+            .map(|(_, value)| *value)
+            .collect()
+    };
+    let first = |tags: &[&str]| values(tags).first().map(|value| value.to_string());
+    let ris_type = first(&["TY"]).unwrap_or_default();
+    let entry_type = match ris_type.as_str() {
+        "JOUR" | "JFULL" | "MGZN" | "NEWS" => "article",
+        "BOOK" | "EBOOK" => "book",
+        "CHAP" | "ECHAP" => "incollection",
+        "CONF" | "CPAPER" => "inproceedings",
+        "THES" => "phdthesis",
+        "RPRT" => "techreport",
+        _ => "misc",
+    };
+    let container = match entry_type {
+        "article" => "journal",
+        _ => "booktitle",
+    };
+    let pages = match (first(&["SP"]), first(&["EP"])) {
+        (Some(start), Some(end)) => Some(format!("{start}--{end}")),
+        (start, _) => start,
+    };
+    let year = first(&["PY", "Y1", "DA"]).map(|date| date.chars().take(4).collect());
+
+    let authors = values(&["AU", "A1"]);
+    let editors = values(&["ED", "A2"]).join(" and ");
+    let fields = [
+        ("author", Some(authors.join(" and "))),
+        ("title", first(&["TI", "T1"])),
+        (container, first(&["T2", "JO", "JF", "BT"])),
+        ("editor", Some(editors)),
+        ("year", year),
+        ("volume", first(&["VL"])),
+        ("number", first(&["IS"])),
+        ("pages", pages),
+        ("publisher", first(&["PB"])),
+        ("address", first(&["CY"])),
+        ("doi", first(&["DO"])),
+        ("url", first(&["UR"])),
+        ("abstract", first(&["AB"])),
+    ];
+    let key = first(&["ID"]).unwrap_or_else(|| generate_key(authors.first().copied(), &fields));
+    new_entry(entry_type, key, fields)
+}
+
+/// Parses a CSL-JSON file, which is a list of items (or a single item).
+fn parse_csl_json(content: &str) -> Result<Vec<BibEntry>, ParseError> {
+    let json: Value = serde_json::from_str(content).map_err(|err| ParseError {
+        message: format!("invalid CSL-JSON: {err}"),
+    })?;
+    let items = match json {
+        Value::Array(items) => items,
+        item @ Value::Object(_) => vec![item],
+        _ => {
+            return Err(ParseError {
+                message: "CSL-JSON has to contain a list of items".to_string(),
+            })
+        }
+    };
+    Ok(items.iter().map(csl_entry).collect())
+}
+
+fn csl_entry(item: &Value) -> BibEntry {
+    let text = |name: &str| match &item[name] {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    };
+    let entry_type = match item["type"].as_str().unwrap_or_default() {
+        "article" | "article-journal" | "article-magazine" | "article-newspaper" => "article",
+        "book" => "book",
+        "chapter" => "incollection",
+        "paper-conference" => "inproceedings",
+        "thesis" => "phdthesis",
+        "report" => "techreport",
+        _ => "misc",
+    };
+    let container = match entry_type {
+        "article" => "journal",
+        _ => "booktitle",
+    };
+    let names = |name: &str| -> Vec<String> {
+        let Some(names) = item[name].as_array() else {
+            return vec![];
+        };
+        names
+            .iter()
+            .filter_map(
+                |name| match (name["family"].as_str(), name["given"].as_str()) {
+                    (Some(family), Some(given)) => Some(format!("{family}, {given}")),
+                    (Some(family), None) => Some(family.to_string()),
+                    _ => name["literal"]
+                        .as_str()
+                        .map(|literal| format!("{{{literal}}}")),
+                },
+            )
+            .collect()
+    };
+    let year = item["issued"]["date-parts"][0][0]
+        .as_i64()
+        .map(|year| year.to_string())
+        .or_else(|| item["issued"]["literal"].as_str().map(str::to_string));
+
+    let authors = names("author");
+    let fields = [
+        ("author", Some(authors.join(" and "))),
+        ("title", text("title")),
+        (container, text("container-title")),
+        ("editor", Some(names("editor").join(" and "))),
+        ("year", year),
+        ("volume", text("volume")),
+        ("number", text("issue")),
+        ("pages", text("page").map(|pages| pages.replace('-', "--"))),
+        ("publisher", text("publisher")),
+        ("address", text("publisher-place")),
+        ("doi", text("DOI")),
+        ("url", text("URL")),
+        ("isbn", text("ISBN")),
+        ("abstract", text("abstract")),
+    ];
+    let key = text("id")
+        .filter(|id| !id.contains(char::is_whitespace))
+        .unwrap_or_else(|| generate_key(authors.first().map(String::as_str), &fields));
+    new_entry(entry_type, key, fields)
+}
+
+/// Creates an entry with the given fields, which are escaped for BibTeX. Empty fields are skipped.
+fn new_entry<const N: usize>(
+    entry_type: &str,
+    key: String,
+    fields: [(&str, Option<String>); N],
+) -> BibEntry {
+    BibEntry {
+        entry_type: entry_type.to_string(),
+        key,
+        fields: fields
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let value = value.filter(|value| !value.trim().is_empty())?;
+                Some(BibField {
+                    name: name.to_string(),
+                    value: match name {
+                        "url" | "doi" => value,
+                        _ => escape(&value),
+                    },
+                })
+            })
+            .collect(),
+        raw: None,
+    }
+}
+
+/// Generates a key like `knuth1984` from the family name of the first author and the year.
+fn generate_key(author: Option<&str>, fields: &[(&str, Option<String>)]) -> String {
+    let family_name = author
+        .map(|author| author.split(',').next().unwrap_or(author))
+        .unwrap_or(DEFAULT_KEY);
+    let year = fields
+        .iter()
+        .find(|(name, _)| *name == "year")
+        .and_then(|(_, year)| year.clone())
+        .unwrap_or_default();
+    let key: String = format!("{family_name}{year}")
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    match key.is_empty() {
+        true => DEFAULT_KEY.to_string(),
+        false => key,
+    }
+}
+
+/// Escapes the characters which have a special meaning in LaTeX (unless they already are).
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let mut previous = None;
+    for char in value.chars() {
+        if SPECIAL_CHARACTERS.contains(&char) && previous != Some('\\') {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+        previous = Some(char);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bibliography::import::{
+        parse_entries, Duplicate, ImportFormat, ImportReport, KeyCollision,
+    };
+    use crate::bibliography::{BibFile, Bibliography};
+
+    const RIS: &str = "TY  - JOUR\nAU  - Doe, Jane\nAU  - Roe, John\nTI  - Graphs & Trees\nT2  - Journal of Things\nPY  - 2021/05/01\nSP  - 10\nEP  - 20\nDO  - 10.1000/xyz\nER  - \n\nTY  - BOOK\nID  - knuth84\nTI  - The TeXbook\nPY  - 1984\nER  - \n";
+    const CSL_JSON: &str = r#"[
+        {"id": "lamport94", "type": "book", "title": "LaTeX: A Document Preparation System",
+         "author": [{"family": "Lamport", "given": "Leslie"}], "issued": {"date-parts": [[1994]]}},
+        {"id": "doe21", "type": "article-journal", "title": "Graphs and Trees", "DOI": "https://doi.org/10.1000/XYZ",
+         "page": "10-20"}
+    ]"#;
+
+    #[test]
+    fn parse_ris() {
+        let entries = parse_entries(ImportFormat::Ris, RIS).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].key, "doe2021");
+        assert_eq!(entries[0].entry_type, "article");
+        assert_eq!(entries[0].field("author"), Some("Doe, Jane and Roe, John"));
+        assert_eq!(entries[0].field("title"), Some("Graphs \\& Trees"));
+        assert_eq!(entries[0].field("journal"), Some("Journal of Things"));
+        assert_eq!(entries[0].field("pages"), Some("10--20"));
+        assert_eq!(entries[1].key, "knuth84");
+        assert_eq!(entries[1].entry_type, "book");
+    }
+
+    #[test]
+    fn parse_csl_json() {
+        let entries = parse_entries(ImportFormat::CslJson, CSL_JSON).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].key, "lamport94");
+        assert_eq!(entries[0].field("author"), Some("Lamport, Leslie"));
+        assert_eq!(entries[0].field("year"), Some("1994"));
+        assert_eq!(entries[1].entry_type, "article");
+        assert_eq!(entries[1].field("pages"), Some("10--20"));
+        assert!(parse_entries(ImportFormat::CslJson, "no json").is_err());
+    }
+
+    #[test]
+    fn import_entries() {
+        let mut bibliography = Bibliography {
+            files: vec![BibFile::parse(
+                "refs.bib".to_string(),
+                "@book{knuth84,\n  title = {The {\\TeX}book}\n}\n",
+            )],
+        };
+        let mut entries = parse_entries(ImportFormat::Ris, RIS).unwrap();
+        entries.extend(parse_entries(ImportFormat::CslJson, CSL_JSON).unwrap());
+        entries.extend(
+            parse_entries(
+                ImportFormat::BibTex,
+                "@misc{knuth84,\n  title = {Something else}\n}\n",
+            )
+            .unwrap(),
+        );
+
+        let report = bibliography.import(entries).unwrap();
+
+        assert_eq!(
+            report,
+            ImportReport {
+                file: "refs.bib".to_string(),
+                added: vec![
+                    "doe2021".to_string(),
+                    "lamport94".to_string(),
+                    "knuth84a".to_string()
+                ],
+                duplicates: vec![
+                    Duplicate {
+                        key: "knuth84".to_string(),
+                        existing_key: "knuth84".to_string()
+                    },
+                    Duplicate {
+                        key: "doe21".to_string(),
+                        existing_key: "doe2021".to_string()
+                    },
+                ],
+                renamed: vec![KeyCollision {
+                    key: "knuth84".to_string(),
+                    new_key: "knuth84a".to_string()
+                }],
+            }
+        );
+        assert!(bibliography.files[0]
+            .to_bib()
+            .ends_with("@misc{knuth84a,\n  title = {Something else}\n}\n"));
+    }
+
+    #[test]
+    fn detect_format() {
+        assert_eq!(
+            ImportFormat::from_file_name("export.RIS"),
+            Some(ImportFormat::Ris)
+        );
+        assert_eq!(
+            ImportFormat::from_file_name("zotero.json"),
+            Some(ImportFormat::CslJson)
+        );
+        assert_eq!(ImportFormat::from_file_name("refs"), None);
+    }
+}
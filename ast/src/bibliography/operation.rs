@@ -0,0 +1,260 @@
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bibliography::{BibEntry, Bibliography};
+use crate::errors::OperationError;
+
+/// Structs that implement this Trait can modify a [Bibliography] in some way.
+/// This specifies the Operation Interface in the Strategy pattern, like
+/// [crate::operation::Operation] does for the Ast.
+pub trait BibOperation: Send + Sync + Debug {
+    /// Execute this Operation on the [Bibliography].
+    /// Returns the path of the file which has to be written.
+    fn execute_on(&self, bibliography: &mut Bibliography) -> Result<String, OperationError>;
+}
+
+/// Enum to represent the different Operations on a [Bibliography].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum JsonBibOperation {
+    AddEntry { arguments: AddEntry },
+    EditEntry { arguments: EditEntry },
+    DeleteEntry { arguments: DeleteEntry },
+}
+
+impl JsonBibOperation {
+    /// This maps the `JsonBibOperation` to the equivalent Trait Object.
+    pub fn to_trait_obj(self) -> Box<dyn BibOperation> {
+        match self {
+            JsonBibOperation::AddEntry {
+                arguments: operation,
+            } => Box::new(operation),
+            JsonBibOperation::EditEntry {
+                arguments: operation,
+            } => Box::new(operation),
+            JsonBibOperation::DeleteEntry {
+                arguments: operation,
+            } => Box::new(operation),
+        }
+    }
+}
+
+/// Appends an entry to a file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AddEntry {
+    /// The path of the file (the first file of the bibliography if there is none)
+    #[serde(default)]
+    pub file: Option<String>,
+    pub entry: BibEntry,
+}
+
+impl BibOperation for AddEntry {
+    fn execute_on(&self, bibliography: &mut Bibliography) -> Result<String, OperationError> {
+        validate_key(bibliography, &self.entry, None)?;
+        let file = match &self.file {
+            Some(path) => bibliography
+                .files
+                .iter_mut()
+                .find(|file| &file.path == path),
+            None => bibliography.files.first_mut(),
+        }
+        .ok_or_else(|| OperationError::InvalidArgument {
+            message: "the document has no bibliography file with this path".to_string(),
+        })?;
+
+        file.push_entry(new_entry(&self.entry));
+        Ok(file.path.clone())
+    }
+}
+
+/// Replaces an entry (including its key) by a new one.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EditEntry {
+    pub key: String,
+    pub entry: BibEntry,
+}
+
+impl BibOperation for EditEntry {
+    fn execute_on(&self, bibliography: &mut Bibliography) -> Result<String, OperationError> {
+        validate_key(bibliography, &self.entry, Some(&self.key))?;
+        let file = bibliography
+            .file_of_entry_mut(&self.key)
+            .ok_or_else(|| unknown_entry(&self.key))?;
+
+        file.replace_entry(&self.key, new_entry(&self.entry));
+        Ok(file.path.clone())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeleteEntry {
+    pub key: String,
+}
+
+impl BibOperation for DeleteEntry {
+    fn execute_on(&self, bibliography: &mut Bibliography) -> Result<String, OperationError> {
+        let file = bibliography
+            .file_of_entry_mut(&self.key)
+            .ok_or_else(|| unknown_entry(&self.key))?;
+
+        file.remove_entry(&self.key);
+        Ok(file.path.clone())
+    }
+}
+
+/// Checks that the key of `entry` is valid and not used by another entry than the one with the
+/// key `replaced`.
+fn validate_key(
+    bibliography: &Bibliography,
+    entry: &BibEntry,
+    replaced: Option<&str>,
+) -> Result<(), OperationError> {
+    if entry.key.is_empty()
+        || entry
+            .key
+            .contains(|c: char| c.is_whitespace() || "{}(),\"#%".contains(c))
+    {
+        return Err(OperationError::InvalidArgument {
+            message: format!("'{}' is no valid key", entry.key),
+        });
+    }
+    if Some(entry.key.as_str()) != replaced && bibliography.entry(&entry.key).is_some() {
+        return Err(OperationError::InvalidArgument {
+            message: format!("there already is an entry with the key '{}'", entry.key),
+        });
+    }
+    Ok(())
+}
+
+/// Normalizes an entry from a client, which is written in the default format.
+fn new_entry(entry: &BibEntry) -> BibEntry {
+    BibEntry {
+        entry_type: entry.entry_type.to_lowercase(),
+        key: entry.key.clone(),
+        fields: entry
+            .fields
+            .iter()
+            .filter(|field| !field.value.trim().is_empty())
+            .map(|field| crate::bibliography::BibField {
+                name: field.name.to_lowercase(),
+                value: field.value.clone(),
+            })
+            .collect(),
+        raw: None,
+    }
+}
+
+fn unknown_entry(key: &str) -> OperationError {
+    OperationError::InvalidArgument {
+        message: format!("there is no entry with the key '{key}'"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bibliography::operation::{AddEntry, DeleteEntry, EditEntry};
+    use crate::bibliography::{BibEntry, BibField, BibFile, Bibliography};
+
+    fn bibliography() -> Bibliography {
+        Bibliography {
+            files: vec![BibFile::parse(
+                "refs.bib".to_string(),
+                "@string{tug = \"TeX Users Group\"}\n\n@book{knuth84,\n  title = {The {\\TeX}book}\n}\n\n% keep me\n@misc{other, note = {x}}\n",
+            )],
+        }
+    }
+
+    fn entry(key: &str, fields: &[(&str, &str)]) -> BibEntry {
+        BibEntry {
+            entry_type: "Article".to_string(),
+            key: key.to_string(),
+            fields: fields
+                .iter()
+                .map(|(name, value)| BibField {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn add_entry() {
+        let mut bibliography = bibliography();
+        let operation = AddEntry {
+            file: None,
+            entry: entry(
+                "lamport94",
+                &[
+                    ("Title", "{\\LaTeX}"),
+                    ("publisher", "tug"),
+                    ("year", "1994"),
+                ],
+            ),
+        };
+
+        assert_eq!(
+            bibliography.execute(Box::new(operation.clone())),
+            Ok("refs.bib".to_string())
+        );
+        assert_eq!(
+            bibliography.files[0].to_bib(),
+            "@string{tug = \"TeX Users Group\"}\n\n@book{knuth84,\n  title = {The {\\TeX}book}\n}\n\n% keep me\n@misc{other, note = {x}}\n\n@article{lamport94,\n  title = {{\\LaTeX}},\n  publisher = tug,\n  year = 1994\n}\n"
+        );
+        // keys are unique
+        assert!(bibliography.execute(Box::new(operation)).is_err());
+    }
+
+    #[test]
+    fn edit_entry() {
+        let mut bibliography = bibliography();
+        let operation = EditEntry {
+            key: "knuth84".to_string(),
+            entry: entry("knuth86", &[("title", "The TeXbook"), ("note", "")]),
+        };
+
+        bibliography.execute(Box::new(operation)).unwrap();
+        assert_eq!(
+            bibliography.files[0].to_bib(),
+            "@string{tug = \"TeX Users Group\"}\n\n@article{knuth86,\n  title = {The TeXbook}\n}\n\n% keep me\n@misc{other, note = {x}}\n"
+        );
+
+        let operation = EditEntry {
+            key: "knuth86".to_string(),
+            entry: entry("other", &[]),
+        };
+        assert!(bibliography.execute(Box::new(operation)).is_err());
+    }
+
+    #[test]
+    fn delete_entry() {
+        let mut bibliography = bibliography();
+
+        bibliography
+            .execute(Box::new(DeleteEntry {
+                key: "knuth84".to_string(),
+            }))
+            .unwrap();
+        assert_eq!(
+            bibliography.files[0].to_bib(),
+            "@string{tug = \"TeX Users Group\"}\n\n% keep me\n@misc{other, note = {x}}\n"
+        );
+
+        bibliography
+            .execute(Box::new(DeleteEntry {
+                key: "other".to_string(),
+            }))
+            .unwrap();
+        assert_eq!(
+            bibliography.files[0].to_bib(),
+            "@string{tug = \"TeX Users Group\"}\n\n% keep me\n"
+        );
+        assert!(bibliography
+            .execute(Box::new(DeleteEntry {
+                key: "other".to_string(),
+            }))
+            .is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::bibliography::{BibChunk, BibEntry, BibField};
+
+const ENTRY_PREFIX: char = '@';
+const CONCATENATION: char = '#';
+/// These are not entries and are kept as text
+const STRING_TYPE: &str = "string";
+const PREAMBLE_TYPE: &str = "preamble";
+const COMMENT_TYPE: &str = "comment";
+
+/// Splits the content of a `.bib` file into entries and the text between them. Returns the
+/// chunks together with the names of the `@string`s. Entries which cannot be read are kept as text.
+pub(super) fn parse_chunks(content: &str) -> (Vec<BibChunk>, Vec<String>) {
+    let mut chunks = vec![];
+    let mut macros = vec![];
+    let mut text_start = 0;
+    let mut position = 0;
+
+    while let Some(offset) = content[position..].find(ENTRY_PREFIX) {
+        let start = position + offset;
+        position = start + 1;
+        let Some((entry_type, body)) = entry_body(content, start) else {
+            continue;
+        };
+        let end = body.end + 1;
+        position = end;
+
+        match entry_type.as_str() {
+            STRING_TYPE => {
+                macros.extend(parse_fields(&content[body]).into_iter().map(|f| f.name));
+                continue;
+            }
+            PREAMBLE_TYPE | COMMENT_TYPE => continue,
+            _ => {}
+        }
+        let body = &content[body];
+        let (key, fields) = match body.split_once(',') {
+            Some((key, fields)) => (key.trim(), parse_fields(fields)),
+            None => (body.trim(), vec![]),
+        };
+        if key.is_empty() {
+            continue;
+        }
+
+        if text_start < start {
+            chunks.push(BibChunk::Text(content[text_start..start].to_string()));
+        }
+        chunks.push(BibChunk::Entry(BibEntry {
+            entry_type,
+            key: key.to_string(),
+            fields,
+            raw: Some(content[start..end].to_string()),
+        }));
+        text_start = end;
+    }
+
+    if text_start < content.len() {
+        chunks.push(BibChunk::Text(content[text_start..].to_string()));
+    }
+    (chunks, macros)
+}
+
+/// Returns the lowercase type and the range of the body between the delimiters of the entry
+/// starting at `start`.
+fn entry_body(content: &str, start: usize) -> Option<(String, std::ops::Range<usize>)> {
+    let rest = &content[start + 1..];
+    let type_length = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(rest.len());
+    if type_length == 0 {
+        return None;
+    }
+    let entry_type = rest[..type_length].to_lowercase();
+    let after_type = &rest[type_length..];
+    let delimiter_offset = after_type.len() - after_type.trim_start().len();
+    let open = start + 1 + type_length + delimiter_offset;
+    let close_delimiter = match content[open..].chars().next()? {
+        '{' => '}',
+        '(' => ')',
+        _ => return None,
+    };
+
+    let mut depth = 0;
+    for (index, char) in content[open + 1..].char_indices() {
+        match char {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            _ if char == close_delimiter && depth == 0 => {
+                return Some((entry_type, open + 1..open + 1 + index));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parses comma separated `name = value` pairs (names are converted to lowercase).
+fn parse_fields(fields: &str) -> Vec<BibField> {
+    let mut parsed = vec![];
+    let mut rest = fields;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        let Some((name, after_name)) = rest.split_once('=') else {
+            break;
+        };
+        let value_end = value_end(after_name);
+        parsed.push(BibField {
+            name: name.trim().to_lowercase(),
+            value: unwrap_value(after_name[..value_end].trim()).to_string(),
+        });
+        rest = &after_name[value_end..];
+    }
+    parsed
+}
+
+/// Returns the end of the value at the start of `value`: the first comma outside of braces and
+/// quotes.
+fn value_end(value: &str) -> usize {
+    let mut depth = 0;
+    let mut quoted = false;
+    for (index, char) in value.char_indices() {
+        match char {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '"' if depth == 0 => quoted = !quoted,
+            ',' if depth == 0 && !quoted => return index,
+            _ => {}
+        }
+    }
+    value.len()
+}
+
+/// Removes the braces or quotes around a value, unless it consists of several parts.
+fn unwrap_value(value: &str) -> &str {
+    if is_concatenation(value) {
+        return value;
+    }
+    let Some(open) = value.chars().next().filter(|c| *c == '{' || *c == '"') else {
+        return value;
+    };
+    // the delimiter opened first has to be closed at the end, e.g. not in `{a} and {b}`
+    let mut depth = 0;
+    for (index, char) in value.char_indices().skip(1) {
+        match char {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            '}' | '"' if depth == 0 && (char == '}') == (open == '{') => {
+                return match index == value.len() - 1 {
+                    true => &value[1..index],
+                    false => value,
+                };
+            }
+            _ => {}
+        }
+    }
+    value
+}
+
+/// Whether the value concatenates several parts with `#` (outside of braces and quotes).
+pub(super) fn is_concatenation(value: &str) -> bool {
+    let mut depth = 0;
+    let mut quoted = false;
+    value.chars().any(|char| {
+        match char {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '"' if depth == 0 => quoted = !quoted,
+            _ => return char == CONCATENATION && depth == 0 && !quoted,
+        }
+        false
+    })
+}
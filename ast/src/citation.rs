@@ -0,0 +1,148 @@
+//! `citation` finds citation commands (`\cite`, `\citep`, ...) in LaTeX code and keeps track of
+//! the cited keys.
+use std::collections::HashMap;
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::latex_constants::*;
+use crate::node::{visit_subtree, Node};
+use crate::reference_index::find_commands;
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::Uuid;
+
+/// The key of `\nocite{*}`, which adds all entries to the bibliography
+pub(crate) const ALL_KEYS: &str = "*";
+
+/// A citation command inside the text of a node, e.g. `\citep[see][p.~5]{knuth84,lamport94}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Citation {
+    /// The command including a star, e.g. `\citet*`
+    pub command: String,
+    pub keys: Vec<String>,
+    /// The note in front of the citation (the first of two optional arguments)
+    pub prenote: Option<String>,
+    /// The note after the citation (the last optional argument)
+    pub postnote: Option<String>,
+    /// The byte range of the whole command in the text
+    pub range: Range<usize>,
+}
+
+/// Finds all citation commands in `latex`. Commands without an argument are skipped.
+pub(crate) fn parse_citations(latex: &str) -> Vec<Citation> {
+    find_commands(latex, &CITATION_COMMANDS)
+        .into_iter()
+        .map(|found| {
+            let notes: Vec<&str> = found
+                .options
+                .iter()
+                .map(|range| latex[range.clone()].trim())
+                .collect();
+            let note = |note: &str| Some(note.to_string()).filter(|note| !note.is_empty());
+            let (prenote, postnote) = match notes[..] {
+                [] => (None, None),
+                [postnote] => (None, note(postnote)),
+                [.., prenote, postnote] => (note(prenote), note(postnote)),
+            };
+            Citation {
+                command: latex[found.range.start..found.name_end].to_string(),
+                keys: latex[found.argument]
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|key| !key.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                prenote,
+                postnote,
+                range: found.range,
+            }
+        })
+        .collect()
+}
+
+/// Maps every cited key to the nodes citing it (in document order). The index spans all files of
+/// the document.
+#[derive(Debug, Default, Serialize)]
+pub struct CitationIndex {
+    pub citations: HashMap<String, Vec<Uuid>>,
+    /// The node printing the bibliography (`\bibliography` or `\printbibliography`)
+    pub bibliography_node: Option<Uuid>,
+}
+
+impl CitationIndex {
+    pub fn new(ast: &TexlaAst) -> Self {
+        let mut index = Self::default();
+        visit_subtree(&ast.root, &mut |node| index.add(node));
+        index
+    }
+
+    fn add(&mut self, node: &mut Node) {
+        let uuid = node.uuid;
+        if let Some(content) = node.node_type.latex_content_mut() {
+            for citation in parse_citations(content) {
+                for key in citation.keys {
+                    self.citations.entry(key).or_default().push(uuid);
+                }
+            }
+            if self.bibliography_node.is_none()
+                && [BIBLIOGRAPHY, PRINT_BIBLIOGRAPHY]
+                    .iter()
+                    .any(|command| content.contains(command))
+            {
+                self.bibliography_node = Some(uuid);
+            }
+        }
+    }
+
+    /// Whether all entries are part of the bibliography because of `\nocite{*}`.
+    pub fn cites_all(&self) -> bool {
+        self.citations.contains_key(ALL_KEYS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::citation::{parse_citations, Citation, CitationIndex};
+    use crate::operation::test::find_uuid_by_content;
+    use crate::parser::parse_latex;
+
+    #[test]
+    fn parse_citation_commands() {
+        let latex = r"As \citet{knuth84} and \citep[see][p.~5]{a, b} show \cite[ch. 2]{c}, \citeauthor*{d} \citename{e}";
+        let citations = parse_citations(latex);
+
+        assert_eq!(citations.len(), 4);
+        assert_eq!(
+            citations[1],
+            Citation {
+                command: "\\citep".to_string(),
+                keys: vec!["a".to_string(), "b".to_string()],
+                prenote: Some("see".to_string()),
+                postnote: Some("p.~5".to_string()),
+                range: 23..46,
+            }
+        );
+        assert_eq!(
+            &latex[citations[1].range.clone()],
+            "\\citep[see][p.~5]{a, b}"
+        );
+        assert_eq!(citations[2].prenote, None);
+        assert_eq!(citations[2].postnote, Some("ch. 2".to_string()));
+        assert_eq!(citations[3].command, "\\citeauthor*");
+    }
+
+    #[test]
+    fn index_citations() {
+        let latex = "\\begin{document}\n\\section{Intro \\cite{a}}\nText \\citep{a,b}.\n\n\\bibliography{refs}\n\\end{document}\n";
+        let ast = parse_latex(latex.to_string()).expect("Valid Latex");
+        let index = CitationIndex::new(&ast);
+
+        assert_eq!(index.citations["a"].len(), 2);
+        assert_eq!(index.citations["b"].len(), 1);
+        assert_eq!(
+            index.bibliography_node,
+            find_uuid_by_content(&ast, "\\bibliography{refs}")
+        );
+        assert!(!index.cites_all());
+    }
+}
@@ -0,0 +1,190 @@
+//! Conflict markers (see [crate::merge]) may enclose arbitrary parts of a document, which is why
+//! they cannot be handled by the regular parser. Instead, every conflict is replaced by a
+//! placeholder environment before parsing. Both sides of the conflict are parsed on their own and
+//! inserted as a Conflict node in place of the placeholder afterwards.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::errors::ParseError;
+use crate::latex_constants::{BEGIN, DOCUMENT_BEGIN, DOCUMENT_END, END};
+use crate::merge::{CONFLICT_BEGIN_MARK, CONFLICT_END_MARK, CONFLICT_SEPARATOR_MARK};
+use crate::node::{ExpandableData, Node, NodeRef, NodeType};
+use crate::parser::parse_latex;
+use crate::texla_ast::TexlaAst;
+
+const PLACEHOLDER_ENVIRONMENT: &str = "TeXLaConflict";
+
+pub(crate) struct ExtractedConflict {
+    ours: String,
+    theirs: String,
+    /// The conflict including its markers
+    raw: String,
+}
+
+enum State {
+    Outside,
+    Ours,
+    Theirs,
+}
+
+/// Replaces all conflicts in `latex` by placeholders and returns them in order.
+pub(crate) fn extract_conflicts(
+    latex: &str,
+) -> Result<(String, Vec<ExtractedConflict>), ParseError> {
+    let mut without_conflicts = String::new();
+    let mut conflicts = vec![];
+    let mut state = State::Outside;
+    let (mut ours, mut theirs, mut raw) = (String::new(), String::new(), String::new());
+
+    for line in latex.split_inclusive('\n') {
+        let marker = line.trim_end();
+        match state {
+            State::Outside if marker == CONFLICT_BEGIN_MARK => {
+                without_conflicts += &placeholder(conflicts.len());
+                state = State::Ours;
+            }
+            State::Outside => {
+                without_conflicts += line;
+                continue;
+            }
+            State::Ours if marker == CONFLICT_SEPARATOR_MARK => state = State::Theirs,
+            State::Theirs if marker == CONFLICT_END_MARK => {
+                raw += line;
+                conflicts.push(ExtractedConflict {
+                    ours: std::mem::take(&mut ours),
+                    theirs: std::mem::take(&mut theirs),
+                    raw: std::mem::take(&mut raw),
+                });
+                state = State::Outside;
+                continue;
+            }
+            _ if marker == CONFLICT_BEGIN_MARK => {
+                return Err(ParseError {
+                    message: "Nested merge conflicts are not supported".to_string(),
+                })
+            }
+            State::Ours => ours += line,
+            State::Theirs => theirs += line,
+        }
+        raw += line;
+    }
+
+    match state {
+        State::Outside => Ok((without_conflicts, conflicts)),
+        _ => Err(ParseError {
+            message: "Found a merge conflict without end marker".to_string(),
+        }),
+    }
+}
+
+fn placeholder(index: usize) -> String {
+    format!(
+        "{BEGIN}{{{PLACEHOLDER_ENVIRONMENT}{index}}}\n{END}{{{PLACEHOLDER_ENVIRONMENT}{index}}}\n"
+    )
+}
+
+/// Replaces the placeholders in `ast` by Conflict nodes.
+/// Conflicts in the preamble cannot be represented as nodes, so they are kept as they are.
+pub(crate) fn insert_conflicts(
+    ast: &mut TexlaAst,
+    conflicts: Vec<ExtractedConflict>,
+) -> Result<(), ParseError> {
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    let mut conflict_nodes = HashMap::new();
+    for (index, conflict) in conflicts.iter().enumerate() {
+        let sides = vec![
+            parse_side(ast, &conflict.ours, true)?,
+            parse_side(ast, &conflict.theirs, false)?,
+        ];
+        let conflict_node = Node::new_expandable(
+            ExpandableData::Conflict,
+            sides,
+            &mut ast.uuid_provider,
+            &mut ast.portal,
+            conflict.raw.clone(),
+            HashMap::new(),
+        );
+        conflict_nodes.insert(format!("{PLACEHOLDER_ENVIRONMENT}{index}"), conflict_node);
+    }
+
+    replace_placeholders(&ast.root, &mut conflict_nodes);
+
+    if let NodeType::Expandable {
+        data: ExpandableData::Document { preamble, .. },
+        ..
+    } = &mut ast.root.lock().unwrap().node_type
+    {
+        for (index, conflict) in conflicts.iter().enumerate() {
+            if preamble.contains(&placeholder(index)) {
+                *preamble = preamble.replacen(&placeholder(index), &conflict.raw, 1);
+                conflict_nodes.remove(&format!("{PLACEHOLDER_ENVIRONMENT}{index}"));
+            }
+        }
+    }
+
+    if conflict_nodes.is_empty() {
+        Ok(())
+    } else {
+        Err(ParseError {
+            message: "Found a merge conflict inside of a LaTeX element".to_string(),
+        })
+    }
+}
+
+/// Parses one side of a conflict and returns a ConflictSide node containing it.
+fn parse_side(ast: &mut TexlaAst, latex: &str, ours: bool) -> Result<NodeRef, ParseError> {
+    let side_ast =
+        parse_latex(format!("{DOCUMENT_BEGIN}\n{latex}{DOCUMENT_END}\n")).map_err(|err| {
+            ParseError {
+                message: format!(
+                    "Could not parse one side of a merge conflict: {}",
+                    err.message
+                ),
+            }
+        })?;
+    ast.portal.extend(side_ast.portal);
+    let children = match &side_ast.root.lock().unwrap().node_type {
+        NodeType::Expandable { children, .. } => children.clone(),
+        NodeType::Leaf { .. } => vec![],
+    };
+
+    Ok(Node::new_expandable(
+        ExpandableData::ConflictSide {
+            ours,
+            level: side_ast.highest_level,
+        },
+        children,
+        &mut ast.uuid_provider,
+        &mut ast.portal,
+        latex.to_string(),
+        HashMap::new(),
+    ))
+}
+
+fn replace_placeholders(node_ref: &NodeRef, conflict_nodes: &mut HashMap<String, NodeRef>) {
+    let children = match &mut node_ref.lock().unwrap().node_type {
+        NodeType::Expandable { children, .. } => {
+            for child_ref in children.iter_mut() {
+                let name = match &child_ref.lock().unwrap().node_type {
+                    NodeType::Expandable {
+                        data: ExpandableData::Environment { name },
+                        ..
+                    } => name.clone(),
+                    _ => continue,
+                };
+                if let Some(conflict_ref) = conflict_nodes.remove(&name) {
+                    conflict_ref.lock().unwrap().parent = Some(Arc::downgrade(node_ref));
+                    *child_ref = conflict_ref;
+                }
+            }
+            children.clone()
+        }
+        NodeType::Leaf { .. } => vec![],
+    };
+    for child_ref in &children {
+        replace_placeholders(child_ref, conflict_nodes);
+    }
+}
@@ -0,0 +1,193 @@
+//! `diagnostics` reports problems in a document that can be found without compiling it.
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::bibliography::Bibliography;
+use crate::citation::CitationIndex;
+use crate::citation::ALL_KEYS;
+use crate::reference_index::ReferenceIndex;
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::Uuid;
+
+/// A problem attached to the Node with the given `uuid`, which is shown to the user by the frontend.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub uuid: Uuid,
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum DiagnosticKind {
+    UndefinedReference,
+    DuplicateLabel,
+    UnusedLabel,
+    UndefinedCitation,
+    UnusedBibEntry,
+    /// Reported by the TeX engine while building the document
+    Compilation,
+}
+
+/// Collects all diagnostics for the given Ast, ordered by kind and label.
+/// Citations are only checked if the `bibliography` contains any files.
+pub fn diagnostics(ast: &TexlaAst, bibliography: &Bibliography) -> Vec<Diagnostic> {
+    let mut diagnostics = cross_reference_diagnostics(&ReferenceIndex::new(ast));
+    if !bibliography.files.is_empty() {
+        diagnostics.extend(citation_diagnostics(&CitationIndex::new(ast), bibliography));
+    }
+    diagnostics
+}
+
+/// Reports references to undefined labels, labels which are defined more than once and labels
+/// which are never referenced.
+pub fn cross_reference_diagnostics(index: &ReferenceIndex) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    for (label, nodes) in sorted(&index.references) {
+        if index.target(label).is_none() {
+            diagnostics.extend(nodes.iter().map(|uuid| Diagnostic {
+                uuid: *uuid,
+                severity: Severity::Warning,
+                kind: DiagnosticKind::UndefinedReference,
+                message: format!("Reference to undefined label '{label}'"),
+            }));
+        }
+    }
+
+    for (label, nodes) in sorted(&index.labels) {
+        if nodes.len() > 1 {
+            diagnostics.extend(nodes.iter().map(|uuid| Diagnostic {
+                uuid: *uuid,
+                severity: Severity::Warning,
+                kind: DiagnosticKind::DuplicateLabel,
+                message: format!("Label '{label}' is defined {} times", nodes.len()),
+            }));
+        }
+    }
+
+    for (label, nodes) in sorted(&index.labels) {
+        if !index.references.contains_key(label) {
+            diagnostics.extend(nodes.iter().map(|uuid| Diagnostic {
+                uuid: *uuid,
+                severity: Severity::Info,
+                kind: DiagnosticKind::UnusedLabel,
+                message: format!("Label '{label}' is never referenced"),
+            }));
+        }
+    }
+
+    diagnostics
+}
+
+/// Reports citations of keys which are not in the bibliography and entries of the bibliography
+/// which are never cited. The latter are attached to the node printing the bibliography.
+pub fn citation_diagnostics(index: &CitationIndex, bibliography: &Bibliography) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    for (key, nodes) in sorted(&index.citations) {
+        if bibliography.entry(key).is_none() && key != ALL_KEYS {
+            diagnostics.extend(nodes.iter().map(|uuid| Diagnostic {
+                uuid: *uuid,
+                severity: Severity::Warning,
+                kind: DiagnosticKind::UndefinedCitation,
+                message: format!("Citation of unknown key '{key}'"),
+            }));
+        }
+    }
+
+    if let (Some(uuid), false) = (index.bibliography_node, index.cites_all()) {
+        for file in &bibliography.files {
+            for entry in file.entries() {
+                if !index.citations.contains_key(&entry.key) {
+                    diagnostics.push(Diagnostic {
+                        uuid,
+                        severity: Severity::Info,
+                        kind: DiagnosticKind::UnusedBibEntry,
+                        message: format!("Entry '{}' of '{}' is never cited", entry.key, file.path),
+                    });
+                }
+            }
+        }
+    }
+
+    diagnostics
+}
+
+fn sorted<V>(map: &HashMap<String, V>) -> Vec<(&String, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_key(|(key, _)| *key);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::bibliography::{BibFile, Bibliography};
+    use crate::diagnostics::{diagnostics, DiagnosticKind};
+    use crate::operation::test::find_uuid_by_content;
+    use crate::parser::parse_latex;
+
+    #[test]
+    fn cross_reference_diagnostics() {
+        let latex = fs::read_to_string("../test_resources/latex/references.tex")
+            .unwrap()
+            .replace(
+                "\\section{Conclusion}",
+                "\\section{Conclusion}\n\\label{sec:intro}\n\\label{sec:unused}",
+            );
+        let ast = parse_latex(latex).expect("Valid Latex");
+        let diagnostics = diagnostics(&ast, &Default::default());
+
+        let kinds: Vec<DiagnosticKind> = diagnostics.iter().map(|d| d.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                DiagnosticKind::UndefinedReference,
+                DiagnosticKind::DuplicateLabel,
+                DiagnosticKind::DuplicateLabel,
+                DiagnosticKind::UnusedLabel,
+            ]
+        );
+        assert!(diagnostics[0].message.contains("sec:missing"));
+        let unused_label = find_uuid_by_content(&ast, "\\label{sec:unused}").unwrap();
+        assert_eq!(diagnostics[3].uuid, unused_label);
+    }
+
+    #[test]
+    fn citation_diagnostics() {
+        let latex = "\\begin{document}\nAs \\citet{knuth84} and \\cite{missing} show.\n\n\\bibliography{refs}\n\\end{document}\n";
+        let ast = parse_latex(latex.to_string()).expect("Valid Latex");
+        let bibliography = Bibliography {
+            files: vec![BibFile::parse(
+                "refs.bib".to_string(),
+                "@book{knuth84, title = {The TeXbook}}\n@book{unused, title = {Unused}}\n",
+            )],
+        };
+        let diagnostics = diagnostics(&ast, &bibliography);
+
+        let kinds: Vec<DiagnosticKind> = diagnostics.iter().map(|d| d.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                DiagnosticKind::UndefinedCitation,
+                DiagnosticKind::UnusedBibEntry
+            ]
+        );
+        assert!(diagnostics[0].message.contains("missing"));
+        assert!(diagnostics[1].message.contains("unused"));
+        assert_eq!(
+            Some(diagnostics[1].uuid),
+            find_uuid_by_content(&ast, "\\bibliography{refs}")
+        );
+    }
+}
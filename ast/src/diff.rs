@@ -0,0 +1,268 @@
+//! `diff` compares two Asts of the same document, e.g. before and after a git pull, and describes
+//! the changes as a list of Operations.
+use std::collections::{HashMap, HashSet};
+
+use crate::errors::AstError;
+use crate::matching::{children_of, longest_common_subsequence, match_nodes, signature};
+use crate::node::NodeRef;
+use crate::operation::add_node::AddNode;
+use crate::operation::delete_node::DeleteNode;
+use crate::operation::edit_metadata::EditMetadata;
+use crate::operation::edit_node::EditNode;
+use crate::operation::move_node::MoveNode;
+use crate::operation::JsonOperation;
+use crate::options::StringificationOptions;
+use crate::texla_ast::TexlaAst;
+use crate::texla_constants::SKIPPED_CONTENT_MARK;
+use crate::uuid_provider::{Position, Uuid};
+
+/// Returns Operations which transform `old` into `new` when executed on `old` one after another
+/// (without reparsing in between). All Uuids refer to nodes of `old`.
+///
+/// The Operations are ordered as follows:
+/// 1. `MoveNode` for nodes which changed their parent or their order among their siblings
+/// 2. `DeleteNode` for nodes without a counterpart in `new`
+/// 3. `AddNode` for nodes without a counterpart in `old` (adjacent siblings are added at once)
+/// 4. `EditMetadata` and `EditNode` (children before their parents) for changed nodes
+///
+/// Nodes are matched by [match_nodes], so the result is minimal with respect to this matching.
+pub fn diff(old: &TexlaAst, new: &TexlaAst) -> Result<Vec<JsonOperation>, AstError> {
+    let old_by_new: HashMap<Uuid, Uuid> = match_nodes(old, new)
+        .into_iter()
+        .map(|(old_uuid, new_uuid)| (new_uuid, old_uuid))
+        .collect();
+
+    let mut diff = Diff {
+        old,
+        old_by_new,
+        options: Default::default(),
+        kept: HashSet::new(),
+        moves: vec![],
+        deletions: vec![],
+        additions: vec![],
+        edits: vec![],
+    };
+    // the roots are always matched
+    diff.compare(&old.root, &new.root, new.highest_level)?;
+    diff.find_deletions(&old.root);
+
+    let Diff {
+        moves,
+        deletions,
+        additions,
+        edits,
+        ..
+    } = diff;
+    Ok([moves, deletions, additions, edits].concat())
+}
+
+struct Diff<'a> {
+    old: &'a TexlaAst,
+    /// Uuids of nodes in the new Ast mapped to the Uuids of their counterparts in the old one
+    old_by_new: HashMap<Uuid, Uuid>,
+    options: StringificationOptions,
+    /// Nodes of the old Ast which are still part of the new one
+    kept: HashSet<Uuid>,
+    moves: Vec<JsonOperation>,
+    deletions: Vec<JsonOperation>,
+    additions: Vec<JsonOperation>,
+    edits: Vec<JsonOperation>,
+}
+
+impl Diff<'_> {
+    /// Compares the subtrees of two matched nodes.
+    fn compare(&mut self, old_ref: &NodeRef, new_ref: &NodeRef, level: i8) -> Result<(), AstError> {
+        let old_uuid = old_ref.lock().unwrap().uuid;
+        self.kept.insert(old_uuid);
+
+        let old_children: Vec<Uuid> = children_of(old_ref)
+            .iter()
+            .map(|child_ref| child_ref.lock().unwrap().uuid)
+            .collect();
+        let new_children = children_of(new_ref);
+        let matched_children: Vec<Uuid> = new_children
+            .iter()
+            .filter_map(|child_ref| self.old_by_new.get(&child_ref.lock().unwrap().uuid))
+            .copied()
+            .collect();
+        // the largest set of children that keep their order does not need to be moved
+        let unmoved: HashSet<Uuid> = longest_common_subsequence(&old_children, &matched_children)
+            .into_iter()
+            .map(|(old_index, _)| old_children[old_index])
+            .collect();
+
+        let children_level = new_ref.lock().unwrap().node_type.children_level(level);
+        let mut previous = None;
+        let mut added_latex = String::new();
+        for child_ref in &new_children {
+            let child_uuid = child_ref.lock().unwrap().uuid;
+            match self.old_by_new.get(&child_uuid).copied() {
+                Some(old_child_uuid) => {
+                    self.add(old_uuid, previous, &mut added_latex);
+                    if !unmoved.contains(&old_child_uuid) {
+                        self.moves.push(JsonOperation::MoveNode {
+                            arguments: MoveNode {
+                                target: old_child_uuid,
+                                destination: Position {
+                                    parent: old_uuid,
+                                    after_sibling: previous,
+                                },
+                            },
+                        });
+                    }
+                    previous = Some(old_child_uuid);
+                }
+                None => {
+                    let child = child_ref.lock().unwrap();
+                    added_latex += &child.to_latex(children_level, &self.options)?;
+                }
+            }
+        }
+        self.add(old_uuid, previous, &mut added_latex);
+
+        for child_ref in &new_children {
+            let child_uuid = child_ref.lock().unwrap().uuid;
+            if let Some(old_child_uuid) = self.old_by_new.get(&child_uuid).copied() {
+                let old_child_ref = self.old.get_node(old_child_uuid);
+                self.compare(&old_child_ref, child_ref, children_level)?;
+            }
+        }
+
+        self.edit(old_ref, new_ref, level)
+    }
+
+    /// Adds the collected `latex` (if any) behind `after_sibling`.
+    fn add(&mut self, parent: Uuid, after_sibling: Option<Uuid>, latex: &mut String) {
+        if !latex.is_empty() {
+            self.additions.push(JsonOperation::AddNode {
+                arguments: AddNode {
+                    destination: Position {
+                        parent,
+                        after_sibling,
+                    },
+                    raw_latex: std::mem::take(latex),
+                },
+            });
+        }
+    }
+
+    fn edit(&mut self, old_ref: &NodeRef, new_ref: &NodeRef, level: i8) -> Result<(), AstError> {
+        let old = old_ref.lock().unwrap();
+        let new = new_ref.lock().unwrap();
+
+        // removed keys are set to the empty value, which deletes them
+        let mut meta_data: HashMap<String, String> = old
+            .meta_data
+            .data
+            .keys()
+            .filter(|key| !new.meta_data.data.contains_key(*key))
+            .map(|key| (key.clone(), String::new()))
+            .collect();
+        meta_data.extend(
+            new.meta_data
+                .data
+                .iter()
+                .filter(|(key, value)| old.meta_data.data.get(*key) != Some(value))
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+        if !meta_data.is_empty() {
+            self.edits.push(JsonOperation::EditMetadata {
+                arguments: EditMetadata {
+                    target: old.uuid,
+                    new: meta_data,
+                },
+            });
+        }
+
+        if signature(&old) != signature(&new) {
+            let latex = new
+                .node_type
+                .to_latex_without_children(level, &self.options)?;
+            // `EditNode` adds the line breaks behind the code before and after the children itself
+            let raw_latex = latex
+                .split(SKIPPED_CONTENT_MARK)
+                .map(|part| part.strip_suffix('\n').unwrap_or(part))
+                .collect::<Vec<&str>>()
+                .join(SKIPPED_CONTENT_MARK);
+            self.edits.push(JsonOperation::EditNode {
+                arguments: EditNode {
+                    target: old.uuid,
+                    raw_latex,
+                },
+            });
+        }
+        Ok(())
+    }
+
+    /// Deletes the topmost nodes of the old Ast which are not kept.
+    fn find_deletions(&mut self, old_ref: &NodeRef) {
+        let uuid = old_ref.lock().unwrap().uuid;
+        if self.kept.contains(&uuid) {
+            for child_ref in &children_of(old_ref) {
+                self.find_deletions(child_ref);
+            }
+        } else {
+            self.deletions.push(JsonOperation::DeleteNode {
+                arguments: DeleteNode { target: uuid },
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::diff::diff;
+    use crate::operation::JsonOperation;
+    use crate::parser::parse_latex;
+    use crate::Ast;
+
+    fn lf(s: String) -> String {
+        s.replace("\r\n", "\n")
+    }
+
+    #[test]
+    fn identical_asts_have_no_diff() {
+        let latex = lf(fs::read_to_string("../test_resources/latex/lots_of_features.tex").unwrap());
+        let old = parse_latex(latex.clone()).expect("Valid Latex");
+        let new = parse_latex(latex).expect("Valid Latex");
+
+        assert!(diff(&old, &new).unwrap().is_empty());
+    }
+
+    #[test]
+    fn diff_transforms_old_into_new() {
+        let mut old = parse_latex(
+            "\\begin{document}\n\\section{A}\nText a\n\n\\subsection{Sub}\nText sub\n\n\\section{B}\nText b\n\nOld text\n\n\\section{C}\nText c\n\n\\end{document}\n"
+                .to_string(),
+        )
+        .expect("Valid Latex");
+        let new = parse_latex(
+            "\\begin{document}\n\\section{C}\nText c\n\n\\section{A2}\nText a\n\nNew text\n\nMore new text\n\n\\section{B}\nText b\n\n\\subsection{Sub}\nText sub\n\n\\end{document}\n"
+                .to_string(),
+        )
+        .expect("Valid Latex");
+
+        let operations = diff(&old, &new).unwrap();
+        let count = |name: &str| {
+            operations
+                .iter()
+                .filter(|operation| format!("{operation:?}").starts_with(name))
+                .count()
+        };
+        assert_eq!(count("MoveNode"), 2, "{operations:?}"); // section C and subsection Sub
+        assert_eq!(count("DeleteNode"), 1, "{operations:?}");
+        assert_eq!(count("AddNode"), 1, "{operations:?}"); // both new paragraphs at once
+        assert_eq!(count("EditNode"), 1, "{operations:?}");
+
+        for operation in operations.into_iter().map(JsonOperation::to_trait_obj) {
+            old.execute(operation).unwrap();
+        }
+        let result = parse_latex(old.to_latex(Default::default()).unwrap()).expect("Valid Latex");
+        assert_eq!(
+            result.to_latex(Default::default()).unwrap(),
+            new.to_latex(Default::default()).unwrap()
+        );
+    }
+}
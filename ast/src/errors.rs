@@ -3,6 +3,8 @@ use std::fmt::{Debug, Display, Formatter};
 
 use chumsky::error::Simple;
 
+use crate::uuid_provider::Uuid;
+
 /// Error specific to Ast creation conversion
 #[derive(Debug, PartialEq)]
 pub struct AstError {
@@ -79,13 +81,44 @@ impl From<serde_json::Error> for StringificationError {
 }
 
 /// Error specific to [super::Ast] Operations. This will be created when Operations on the Ast fail.
+/// Operations are validated before they modify the Ast, so a failed Operation leaves the Ast untouched.
 #[derive(Debug, PartialEq)]
-pub struct OperationError {
-    pub(crate) message: String,
+pub enum OperationError {
+    /// There is no Node with this Uuid (anymore).
+    UnknownNode { uuid: Uuid },
+    /// The Node with this Uuid cannot have children.
+    InvalidParent { uuid: Uuid },
+    /// `sibling` is not a child of `parent`.
+    UnknownSibling { parent: Uuid, sibling: Uuid },
+    /// `target` would be moved into its own subtree beneath `destination`.
+    Cycle { target: Uuid, destination: Uuid },
+    /// The root Node cannot be removed or moved.
+    RootDeletion,
+    /// The arguments of the Operation are invalid for another reason.
+    InvalidArgument { message: String },
 }
 
 impl Display for OperationError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Could not execute operation: {}", self.message)
+        write!(f, "Could not execute operation: ")?;
+        match self {
+            OperationError::UnknownNode { uuid } => write!(f, "there is no node with id {uuid}"),
+            OperationError::InvalidParent { uuid } => {
+                write!(f, "the node with id {uuid} cannot have children")
+            }
+            OperationError::UnknownSibling { parent, sibling } => write!(
+                f,
+                "the node with id {sibling} is not a child of the node with id {parent}"
+            ),
+            OperationError::Cycle {
+                target,
+                destination,
+            } => write!(
+                f,
+                "the node with id {target} cannot be moved into its own descendant with id {destination}"
+            ),
+            OperationError::RootDeletion => write!(f, "the root node cannot be removed or moved"),
+            OperationError::InvalidArgument { message } => write!(f, "{message}"),
+        }
     }
 }
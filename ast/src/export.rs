@@ -0,0 +1,510 @@
+//! `export` converts a [crate::texla_ast::TexlaAst] into formats other than LaTeX.
+//! The exporters walk the Ast and only translate the most common LaTeX commands, everything else
+//! is dropped while keeping its arguments.
+use std::collections::HashMap;
+
+use crate::latex_constants::*;
+
+pub mod anonymization;
+pub mod html;
+pub mod markdown;
+pub mod standalone;
+pub mod submission;
+
+/// Text styles which are translated to the respective markup.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Style {
+    Bold,
+    Italic,
+    Code,
+}
+
+/// Translates the inline markup of a LaTeX paragraph into some target format.
+pub(crate) trait InlineFormat {
+    /// Escapes plain text.
+    fn escape(&self, text: &str) -> String;
+    fn math(&self, latex: &str) -> String;
+    /// Applies `style` to the converted `content` (which is neither converted nor escaped for
+    /// [Style::Code]).
+    fn styled(&self, style: Style, content: String) -> String;
+    fn link(&self, url: &str, content: String) -> String;
+    /// A target for references to `label`.
+    fn anchor(&self, label: &str) -> String;
+    /// A reference to `label` showing `text`.
+    fn reference(&self, label: &str, text: &str) -> String;
+    fn line_break(&self) -> String;
+}
+
+const BOLD_COMMANDS: [&str; 2] = ["textbf", "bf"];
+const ITALIC_COMMANDS: [&str; 4] = ["emph", "textit", "it", "textsl"];
+const CODE_COMMANDS: [&str; 1] = ["texttt"];
+/// Commands whose arguments are not part of the text
+const IGNORED_COMMANDS: [&str; 7] = [
+    "nocite",
+    "footnote",
+    "index",
+    "vspace",
+    "hspace",
+    "newpage",
+    "clearpage",
+];
+
+/// Commands which only affect the layout of the PDF
+const LAYOUT_COMMANDS: [&str; 5] = [
+    "\\maketitle",
+    "\\tableofcontents",
+    "\\newpage",
+    "\\clearpage",
+    APPENDIX,
+];
+
+/// Schemes links may use, others (like `javascript:`) would run code when the link is clicked
+const LINK_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// Numbers segments, figures, tables and equations the way LaTeX does (without resetting
+/// counters at the start of chapters) and remembers the numbers of labels.
+#[derive(Debug, Default)]
+pub(crate) struct Numbering {
+    /// The current number on every level starting with the highest one
+    segments: Vec<u32>,
+    counters: HashMap<String, u32>,
+    /// The numbers of the segments, floats and equations the current node is part of
+    current: Vec<Option<String>>,
+    /// The number shown by references to each label
+    pub(crate) labels: HashMap<String, String>,
+}
+
+impl Numbering {
+    /// Starts numbering from the beginning, but keeps the labels found so far. Exporters walk the
+    /// document twice, because labels may be referenced before they are defined.
+    pub(crate) fn restart(&mut self) {
+        self.segments.clear();
+        self.counters.clear();
+        self.current.clear();
+    }
+
+    /// Returns the number of the next segment with the given depth (0 is the highest level),
+    /// e.g. `2.1`.
+    pub(crate) fn next_segment(&mut self, depth: usize) -> String {
+        self.segments.resize(depth + 1, 0);
+        self.segments[depth] += 1;
+        self.segments
+            .iter()
+            .map(|number| number.to_string())
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+
+    pub(crate) fn next(&mut self, counter: &str) -> String {
+        let number = self.counters.entry(counter.to_string()).or_default();
+        *number += 1;
+        number.to_string()
+    }
+
+    /// Enters an element with the given number (if it is numbered).
+    pub(crate) fn enter(&mut self, number: Option<String>) {
+        self.current.push(number);
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.current.pop();
+    }
+
+    /// Remembers the labels defined in `latex` (see `record_label()`).
+    pub(crate) fn record_labels(&mut self, latex: &str, number: Option<&str>) {
+        for label in take_labels(latex).0 {
+            self.record_label(&label, number);
+        }
+    }
+
+    /// Remembers the number shown by references to `label`, which is the number of the
+    /// innermost numbered element by default.
+    pub(crate) fn record_label(&mut self, label: &str, number: Option<&str>) {
+        let number = number
+            .map(str::to_string)
+            .or_else(|| self.current.iter().rev().flatten().next().cloned())
+            .unwrap_or(label.to_string());
+        self.labels.entry(label.to_string()).or_insert(number);
+    }
+}
+
+/// Returns the argument of the first occurrence of `command` in `latex`, e.g. the title in the
+/// preamble.
+pub(crate) fn command_argument<'a>(latex: &'a str, command: &str) -> Option<&'a str> {
+    let start = latex.find(&format!("{KEYWORD_PREFIX}{command}{BLOCK_BEGIN}"))?;
+    let argument_start = start + command.len() + 2;
+    let argument_length = group_length(&latex[argument_start..])?;
+    Some(&latex[argument_start..argument_start + argument_length])
+}
+
+/// Whether `latex` consists of commands which have no counterpart in other formats only.
+pub(crate) fn is_layout_command(latex: &str) -> bool {
+    latex
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .all(|line| LAYOUT_COMMANDS.contains(&line))
+}
+
+/// Returns the labels defined by `\label` in `latex` and `latex` without them.
+pub(crate) fn take_labels(latex: &str) -> (Vec<String>, String) {
+    let mut labels = vec![];
+    let mut rest = latex.to_string();
+    while let Some(label) = command_argument(&rest, LABEL.trim_start_matches(KEYWORD_PREFIX)) {
+        let label = label.to_string();
+        let command = format!("{LABEL}{BLOCK_BEGIN}{label}{BLOCK_END}");
+        rest = rest.replacen(&command, "", 1);
+        labels.push(label);
+    }
+    (labels, rest)
+}
+
+/// Returns the length of the group starting at the beginning of `latex` (after its opening
+/// brace) up to its closing brace.
+fn group_length(latex: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (index, char) in latex.char_indices() {
+        match char {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(index),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether `url` is relative or uses one of [LINK_SCHEMES].
+fn is_safe_url(url: &str) -> bool {
+    // browsers ignore whitespace and control characters within the scheme
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    match url.find([':', '/', '?', '#']) {
+        Some(end) if url[end..].starts_with(':') => {
+            LINK_SCHEMES.contains(&url[..end].to_lowercase().as_str())
+        }
+        _ => true,
+    }
+}
+
+/// Translates the LaTeX code of a paragraph (or a heading, caption, ...) into `format`.
+/// `numbers` maps labels to the numbers shown by references to them.
+pub(crate) fn convert_inline(
+    latex: &str,
+    format: &impl InlineFormat,
+    numbers: &HashMap<String, String>,
+) -> String {
+    InlineConverter {
+        latex,
+        position: 0,
+        format,
+        numbers,
+    }
+    .convert()
+}
+
+struct InlineConverter<'a, F: InlineFormat> {
+    latex: &'a str,
+    position: usize,
+    format: &'a F,
+    numbers: &'a HashMap<String, String>,
+}
+
+impl<'a, F: InlineFormat> InlineConverter<'a, F> {
+    fn rest(&self) -> &str {
+        &self.latex[self.position..]
+    }
+
+    fn convert(&mut self) -> String {
+        let mut result = String::new();
+        let mut text = String::new();
+        while let Some(char) = self.rest().chars().next() {
+            let converted = match char {
+                '%' => {
+                    // comments last until the end of the line
+                    let length = self.rest().find('\n').unwrap_or(self.rest().len());
+                    self.position += length;
+                    continue;
+                }
+                '$' => self.delimited_math("$", "$"),
+                '\\' if self.rest().starts_with("\\(") => self.delimited_math("\\(", "\\)"),
+                '\\' => self.command(),
+                '{' => {
+                    self.position += 1;
+                    self.group()
+                }
+                '}' => {
+                    self.position += 1;
+                    Some(String::new())
+                }
+                '~' => {
+                    self.position += 1;
+                    text.push('\u{a0}');
+                    continue;
+                }
+                '-' if self.rest().starts_with("---") => self.replace(3, "\u{2014}"),
+                '-' if self.rest().starts_with("--") => self.replace(2, "\u{2013}"),
+                '`' if self.rest().starts_with("``") => self.replace(2, "\u{201c}"),
+                '\'' if self.rest().starts_with("''") => self.replace(2, "\u{201d}"),
+                _ => {
+                    self.position += char.len_utf8();
+                    text.push(char);
+                    continue;
+                }
+            };
+            result += &self.format.escape(&std::mem::take(&mut text));
+            result += &converted.unwrap_or_default();
+        }
+        result += &self.format.escape(&text);
+        result
+    }
+
+    fn replace(&mut self, length: usize, replacement: &str) -> Option<String> {
+        self.position += length;
+        Some(self.format.escape(replacement))
+    }
+
+    fn delimited_math(&mut self, left: &str, right: &str) -> Option<String> {
+        let start = self.position + left.len();
+        match self.latex[start..].find(right) {
+            Some(length) => {
+                self.position = start + length + right.len();
+                Some(self.format.math(&self.latex[start..start + length]))
+            }
+            None => {
+                self.position = self.latex.len();
+                Some(self.format.escape(&self.latex[start..]))
+            }
+        }
+    }
+
+    /// Converts the rest of a group, whose opening brace was already consumed.
+    fn group(&mut self) -> Option<String> {
+        let length = group_length(self.rest()).unwrap_or(self.rest().len());
+        let content = &self.latex[self.position..self.position + length];
+        self.position = (self.position + length + 1).min(self.latex.len());
+        Some(convert_inline(content, self.format, self.numbers))
+    }
+
+    /// Reads the next argument in braces without converting it.
+    fn raw_argument(&mut self) -> Option<&'a str> {
+        let latex = self.latex;
+        let trimmed = latex[self.position..].trim_start();
+        if !trimmed.starts_with(BLOCK_BEGIN) {
+            return None;
+        }
+        let start = latex.len() - trimmed.len() + 1;
+        let length = group_length(&latex[start..])?;
+        self.position = start + length + 1;
+        Some(&latex[start..start + length])
+    }
+
+    /// Reads the argument of `\verb`, which is enclosed by the same character on both sides
+    /// (like `\verb|x|`) instead of braces.
+    fn verbatim_argument(&mut self) -> Option<&'a str> {
+        let latex = self.latex;
+        let delimiter = latex[self.position..].chars().next()?;
+        let start = self.position + delimiter.len_utf8();
+        let length = latex[start..].find(delimiter)?;
+        self.position = start + length + delimiter.len_utf8();
+        Some(&latex[start..start + length])
+    }
+
+    fn argument(&mut self) -> String {
+        self.raw_argument()
+            .map(|argument| convert_inline(argument, self.format, self.numbers))
+            .unwrap_or_default()
+    }
+
+    fn command(&mut self) -> Option<String> {
+        // skip the backslash
+        self.position += 1;
+        let name_length = self
+            .rest()
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(self.rest().len());
+        if name_length == 0 {
+            let char = self.rest().chars().next()?;
+            self.position += char.len_utf8();
+            return match char {
+                '\\' => Some(self.format.line_break()),
+                ',' | ' ' => Some(self.format.escape(" ")),
+                _ if "%&_#${}".contains(char) => Some(self.format.escape(&char.to_string())),
+                _ => None,
+            };
+        }
+
+        let name = &self.latex[self.position..self.position + name_length];
+        self.position += name_length;
+        if self.rest().starts_with('*') {
+            self.position += 1;
+        }
+        if name == "verb" {
+            let content = self.verbatim_argument()?.to_string();
+            return Some(self.format.styled(Style::Code, content));
+        }
+        // optional arguments are not shown
+        while self.rest().trim_start().starts_with(OPTIONS_BEGIN) {
+            match self.rest().find(OPTIONS_END) {
+                Some(length) => self.position += length + 1,
+                None => break,
+            }
+        }
+
+        match name {
+            _ if BOLD_COMMANDS.contains(&name) => {
+                Some(self.format.styled(Style::Bold, self.argument()))
+            }
+            _ if ITALIC_COMMANDS.contains(&name) => {
+                Some(self.format.styled(Style::Italic, self.argument()))
+            }
+            _ if CODE_COMMANDS.contains(&name) => {
+                let content = self.raw_argument().unwrap_or_default().to_string();
+                Some(self.format.styled(Style::Code, content))
+            }
+            _ if IGNORED_COMMANDS.contains(&name) => {
+                self.raw_argument();
+                None
+            }
+            _ if CITATION_COMMANDS.contains(&format!("{KEYWORD_PREFIX}{name}").as_str()) => {
+                let keys = self.raw_argument().unwrap_or_default().replace(' ', "");
+                Some(
+                    self.format
+                        .escape(&format!("[{}]", keys.replace(',', ", "))),
+                )
+            }
+            _ if REFERENCE_COMMANDS.contains(&format!("{KEYWORD_PREFIX}{name}").as_str()) => {
+                let label = self.raw_argument()?.trim().to_string();
+                let number = self.numbers.get(&label).unwrap_or(&label);
+                let text = match name {
+                    "eqref" => format!("({number})"),
+                    _ => number.clone(),
+                };
+                Some(self.format.reference(&label, &text))
+            }
+            "label" => {
+                let label = self.raw_argument()?.trim().to_string();
+                Some(self.format.anchor(&label))
+            }
+            "url" => {
+                let url = self.raw_argument()?.to_string();
+                let content = self.format.escape(&url);
+                match is_safe_url(&url) {
+                    true => Some(self.format.link(&url, content)),
+                    false => Some(content),
+                }
+            }
+            "href" => {
+                let url = self.raw_argument()?.to_string();
+                let content = self.argument();
+                match is_safe_url(&url) {
+                    true => Some(self.format.link(&url, content)),
+                    false => Some(content),
+                }
+            }
+            "LaTeX" | "TeX" => Some(self.format.escape(name)),
+            "dots" | "ldots" => Some(self.format.escape("\u{2026}")),
+            // unknown commands are dropped, but their arguments are kept
+            _ => {
+                let mut content = String::new();
+                while self.rest().starts_with(BLOCK_BEGIN) {
+                    content += &self.argument();
+                }
+                Some(content)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::export::{command_argument, convert_inline, take_labels, InlineFormat, Style};
+
+    /// Shows the structure the converter recognized
+    struct Plain;
+
+    impl InlineFormat for Plain {
+        fn escape(&self, text: &str) -> String {
+            text.to_string()
+        }
+        fn math(&self, latex: &str) -> String {
+            format!("<math {latex}>")
+        }
+        fn styled(&self, style: Style, content: String) -> String {
+            format!("<{style:?} {content}>")
+        }
+        fn link(&self, url: &str, content: String) -> String {
+            format!("<link {url} {content}>")
+        }
+        fn anchor(&self, label: &str) -> String {
+            format!("<anchor {label}>")
+        }
+        fn reference(&self, label: &str, text: &str) -> String {
+            format!("<ref {label} {text}>")
+        }
+        fn line_break(&self) -> String {
+            "<br>".to_string()
+        }
+    }
+
+    #[test]
+    fn inline_markup() {
+        let numbers = HashMap::from([("sec".to_string(), "2.1".to_string())]);
+        let latex = r"Some \textbf{bold \emph{and} italic} text~with $x^2$, see \cref{sec} and \href{https://texla.de}{TeXLa} % comment";
+
+        assert_eq!(
+            convert_inline(latex, &Plain, &numbers),
+            "Some <Bold bold <Italic and> italic> text\u{a0}with <math x^2>, see <ref sec 2.1> and <link https://texla.de TeXLa> "
+        );
+    }
+
+    #[test]
+    fn code() {
+        let latex = r"\texttt{a_b} and \verb|\x{y}| or \verb*+z+";
+
+        assert_eq!(
+            convert_inline(latex, &Plain, &HashMap::new()),
+            r"<Code a_b> and <Code \x{y}> or <Code z>"
+        );
+    }
+
+    #[test]
+    fn citations() {
+        let latex = r"\citeauthor{a}\nocite{*} in \citep[p.~5]{b, c}";
+
+        assert_eq!(
+            convert_inline(latex, &Plain, &HashMap::new()),
+            "[a] in [b, c]"
+        );
+    }
+
+    #[test]
+    fn unsafe_links() {
+        let latex = r"\href{JavaScript:alert(1)}{click} \url{java script:x} \href{mailto:a@b.de}{mail} \href{#sec}{here}";
+
+        assert_eq!(
+            convert_inline(latex, &Plain, &HashMap::new()),
+            "click java script:x <link mailto:a@b.de mail> <link #sec here>"
+        );
+    }
+
+    #[test]
+    fn arguments_and_labels() {
+        let preamble = "\\documentclass{article}\n\\title{A {nested} title}\n";
+        assert_eq!(
+            command_argument(preamble, "title"),
+            Some("A {nested} title")
+        );
+
+        let (labels, rest) = take_labels("x = 1 \\label{eq:a}\\label{eq:b}");
+        assert_eq!(labels, vec!["eq:a", "eq:b"]);
+        assert_eq!(rest, "x = 1 ");
+    }
+}
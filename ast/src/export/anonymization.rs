@@ -0,0 +1,242 @@
+use serde::Serialize;
+
+use crate::errors::AstError;
+use crate::export::submission::to_submission;
+use crate::latex_constants::*;
+use crate::node::{visit_subtree, ExpandableData, LeafData, Node, NodeType};
+use crate::options::{NodeFilter, VARIANT_METADATA_KEY};
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::Uuid;
+
+/// The variant of nodes which reveal the identity of the authors (e.g. self-citations), which are
+/// dropped together with their subtrees.
+pub const IDENTIFYING_VARIANT: &str = "identifying";
+const ANONYMOUS_AUTHOR: &str = "Anonymous";
+/// Commands whose argument is replaced by [ANONYMOUS_AUTHOR]
+const AUTHOR_COMMANDS: [&str; 1] = ["\\author"];
+/// Commands with data about the authors, which are removed together with their arguments
+const AUTHOR_DATA_COMMANDS: [&str; 8] = [
+    "\\affiliation",
+    "\\affil",
+    "\\institute",
+    "\\address",
+    "\\email",
+    "\\thanks",
+    "\\orcid",
+    "\\IEEEauthorblockA",
+];
+/// Matches both the British and the American spelling in headings
+const ACKNOWLEDGEMENTS_HEADING: &str = "acknowledg";
+const ACKNOWLEDGEMENTS_ENVIRONMENTS: [&str; 3] = ["acks", "acknowledgements", "acknowledgments"];
+
+/// Everything which was removed to anonymize a document.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct AnonymizationReport {
+    /// The commands with data about the authors as they were found, e.g. `\author{Jane Doe}`
+    pub author_data: Vec<String>,
+    /// The nodes dropped together with their subtrees
+    pub nodes: Vec<RemovedNode>,
+    /// The number of comments removed
+    pub comments: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct RemovedNode {
+    pub uuid: Uuid,
+    /// The first line of its LaTeX code
+    pub summary: String,
+}
+
+/// A document prepared for double-blind review.
+#[derive(Debug, PartialEq)]
+pub struct Anonymized {
+    pub latex: String,
+    pub report: AnonymizationReport,
+}
+
+/// Converts the document into a single file like [to_submission] and removes all data about the
+/// authors: the author is replaced by [ANONYMOUS_AUTHOR], affiliations are removed and
+/// acknowledgements as well as nodes of the [IDENTIFYING_VARIANT] are dropped.
+pub fn to_anonymized(
+    ast: &TexlaAst,
+    node_filter: &NodeFilter,
+    resolve_image: &mut dyn FnMut(&str) -> String,
+) -> Result<Anonymized, AstError> {
+    let mut report = AnonymizationReport::default();
+    let mut node_filter = node_filter.clone();
+    visit_subtree(&ast.root, &mut |node| {
+        if is_identifying(node) {
+            node_filter.exclude_nodes.push(node.uuid);
+            report.nodes.push(RemovedNode {
+                uuid: node.uuid,
+                summary: summary(node),
+            });
+        } else if let NodeType::Leaf {
+            data: LeafData::Comment { .. },
+        } = node.node_type
+        {
+            report.comments += 1;
+        }
+    });
+
+    let latex = to_submission(ast, &node_filter, resolve_image)?;
+    let latex = remove_author_data(&latex, &mut report.author_data);
+    Ok(Anonymized { latex, report })
+}
+
+fn is_identifying(node: &Node) -> bool {
+    let is_tagged = node
+        .meta_data
+        .data
+        .get(VARIANT_METADATA_KEY)
+        .is_some_and(|variants| {
+            variants
+                .split_whitespace()
+                .any(|v| v == IDENTIFYING_VARIANT)
+        });
+    is_tagged
+        || match &node.node_type {
+            NodeType::Expandable {
+                data: ExpandableData::Segment { heading, .. },
+                ..
+            } => heading.to_lowercase().contains(ACKNOWLEDGEMENTS_HEADING),
+            NodeType::Expandable {
+                data: ExpandableData::Environment { name },
+                ..
+            } => ACKNOWLEDGEMENTS_ENVIRONMENTS.contains(&name.as_str()),
+            _ => false,
+        }
+}
+
+fn summary(node: &Node) -> String {
+    match &node.node_type {
+        NodeType::Expandable {
+            data: ExpandableData::Segment { heading, .. },
+            ..
+        } => heading.clone(),
+        _ => node
+            .raw_latex
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// Replaces the authors and removes the commands with data about them (lines which become empty
+/// are removed as well). The removed commands are appended to `removed`.
+fn remove_author_data(latex: &str, removed: &mut Vec<String>) -> String {
+    let mut anonymized = String::with_capacity(latex.len());
+    let mut position = 0;
+
+    while let Some(offset) = latex[position..].find(KEYWORD_PREFIX) {
+        let start = position + offset;
+        let is_command = |command: &&str| {
+            latex[start..].starts_with(*command)
+                && !latex[start + command.len()..].starts_with(|c: char| c.is_ascii_alphabetic())
+        };
+        let author = AUTHOR_COMMANDS.into_iter().find(is_command);
+        let command = author.or_else(|| AUTHOR_DATA_COMMANDS.into_iter().find(is_command));
+        let end = command.and_then(|command| command_end(latex, start + command.len()));
+        let (Some(command), Some(end)) = (command, end) else {
+            anonymized += &latex[position..start + KEYWORD_PREFIX.len()];
+            position = start + KEYWORD_PREFIX.len();
+            continue;
+        };
+
+        removed.push(latex[start..end].to_string());
+        anonymized += &latex[position..start];
+        position = end;
+        let line_start = anonymized.rfind('\n').map_or(0, |index| index + 1);
+        if author.is_some() {
+            anonymized += &format!("{command}{BLOCK_BEGIN}{ANONYMOUS_AUTHOR}{BLOCK_END}");
+        } else if anonymized[line_start..].trim().is_empty() && latex[end..].starts_with('\n') {
+            // the command was on a line of its own
+            anonymized.truncate(line_start);
+            position += 1;
+        }
+    }
+
+    anonymized += &latex[position..];
+    anonymized
+}
+
+/// Returns the end of a command whose name ends at `position`: a star, optional arguments and
+/// a mandatory argument (in which braces may be nested) are part of it.
+fn command_end(latex: &str, mut position: usize) -> Option<usize> {
+    position += latex[position..].len() - latex[position..].trim_start_matches('*').len();
+    while latex[position..].trim_start().starts_with(OPTIONS_BEGIN) {
+        let options_start = position + latex[position..].find(OPTIONS_BEGIN)?;
+        position = options_start + latex[options_start..].find(OPTIONS_END)? + 1;
+    }
+    if !latex[position..].trim_start().starts_with(BLOCK_BEGIN) {
+        return None;
+    }
+
+    let argument_start = position + latex[position..].find(BLOCK_BEGIN)?;
+    let mut depth = 0;
+    let mut escaped = false;
+    for (index, char) in latex[argument_start..].char_indices() {
+        match char {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(argument_start + index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::anonymization::{remove_author_data, to_anonymized};
+    use crate::operation::test::find_uuid_by_content;
+    use crate::parser::parse_latex;
+
+    #[test]
+    fn replace_authors() {
+        let mut removed = vec![];
+        let latex = remove_author_data(
+            "\\title{Paper\\thanks{Funded by X}}\n\\author{Jane Doe \\and {John} Roe}\n  \\affiliation{University of Y}\n\\email{jane@y.edu}\n\\maketitle\n",
+            &mut removed,
+        );
+
+        assert_eq!(latex, "\\title{Paper}\n\\author{Anonymous}\n\\maketitle\n");
+        assert_eq!(
+            removed,
+            vec![
+                "\\thanks{Funded by X}",
+                "\\author{Jane Doe \\and {John} Roe}",
+                "\\affiliation{University of Y}",
+                "\\email{jane@y.edu}"
+            ]
+        );
+    }
+
+    #[test]
+    fn anonymize_document() {
+        let latex = "\\documentclass{article}\n\\author{Jane Doe}\n\\begin{document}\n\\section{Intro}\n% our earlier paper\nText\n\n% TEXLA METADATA (variant:identifying,)\n\\section{Our previous work}\nAs we showed in \\cite{doe21}.\n\n\\section*{Acknowledgements}\nWe thank our funders.\n\n\\end{document}\n";
+        let ast = parse_latex(latex.to_string()).expect("Valid Latex");
+        let previous_work = find_uuid_by_content(&ast, "Our previous work").unwrap();
+
+        let anonymized =
+            to_anonymized(&ast, &Default::default(), &mut |path| path.to_string()).unwrap();
+
+        assert_eq!(
+            anonymized.latex,
+            "\\documentclass{article}\n\\author{Anonymous}\n\\begin{document}\n\\section{Intro}\nText\n\n\\end{document}\n"
+        );
+        assert_eq!(anonymized.report.author_data, vec!["\\author{Jane Doe}"]);
+        assert_eq!(anonymized.report.nodes.len(), 2);
+        assert_eq!(anonymized.report.nodes[0].uuid, previous_work);
+        assert_eq!(anonymized.report.nodes[0].summary, "Our previous work");
+        assert_eq!(anonymized.report.comments, 1);
+    }
+}
@@ -0,0 +1,379 @@
+use crate::export::{
+    command_argument, convert_inline, is_layout_command, take_labels, InlineFormat, Numbering,
+    Style,
+};
+use crate::node::{ExpandableData, LeafData, MathKind, NodeRef, NodeType};
+use crate::texla_ast::TexlaAst;
+
+const KATEX_URL: &str = "https://cdn.jsdelivr.net/npm/katex@0.16.9/dist";
+/// Environments which are numbered and whose captions are prefixed with their name and number
+const FLOATS: [(&str, &str); 2] = [("figure", "Figure"), ("table", "Table")];
+const LISTS: [(&str, &str); 3] = [
+    ("itemize", "ul"),
+    ("enumerate", "ol"),
+    ("description", "ul"),
+];
+const ITEM: &str = "\\item";
+
+/// Converts the document into a standalone HTML page.
+/// Math is kept as LaTeX code in elements KaTeX renders when the page is loaded.
+/// `resolve_image` maps the path of an image in the document to the path used in the page (which
+/// is where the caller is supposed to copy the image to).
+pub fn to_html(ast: &TexlaAst, resolve_image: &dyn Fn(&str) -> String) -> String {
+    let mut exporter = HtmlExporter {
+        resolve_image,
+        highest_level: ast.highest_level,
+        numbering: Default::default(),
+        floats: vec![],
+        in_list: false,
+        body: String::new(),
+    };
+    // labels may be referenced before they are defined, so the first pass only collects them
+    exporter.node(&ast.root, ast.highest_level);
+    exporter.numbering.restart();
+    exporter.body.clear();
+    exporter.node(&ast.root, ast.highest_level);
+
+    let preamble = match &ast.root.lock().unwrap().node_type {
+        NodeType::Expandable {
+            data: ExpandableData::Document { preamble, .. },
+            ..
+        } => preamble.clone(),
+        _ => String::new(),
+    };
+    let title = command_argument(&preamble, "title")
+        .map(|title| convert_inline(title, &Html, &exporter.numbering.labels))
+        .unwrap_or_default();
+    let author = command_argument(&preamble, "author")
+        .map(|author| convert_inline(author, &Html, &exporter.numbering.labels))
+        .unwrap_or_default();
+
+    let mut header = String::new();
+    if !title.is_empty() {
+        header += &format!("<h1 class=\"title\">{title}</h1>\n");
+    }
+    if !author.is_empty() {
+        header += &format!("<p class=\"author\">{author}</p>\n");
+    }
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{}</title>
+<link rel=\"stylesheet\" href=\"{KATEX_URL}/katex.min.css\">
+<script defer src=\"{KATEX_URL}/katex.min.js\"></script>
+<script defer src=\"{KATEX_URL}/contrib/auto-render.min.js\" onload=\"renderMathInElement(document.body)\"></script>
+</head>
+<body>
+{header}{}</body>
+</html>
+",
+        strip_tags(&title),
+        exporter.body
+    )
+}
+
+struct HtmlExporter<'a> {
+    resolve_image: &'a dyn Fn(&str) -> String,
+    highest_level: i8,
+    numbering: Numbering,
+    /// The names and numbers of the floats the current node is part of
+    floats: Vec<(String, String)>,
+    in_list: bool,
+    body: String,
+}
+
+impl HtmlExporter<'_> {
+    fn node(&mut self, node_ref: &NodeRef, level: i8) {
+        let node = node_ref.lock().unwrap();
+        let uuid = node.uuid;
+        let (data, children) = match &node.node_type {
+            NodeType::Leaf { data } => {
+                let data = data.clone();
+                drop(node);
+                self.leaf(data, uuid);
+                return;
+            }
+            NodeType::Expandable { data, children } => (data.clone(), children.clone()),
+        };
+        let children_level = node.node_type.children_level(level);
+        drop(node);
+
+        let render_children = |this: &mut Self| {
+            for child_ref in &children {
+                this.node(child_ref, children_level);
+            }
+        };
+
+        match data {
+            ExpandableData::Document { .. } | ExpandableData::File { .. } => render_children(self),
+            ExpandableData::Segment { heading, counted } => {
+                let depth = (level - self.highest_level).max(0) as usize;
+                let tag = format!("h{}", (depth + 2).min(6));
+                let number = counted.then(|| self.numbering.next_segment(depth));
+                self.numbering.record_labels(&heading, number.as_deref());
+
+                let heading = self.inline(&heading);
+                self.body += &match &number {
+                    Some(number) => format!(
+                        "<section id=\"node-{uuid}\">\n<{tag}><span class=\"number\">{number}</span> {heading}</{tag}>\n"
+                    ),
+                    None => format!("<section id=\"node-{uuid}\">\n<{tag}>{heading}</{tag}>\n"),
+                };
+                self.numbering.enter(number);
+                render_children(self);
+                self.numbering.leave();
+                self.body += "</section>\n";
+            }
+            ExpandableData::Environment { name } => {
+                let base_name = name.trim_end_matches('*');
+                if let Some((_, caption_name)) = FLOATS.iter().find(|(env, _)| *env == base_name) {
+                    let number = self.numbering.next(base_name);
+                    self.body += &format!("<figure class=\"{base_name}\" id=\"node-{uuid}\">\n");
+                    self.floats
+                        .push((caption_name.to_string(), number.to_string()));
+                    self.numbering.enter(Some(number));
+                    render_children(self);
+                    self.numbering.leave();
+                    self.floats.pop();
+                    self.body += "</figure>\n";
+                } else if let Some((_, tag)) = LISTS.iter().find(|(env, _)| *env == base_name) {
+                    self.body += &format!("<{tag}>\n");
+                    let in_list = std::mem::replace(&mut self.in_list, true);
+                    render_children(self);
+                    self.in_list = in_list;
+                    self.body += &format!("</{tag}>\n");
+                } else {
+                    let tag = if base_name == "quote" || base_name == "quotation" {
+                        "blockquote"
+                    } else {
+                        "div"
+                    };
+                    self.body += &format!("<{tag} class=\"{}\">\n", escape(base_name));
+                    let in_list = std::mem::replace(&mut self.in_list, false);
+                    render_children(self);
+                    self.in_list = in_list;
+                    self.body += &format!("</{tag}>\n");
+                }
+            }
+            ExpandableData::Dummy {
+                before_children,
+                after_children,
+                ..
+            } => {
+                self.raw_latex(&before_children);
+                render_children(self);
+                self.raw_latex(&after_children);
+            }
+            ExpandableData::Conflict => {
+                self.body += "<div class=\"conflict\">\n";
+                render_children(self);
+                self.body += "</div>\n";
+            }
+            ExpandableData::ConflictSide { ours, .. } => {
+                let side = if ours { "ours" } else { "theirs" };
+                self.body += &format!("<div class=\"conflict-{side}\">\n");
+                render_children(self);
+                self.body += "</div>\n";
+            }
+        }
+    }
+
+    fn leaf(&mut self, data: LeafData, uuid: u64) {
+        match data {
+            LeafData::Text { text } => {
+                self.numbering.record_labels(&text, None);
+                if self.in_list {
+                    for item in text.split(ITEM).filter(|item| !item.trim().is_empty()) {
+                        self.body += &format!("<li>{}</li>\n", self.inline(item.trim()));
+                    }
+                } else {
+                    self.body += &format!("<p>{}</p>\n", self.inline(text.trim()));
+                }
+            }
+            LeafData::Math { kind, content } => {
+                let (labels, content) = take_labels(&content);
+                let number = match kind {
+                    MathKind::Equation | MathKind::Align => Some(self.numbering.next("equation")),
+                    _ => None,
+                };
+                for label in &labels {
+                    self.numbering.record_label(label, number.as_deref());
+                }
+                let content = match kind {
+                    MathKind::Align => format!("\\begin{{aligned}}{content}\\end{{aligned}}"),
+                    _ => content,
+                };
+                let id = labels
+                    .first()
+                    .map(|label| escape(label))
+                    .unwrap_or(format!("node-{uuid}"));
+                let number = number
+                    .map(|number| format!("<span class=\"equation-number\">({number})</span>"))
+                    .unwrap_or_default();
+                self.body += &format!(
+                    "<div class=\"math display\" id=\"{id}\">\\[{}\\]{number}</div>\n",
+                    escape(content.trim())
+                );
+            }
+            LeafData::Image { path, .. } => {
+                let source = (self.resolve_image)(&path);
+                self.body += &format!(
+                    "<img src=\"{}\" alt=\"{}\">\n",
+                    escape(&source),
+                    escape(&path)
+                );
+            }
+            LeafData::Label { label } => {
+                self.numbering.record_label(&label, None);
+                self.body += &Html.anchor(&label);
+                self.body += "\n";
+            }
+            LeafData::Caption { caption } => {
+                self.numbering.record_labels(&caption, None);
+                let caption = self.inline(&caption);
+                self.body += &match self.floats.last() {
+                    Some((name, number)) => format!(
+                        "<figcaption><span class=\"number\">{name} {number}:</span> {caption}</figcaption>\n"
+                    ),
+                    None => format!("<p class=\"caption\">{caption}</p>\n"),
+                };
+            }
+            LeafData::Comment { .. } => {}
+        }
+    }
+
+    /// LaTeX code without a structure known to TeXLa is shown as it is.
+    fn raw_latex(&mut self, latex: &str) {
+        if !latex.trim().is_empty() && !is_layout_command(latex) {
+            self.body += &format!("<pre class=\"latex\">{}</pre>\n", escape(latex.trim()));
+        }
+    }
+
+    fn inline(&self, latex: &str) -> String {
+        convert_inline(latex, &Html, &self.numbering.labels)
+    }
+}
+
+struct Html;
+
+impl InlineFormat for Html {
+    fn escape(&self, text: &str) -> String {
+        escape(text)
+    }
+
+    fn math(&self, latex: &str) -> String {
+        format!("<span class=\"math inline\">\\({}\\)</span>", escape(latex))
+    }
+
+    fn styled(&self, style: Style, content: String) -> String {
+        let tag = match style {
+            Style::Bold => "strong",
+            Style::Italic => "em",
+            Style::Code => return format!("<code>{}</code>", escape(&content)),
+        };
+        format!("<{tag}>{content}</{tag}>")
+    }
+
+    fn link(&self, url: &str, content: String) -> String {
+        format!("<a href=\"{}\">{content}</a>", escape(url))
+    }
+
+    fn anchor(&self, label: &str) -> String {
+        format!("<a id=\"{}\"></a>", escape(label))
+    }
+
+    fn reference(&self, label: &str, text: &str) -> String {
+        format!("<a href=\"#{}\">{}</a>", escape(label), escape(text))
+    }
+
+    fn line_break(&self) -> String {
+        "<br>".to_string()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Removes the markup from converted inline code, e.g. for the title of the page.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for char in html.chars() {
+        match char {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(char),
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::html::to_html;
+    use crate::parser::parse_latex;
+
+    #[test]
+    fn export_html() {
+        let ast = parse_latex(
+            "\\title{A \\& B}\n\\begin{document}\n\\section{Intro}\n\\label{sec:intro}\nSee \\ref{fig:cat}, \\eqref{eq:e} and $x < 1$.\n\n\\begin{figure}\n\\includegraphics{images/cat}\n\\caption{A cat}\n\\label{fig:cat}\n\\end{figure}\n\\subsection{Math}\n\\begin{equation}\ne = mc^2 \\label{eq:e}\n\\end{equation}\nBack to \\ref{sec:intro}.\n\n\\end{document}\n"
+                .to_string(),
+        )
+        .expect("Valid Latex");
+        let html = to_html(&ast, &|path| format!("{path}.png"));
+
+        assert!(html.contains("<title>A &amp; B</title>"), "{html}");
+        assert!(
+            html.contains(
+                "<h2><span class=\"number\">1</span> Intro</h2>\n<a id=\"sec:intro\"></a>"
+            ),
+            "{html}"
+        );
+        assert!(
+            html.contains("<h3><span class=\"number\">1.1</span> Math</h3>"),
+            "{html}"
+        );
+        assert!(
+            html.contains("See <a href=\"#fig:cat\">1</a>, <a href=\"#eq:e\">(1)</a> and <span class=\"math inline\">\\(x &lt; 1\\)</span>."),
+            "{html}"
+        );
+        assert!(
+            html.contains("<img src=\"images/cat.png\" alt=\"images/cat\">"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<figcaption><span class=\"number\">Figure 1:</span> A cat</figcaption>"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<div class=\"math display\" id=\"eq:e\">\\[e = mc^2\\]<span class=\"equation-number\">(1)</span></div>"),
+            "{html}"
+        );
+        assert!(
+            html.contains("Back to <a href=\"#sec:intro\">1</a>."),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn escape_code_once() {
+        let ast = parse_latex(
+            "\\begin{document}\nUse \\texttt{a<b & c} or \\verb|x<y|.\n\n\\end{document}\n"
+                .to_string(),
+        )
+        .expect("Valid Latex");
+        let html = to_html(&ast, &|path| path.to_string());
+
+        assert!(
+            html.contains("Use <code>a&lt;b &amp; c</code> or <code>x&lt;y</code>."),
+            "{html}"
+        );
+    }
+}
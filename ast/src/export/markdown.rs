@@ -0,0 +1,343 @@
+use crate::errors::AstError;
+use crate::export::{
+    convert_inline, is_layout_command, take_labels, InlineFormat, Numbering, Style,
+};
+use crate::node::{ExpandableData, LeafData, MathKind, NodeRef, NodeType};
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::Uuid;
+
+/// Environments which are numbered and whose captions are prefixed with their name and number
+const FLOATS: [(&str, &str); 2] = [("figure", "Figure"), ("table", "Table")];
+const ITEM: &str = "\\item";
+
+/// Converts the document (or the subtree beneath `target`) into CommonMark with math, as it is
+/// understood by most wikis and issue trackers.
+/// The headings of a subtree start at `#`, but its numbers are the ones in the whole document.
+/// `resolve_image` maps the path of an image in the document to the path used in the Markdown
+/// code.
+pub fn to_markdown(
+    ast: &TexlaAst,
+    target: Option<Uuid>,
+    resolve_image: &dyn Fn(&str) -> String,
+) -> Result<String, AstError> {
+    let base_level = match target {
+        Some(uuid) => {
+            ast.validate_node(uuid)?;
+            ast.node_level(uuid).unwrap_or(ast.highest_level)
+        }
+        None => ast.highest_level,
+    };
+    let mut exporter = MarkdownExporter {
+        resolve_image,
+        highest_level: ast.highest_level,
+        base_level,
+        target,
+        range: None,
+        numbering: Default::default(),
+        floats: vec![],
+        list: None,
+        markdown: String::new(),
+    };
+    // labels may be referenced before they are defined, so the first pass only collects them
+    exporter.node(&ast.root, ast.highest_level);
+    exporter.numbering.restart();
+    exporter.markdown.clear();
+    exporter.node(&ast.root, ast.highest_level);
+
+    let markdown = match exporter.range {
+        Some((start, end)) => &exporter.markdown[start..end],
+        None => &exporter.markdown,
+    };
+    Ok(markdown.trim_end().to_string() + "\n")
+}
+
+struct MarkdownExporter<'a> {
+    resolve_image: &'a dyn Fn(&str) -> String,
+    highest_level: i8,
+    /// The level of the headings starting with `#`
+    base_level: i8,
+    target: Option<Uuid>,
+    /// The part of `markdown` which belongs to the subtree beneath `target`
+    range: Option<(usize, usize)>,
+    numbering: Numbering,
+    /// The names and numbers of the floats the current node is part of
+    floats: Vec<(String, String)>,
+    /// The list the current node is part of and whether it is ordered
+    list: Option<bool>,
+    markdown: String,
+}
+
+impl MarkdownExporter<'_> {
+    fn node(&mut self, node_ref: &NodeRef, level: i8) {
+        let uuid = node_ref.lock().unwrap().uuid;
+        let start = self.markdown.len();
+        self.node_content(node_ref, level);
+        if Some(uuid) == self.target {
+            self.range = Some((start, self.markdown.len()));
+        }
+    }
+
+    fn node_content(&mut self, node_ref: &NodeRef, level: i8) {
+        let node = node_ref.lock().unwrap();
+        let (data, children) = match &node.node_type {
+            NodeType::Leaf { data } => {
+                let data = data.clone();
+                drop(node);
+                self.leaf(data);
+                return;
+            }
+            NodeType::Expandable { data, children } => (data.clone(), children.clone()),
+        };
+        let children_level = node.node_type.children_level(level);
+        drop(node);
+
+        let render_children = |this: &mut Self| {
+            for child_ref in &children {
+                this.node(child_ref, children_level);
+            }
+        };
+
+        match data {
+            ExpandableData::Segment { heading, counted } => {
+                let depth = (level - self.highest_level).max(0) as usize;
+                let number = counted.then(|| self.numbering.next_segment(depth));
+                self.numbering.record_labels(&heading, number.as_deref());
+
+                let hashes = "#".repeat(((level - self.base_level).max(0) as usize + 1).min(6));
+                let heading = self.inline(&heading);
+                self.markdown += &match &number {
+                    Some(number) => format!("{hashes} {number} {heading}\n\n"),
+                    None => format!("{hashes} {heading}\n\n"),
+                };
+                self.numbering.enter(number);
+                render_children(self);
+                self.numbering.leave();
+            }
+            ExpandableData::Environment { name } => {
+                let base_name = name.trim_end_matches('*');
+                let list = match base_name {
+                    "itemize" | "description" => Some(false),
+                    "enumerate" => Some(true),
+                    _ => None,
+                };
+                if let Some((_, caption_name)) = FLOATS.iter().find(|(env, _)| *env == base_name) {
+                    let number = self.numbering.next(base_name);
+                    self.floats.push((caption_name.to_string(), number.clone()));
+                    self.numbering.enter(Some(number));
+                    render_children(self);
+                    self.numbering.leave();
+                    self.floats.pop();
+                } else {
+                    let outer_list = std::mem::replace(&mut self.list, list);
+                    render_children(self);
+                    self.list = outer_list;
+                }
+            }
+            ExpandableData::Dummy {
+                before_children,
+                after_children,
+                ..
+            } => {
+                self.raw_latex(&before_children);
+                render_children(self);
+                self.raw_latex(&after_children);
+            }
+            ExpandableData::ConflictSide { ours, .. } => {
+                let side = if ours { "ours" } else { "theirs" };
+                self.markdown += &format!("<!-- conflict: {side} -->\n\n");
+                render_children(self);
+            }
+            ExpandableData::Document { .. }
+            | ExpandableData::File { .. }
+            | ExpandableData::Conflict => render_children(self),
+        }
+    }
+
+    fn leaf(&mut self, data: LeafData) {
+        match data {
+            LeafData::Text { text } => {
+                self.numbering.record_labels(&text, None);
+                match self.list {
+                    Some(ordered) => {
+                        let items = text.split(ITEM).filter(|item| !item.trim().is_empty());
+                        for (index, item) in items.enumerate() {
+                            let marker = match ordered {
+                                true => format!("{}.", index + 1),
+                                false => "-".to_string(),
+                            };
+                            self.markdown += &format!("{marker} {}\n", self.inline(item.trim()));
+                        }
+                        self.markdown += "\n";
+                    }
+                    None => self.markdown += &format!("{}\n\n", self.inline(text.trim())),
+                }
+            }
+            LeafData::Math { kind, content } => {
+                let (labels, content) = take_labels(&content);
+                let number = match kind {
+                    MathKind::Equation | MathKind::Align => Some(self.numbering.next("equation")),
+                    _ => None,
+                };
+                for label in &labels {
+                    self.numbering.record_label(label, number.as_deref());
+                    // the blank line ends the HTML block
+                    self.markdown += &Markdown.anchor(label);
+                    self.markdown += "\n\n";
+                }
+                let content = match kind {
+                    MathKind::Align => format!("\\begin{{aligned}}{content}\\end{{aligned}}"),
+                    _ => content,
+                };
+                let tag = number
+                    .map(|number| format!(" \\tag{{{number}}}"))
+                    .unwrap_or_default();
+                self.markdown += &format!("$$\n{}{tag}\n$$\n\n", content.trim());
+            }
+            LeafData::Image { path, .. } => {
+                let source = (self.resolve_image)(&path);
+                let alt = match self.floats.last() {
+                    Some((name, number)) => format!("{name} {number}"),
+                    None => path.clone(),
+                };
+                self.markdown += &format!("![{}]({})\n\n", Markdown.escape(&alt), source);
+            }
+            LeafData::Label { label } => {
+                self.numbering.record_label(&label, None);
+                self.markdown += &Markdown.anchor(&label);
+                self.markdown += "\n\n";
+            }
+            LeafData::Caption { caption } => {
+                self.numbering.record_labels(&caption, None);
+                let caption = self.inline(&caption);
+                self.markdown += &match self.floats.last() {
+                    Some((name, number)) => format!("*{name} {number}: {caption}*\n\n"),
+                    None => format!("*{caption}*\n\n"),
+                };
+            }
+            LeafData::Comment { comment } => {
+                let comment = comment
+                    .lines()
+                    .map(|line| line.trim_start_matches('%').trim())
+                    .collect::<Vec<&str>>()
+                    .join("\n")
+                    // a comment must not contain its end marker
+                    .replace("--", "- -");
+                self.markdown += &format!("<!-- {comment} -->\n\n");
+            }
+        }
+    }
+
+    /// LaTeX code without a structure known to TeXLa is shown as it is.
+    fn raw_latex(&mut self, latex: &str) {
+        if !latex.trim().is_empty() && !is_layout_command(latex) {
+            self.markdown += &format!("```latex\n{}\n```\n\n", latex.trim());
+        }
+    }
+
+    fn inline(&self, latex: &str) -> String {
+        convert_inline(latex, &Markdown, &self.numbering.labels)
+    }
+}
+
+struct Markdown;
+
+impl InlineFormat for Markdown {
+    fn escape(&self, text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+        for char in text.chars() {
+            if "\\`*_[]<>$".contains(char) {
+                escaped.push('\\');
+            }
+            escaped.push(char);
+        }
+        escaped
+    }
+
+    fn math(&self, latex: &str) -> String {
+        format!("${}$", latex.trim())
+    }
+
+    fn styled(&self, style: Style, content: String) -> String {
+        match style {
+            Style::Bold => format!("**{content}**"),
+            Style::Italic => format!("*{content}*"),
+            Style::Code => code_span(&content),
+        }
+    }
+
+    fn link(&self, url: &str, content: String) -> String {
+        format!("[{content}]({url})")
+    }
+
+    fn anchor(&self, label: &str) -> String {
+        format!("<a id=\"{}\"></a>", label.replace('"', "&quot;"))
+    }
+
+    fn reference(&self, label: &str, text: &str) -> String {
+        format!("[{}](#{})", self.escape(text), label)
+    }
+
+    fn line_break(&self) -> String {
+        "\\\n".to_string()
+    }
+}
+
+/// Encloses `content` in a code span, whose content is shown literally. The fence is longer than
+/// any run of backticks in the content, so that it cannot end the span early.
+fn code_span(content: &str) -> String {
+    let longest_run = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run + 1);
+    // one space on both sides is stripped from the content, so that it can start or end with a
+    // backtick
+    let padding = match content.starts_with('`')
+        || content.ends_with('`')
+        || (content.starts_with(' ') && content.ends_with(' '))
+    {
+        true => " ",
+        false => "",
+    };
+    format!("{fence}{padding}{content}{padding}{fence}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::markdown::to_markdown;
+    use crate::operation::test::find_uuid_by_content;
+    use crate::parser::parse_latex;
+
+    const LATEX: &str = "\\begin{document}\n\\section{Intro}\n% a comment\nSome \\textbf{bold} text with $x_1$.\n\n\\begin{itemize}\n\\item First\n\\item Second\n\\end{itemize}\n\\section{Results}\n\\label{sec:results}\n\\subsection{Details}\nSee \\ref{sec:results} and \\eqref{eq:e}.\n\n\\begin{equation}\ne = mc^2 \\label{eq:e}\n\\end{equation}\n\\begin{figure}\n\\includegraphics{cat}\n\\caption{A cat}\n\\end{figure}\n\\end{document}\n";
+
+    #[test]
+    fn export_document() {
+        let ast = parse_latex(LATEX.to_string()).expect("Valid Latex");
+        let markdown = to_markdown(&ast, None, &|path| format!("{path}.png")).unwrap();
+
+        assert_eq!(
+            markdown,
+            "# 1 Intro\n\n<!-- a comment -->\n\nSome **bold** text with $x_1$.\n\n- First\n- Second\n\n# 2 Results\n\n<a id=\"sec:results\"></a>\n\n## 2.1 Details\n\nSee [2](#sec:results) and [(1)](#eq:e).\n\n<a id=\"eq:e\"></a>\n\n$$\ne = mc^2 \\tag{1}\n$$\n\n![Figure 1](cat.png)\n\n*Figure 1: A cat*\n"
+        );
+    }
+
+    #[test]
+    fn code() {
+        let ast = parse_latex(
+            "\\begin{document}\nUse \\texttt{a_b} or \\verb|x``y|.\n\n\\end{document}\n"
+                .to_string(),
+        )
+        .expect("Valid Latex");
+        let markdown = to_markdown(&ast, None, &|path| path.to_string()).unwrap();
+
+        assert_eq!(markdown, "Use `a_b` or ```x``y```.\n");
+    }
+
+    #[test]
+    fn export_subtree() {
+        let ast = parse_latex(LATEX.to_string()).expect("Valid Latex");
+        let uuid = find_uuid_by_content(&ast, "Details").unwrap();
+        let markdown = to_markdown(&ast, Some(uuid), &|path| path.to_string()).unwrap();
+
+        assert!(markdown.starts_with("# 2.1 Details\n\n"), "{markdown}");
+        assert!(!markdown.contains("Intro"), "{markdown}");
+        assert!(to_markdown(&ast, Some(123456), &|path| path.to_string()).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use crate::bibliography::bibliography_paths;
+use crate::errors::AstError;
+use crate::latex_constants::*;
+use crate::node::{ExpandableData, NodeType};
+use crate::options::StringificationOptions;
+use crate::reference_index::find_keys;
+use crate::texla_ast::TexlaAst;
+use crate::texla_constants::{FILE_BEGIN_MARK, FILE_END_MARK};
+use crate::uuid_provider::Uuid;
+
+/// A document consisting of a single subtree of another document, together with the files it
+/// needs to be compiled.
+#[derive(Debug, PartialEq)]
+pub struct Standalone {
+    /// The LaTeX code of the document in a single file
+    pub latex: String,
+    /// The paths of the images included in the subtree, as they are written in the LaTeX code
+    pub images: Vec<String>,
+    /// The keys cited in the subtree
+    pub citations: Vec<String>,
+    /// The paths of the bibliography files of the original document
+    pub bibliographies: Vec<String>,
+}
+
+/// Creates a document with the preamble of `ast` containing only the subtree beneath `target`.
+/// Files included in the subtree are inlined. If the subtree cites anything, the bibliography of
+/// the original document is printed at its end.
+pub fn to_standalone(
+    ast: &TexlaAst,
+    target: Uuid,
+    options: &StringificationOptions,
+) -> Result<Standalone, AstError> {
+    ast.validate_node(target)?;
+    let level = ast.node_level(target).unwrap_or(ast.highest_level);
+    let subtree = ast
+        .get_node(target)
+        .lock()
+        .unwrap()
+        .to_latex(level, options)?;
+    let subtree = inline_files(&subtree);
+
+    let (preamble, postamble) = match &ast.root.lock().unwrap().node_type {
+        NodeType::Expandable {
+            data:
+                ExpandableData::Document {
+                    preamble,
+                    postamble,
+                },
+            ..
+        } => (preamble.clone(), postamble.clone()),
+        _ => (String::new(), String::new()),
+    };
+    if target == ast.root.lock().unwrap().uuid {
+        // the whole document is exported
+        return Ok(Standalone {
+            images: arguments(&subtree, &[INCLUDEGRAPHICS]),
+            citations: arguments(&subtree, &CITATION_COMMANDS),
+            bibliographies: bibliography_paths(&subtree),
+            latex: subtree,
+        });
+    }
+
+    let document = ast
+        .root
+        .lock()
+        .unwrap()
+        .to_latex(ast.highest_level, options)?;
+    let document = inline_files(&document);
+    let citations = arguments(&subtree, &CITATION_COMMANDS);
+    let mut bibliography = String::new();
+    if !citations.is_empty() && !subtree.contains(BIBLIOGRAPHY) {
+        if let Some(style) = arguments(&document, &[BIBLIOGRAPHY_STYLE]).first() {
+            bibliography += &format!("{BIBLIOGRAPHY_STYLE}{{{style}}}\n");
+        }
+        let names = arguments(&document, &[BIBLIOGRAPHY]);
+        if !names.is_empty() {
+            bibliography += &format!("{BIBLIOGRAPHY}{{{}}}\n", names.join(","));
+        } else if preamble.contains(ADD_BIB_RESOURCE) && !subtree.contains(PRINT_BIBLIOGRAPHY) {
+            bibliography += &format!("{PRINT_BIBLIOGRAPHY}\n");
+        }
+    }
+
+    Ok(Standalone {
+        latex: format!(
+            "{preamble}{DOCUMENT_BEGIN}\n{subtree}{bibliography}{DOCUMENT_END}\n{postamble}"
+        ),
+        images: arguments(&subtree, &[INCLUDEGRAPHICS]),
+        citations,
+        bibliographies: bibliography_paths(&document),
+    })
+}
+
+/// Removes the marks around the content of included files.
+pub(crate) fn inline_files(latex: &str) -> String {
+    latex
+        .lines()
+        .filter(|line| !line.starts_with(FILE_BEGIN_MARK) && !line.starts_with(FILE_END_MARK))
+        .map(|line| line.to_string() + "\n")
+        .collect()
+}
+
+/// Returns the distinct keys in the arguments of the given commands in document order.
+fn arguments(latex: &str, commands: &[&str]) -> Vec<String> {
+    let mut arguments: Vec<String> = vec![];
+    for key in find_keys(latex, commands) {
+        let argument = latex[key].to_string();
+        if !arguments.contains(&argument) {
+            arguments.push(argument);
+        }
+    }
+    arguments
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::standalone::to_standalone;
+    use crate::operation::test::find_uuid_by_content;
+    use crate::parser::parse_latex;
+
+    #[test]
+    fn export_section() {
+        let latex = "\\documentclass{article}\n\\usepackage{graphicx}\n\\begin{document}\n\\section{Intro}\nAs shown by \\cite{knuth84}.\n\n\\section{Results}\n% TEXLA FILE BEGIN {results}\nWe cite \\cite{lamport94, knuth84} here.\n\n\\includegraphics[width=5cm]{images/plot}\n% TEXLA FILE END {results}\n\\bibliographystyle{plain}\n\\bibliography{refs}\n\\end{document}\n";
+        let ast = parse_latex(latex.to_string()).expect("Valid Latex");
+        let uuid = find_uuid_by_content(&ast, "Results").unwrap();
+
+        let standalone = to_standalone(&ast, uuid, &Default::default()).unwrap();
+
+        assert_eq!(
+            standalone.latex,
+            "\\documentclass{article}\n\\usepackage{graphicx}\n\\begin{document}\n\\section{Results}\nWe cite \\cite{lamport94, knuth84} here.\n\n\\includegraphics[width=5cm]{images/plot}\n\\bibliographystyle{plain}\n\\bibliography{refs}\n\n\\end{document}\n"
+        );
+        assert_eq!(standalone.images, vec!["images/plot"]);
+        assert_eq!(standalone.citations, vec!["lamport94", "knuth84"]);
+        assert_eq!(standalone.bibliographies, vec!["refs.bib"]);
+
+        // the bibliography is not part of the first section
+        let uuid = find_uuid_by_content(&ast, "Intro").unwrap();
+        let standalone = to_standalone(&ast, uuid, &Default::default()).unwrap();
+        assert!(
+            standalone.latex.ends_with(
+                "\\cite{knuth84}.\n\n\\bibliographystyle{plain}\n\\bibliography{refs}\n\\end{document}\n"
+            ),
+            "{}",
+            standalone.latex
+        );
+        assert!(standalone.images.is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::errors::AstError;
+use crate::export::standalone::inline_files;
+use crate::latex_constants::*;
+use crate::node::{visit_subtree, LeafData, NodeType};
+use crate::options::{NodeFilter, StringificationOptions};
+use crate::reference_index::find_keys;
+use crate::texla_ast::TexlaAst;
+
+const COMMENT_PREFIX: char = '%';
+/// Environments whose content is printed as it is, so `%` does not start a comment in them
+const VERBATIM_ENVIRONMENTS: [&str; 3] = ["verbatim", "lstlisting", "minted"];
+
+/// Converts the document into a single file as it is expected by arXiv and most journals: all
+/// files are inlined and comments as well as TeXLa metadata are removed.
+/// Only the nodes selected by `node_filter` are part of the submission. `resolve_image` maps the
+/// path of each image (of an image node) to the path it has in the submission.
+pub fn to_submission(
+    ast: &TexlaAst,
+    node_filter: &NodeFilter,
+    resolve_image: &mut dyn FnMut(&str) -> String,
+) -> Result<String, AstError> {
+    let options = StringificationOptions {
+        include_comments: false,
+        include_metadata: false,
+        node_filter: node_filter.clone(),
+    };
+    let latex = ast
+        .root
+        .lock()
+        .unwrap()
+        .to_latex(ast.highest_level, &options)?;
+    let mut latex = strip_comments(&inline_files(&latex));
+
+    let mut images = vec![];
+    visit_subtree(&ast.root, &mut |node| {
+        if let NodeType::Leaf {
+            data: LeafData::Image { path, .. },
+        } = &node.node_type
+        {
+            images.push(path.clone());
+        }
+    });
+    // replace from back to front to keep the ranges valid
+    for path in find_keys(&latex, &[INCLUDEGRAPHICS]).into_iter().rev() {
+        if images.contains(&latex[path.clone()].to_string()) {
+            let resolved = resolve_image(&latex[path.clone()]);
+            latex.replace_range(path, &resolved);
+        }
+    }
+
+    Ok(latex)
+}
+
+/// Removes all comments, except in verbatim environments. Lines only consisting of a comment are
+/// removed completely, other comments are cut off after the `%`, which keeps suppressing the
+/// space at the end of the line.
+pub(crate) fn strip_comments(latex: &str) -> String {
+    let mut stripped = String::with_capacity(latex.len());
+    let mut verbatim: Option<&str> = None;
+
+    for line in latex.lines() {
+        match verbatim {
+            Some(environment) => {
+                if line.contains(&format!("{END}{{{environment}}}")) {
+                    verbatim = None;
+                }
+                stripped += line;
+            }
+            None => {
+                verbatim = VERBATIM_ENVIRONMENTS
+                    .into_iter()
+                    .find(|environment| line.contains(&format!("{BEGIN}{{{environment}}}")));
+                match comment_start(line) {
+                    Some(start) if line[..start].trim().is_empty() => continue,
+                    Some(start) => stripped += &line[..=start],
+                    None => stripped += line,
+                }
+            }
+        }
+        stripped += "\n";
+    }
+    stripped
+}
+
+/// Returns the position of the `%` starting a comment (which is not escaped by a backslash).
+fn comment_start(line: &str) -> Option<usize> {
+    let mut backslashes = 0;
+    for (index, char) in line.char_indices() {
+        match char {
+            COMMENT_PREFIX if backslashes % 2 == 0 => return Some(index),
+            '\\' => backslashes += 1,
+            _ => backslashes = 0,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::submission::{strip_comments, to_submission};
+    use crate::parser::parse_latex;
+
+    #[test]
+    fn remove_comments() {
+        assert_eq!(
+            strip_comments("% a comment\n50\\% of all % a comment\nline%\n\\begin{verbatim}\n% kept\n\\end{verbatim}\n  % indented\n"),
+            "50\\% of all %\nline%\n\\begin{verbatim}\n% kept\n\\end{verbatim}\n"
+        );
+    }
+
+    #[test]
+    fn flatten_document() {
+        let latex = "\\documentclass{article}\n% the packages\n\\usepackage{graphicx}\n\\begin{document}\n\\section{Intro}\n% TEXLA FILE BEGIN {intro}\n% a comment\nSome text % with a comment\n\n\\includegraphics[width=3cm]{../figures/plot}\n% TEXLA FILE END {intro}\n\\end{document}\n";
+        let ast = parse_latex(latex.to_string()).expect("Valid Latex");
+        let mut images = vec![];
+
+        let submission = to_submission(&ast, &Default::default(), &mut |path| {
+            images.push(path.to_string());
+            "figures/plot.pdf".to_string()
+        })
+        .unwrap();
+
+        assert_eq!(
+            submission,
+            "\\documentclass{article}\n\\usepackage{graphicx}\n\\begin{document}\n\\section{Intro}\nSome text\n\n\\includegraphics[width=3cm]{figures/plot.pdf}\n\\end{document}\n"
+        );
+        assert_eq!(images, vec!["../figures/plot"]);
+    }
+}
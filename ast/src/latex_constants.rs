@@ -1,6 +1,7 @@
 //! `latex_constants` defines some tokens used in Latex to as delimiters or keywords.
 // LaTeX files and paths
 pub const LATEX_FILE_EXTENSION: &str = "tex";
+pub const BIB_FILE_EXTENSION: &str = "bib";
 pub const LATEX_PATH_SEPARATOR: &str = "/";
 
 // commands
@@ -10,6 +11,33 @@ pub const INPUT: &str = "\\input";
 pub(crate) const INCLUDEGRAPHICS: &str = "\\includegraphics";
 pub(crate) const CAPTION: &str = "\\caption";
 pub(crate) const LABEL: &str = "\\label";
+pub(crate) const REFERENCE_COMMANDS: [&str; 8] = [
+    "\\ref",
+    "\\eqref",
+    "\\autoref",
+    "\\cref",
+    "\\Cref",
+    "\\pageref",
+    "\\nameref",
+    "\\vref",
+];
+pub(crate) const CITATION_COMMANDS: [&str; 10] = [
+    "\\cite",
+    "\\citep",
+    "\\citet",
+    "\\citeauthor",
+    "\\citeyear",
+    "\\nocite",
+    "\\parencite",
+    "\\textcite",
+    "\\autocite",
+    "\\footcite",
+];
+pub(crate) const BIBLIOGRAPHY: &str = "\\bibliography";
+pub(crate) const BIBLIOGRAPHY_STYLE: &str = "\\bibliographystyle";
+pub(crate) const ADD_BIB_RESOURCE: &str = "\\addbibresource";
+pub(crate) const PRINT_BIBLIOGRAPHY: &str = "\\printbibliography";
+pub(crate) const APPENDIX: &str = "\\appendix";
 
 // environments
 pub(crate) const DOCUMENT_BEGIN: &str = "\\begin{document}";
@@ -5,17 +5,30 @@ use errors::AstError;
 use operation::Operation;
 use options::StringificationOptions;
 
+pub mod bibliography;
+pub mod citation;
+mod conflict;
+pub mod diagnostics;
+pub mod diff;
 pub mod errors;
+pub mod export;
 pub mod latex_constants;
+pub mod matching;
+pub mod merge;
 mod meta_data;
 pub(crate) mod node;
 pub mod operation;
 pub mod options;
+pub mod outline;
 mod parser;
+pub mod reference_index;
+pub mod source_map;
 pub mod texla_ast;
 pub mod texla_constants;
 mod uuid_provider;
 
+pub use uuid_provider::Uuid;
+
 /// The **Ast (Abstract Syntax Tree)** is the central Data structure of this Library and is used to represent LaTeX Documents programmatically.\
 /// This Trait requires methods to convert to LaTeX as well as to create the Ast from LaTeX.\
 /// Additionally it requires the implementation of the Strategy Pattern in order to make modifications on the Ast.\
@@ -34,5 +47,6 @@ pub trait Ast: Sized + Send + Sync + Serialize {
     fn to_latex(&self, options: StringificationOptions) -> Result<String, AstError>;
     /// Modifies this Ast by applying the provided `Operation`.
     /// [Operation] is a Strategy template. To implement this, `execute_on()` should be called on the operation and this Ast should be passed.
-    fn execute(&mut self, operation: Box<dyn Operation<Self>>) -> Result<(), AstError>;
+    /// Returns the Uuid of the Node created by the Operation, if there is one.
+    fn execute(&mut self, operation: Box<dyn Operation<Self>>) -> Result<Option<Uuid>, AstError>;
 }
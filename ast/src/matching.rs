@@ -0,0 +1,271 @@
+//! `matching` finds the nodes of two Asts which represent the same part of a document.
+//! This is needed because all Uuids change when a document is parsed again.
+use std::collections::{HashMap, HashSet};
+
+use crate::node::{Node, NodeRef, NodeType};
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::Uuid;
+
+/// Maps the Uuids of nodes in `old` to the Uuids of the corresponding nodes in `new`.
+/// Nodes without a counterpart (because they were added, deleted or changed beyond recognition)
+/// are not contained in the result.
+///
+/// 1. Children of matched nodes are matched by the longest common subsequence of their contents.
+/// 2. Identical subtrees which occur exactly once among the unmatched nodes of both Asts are
+///    matched (moved nodes).
+/// 3. The remaining children of matched nodes are matched by position if both sides have the same
+///    number of them (edited nodes).
+pub fn match_nodes(old: &TexlaAst, new: &TexlaAst) -> HashMap<Uuid, Uuid> {
+    let mut matching = Matching::default();
+    match_subtrees(&old.root, &new.root, &mut matching);
+
+    let unmatched_old = unmatched_subtrees(&old.root, &|uuid| matching.contains_old(uuid));
+    let unmatched_new = unmatched_subtrees(&new.root, &|uuid| matching.contains_new(uuid));
+
+    let mut old_counts: HashMap<&String, usize> = HashMap::new();
+    for (_, signature) in &unmatched_old {
+        *old_counts.entry(signature).or_default() += 1;
+    }
+    let mut new_by_signature: HashMap<&String, Vec<&NodeRef>> = HashMap::new();
+    for (new_ref, signature) in &unmatched_new {
+        new_by_signature.entry(signature).or_default().push(new_ref);
+    }
+
+    for (old_ref, signature) in &unmatched_old {
+        let already_matched = matching.contains_old(old_ref.lock().unwrap().uuid);
+        if already_matched || old_counts[signature] != 1 {
+            continue;
+        }
+        if let Some([new_ref]) = new_by_signature.get(signature).map(Vec::as_slice) {
+            match_subtrees(old_ref, new_ref, &mut matching);
+        }
+    }
+
+    match_remaining_children(&old.root, new, &mut matching);
+    matching.old_to_new
+}
+
+/// The matching built so far. The matched nodes of the new Ast are kept in a set of their own,
+/// so that they do not have to be collected from the map for every node.
+#[derive(Default)]
+struct Matching {
+    old_to_new: HashMap<Uuid, Uuid>,
+    matched_new: HashSet<Uuid>,
+}
+
+impl Matching {
+    fn insert(&mut self, old_uuid: Uuid, new_uuid: Uuid) {
+        self.old_to_new.insert(old_uuid, new_uuid);
+        self.matched_new.insert(new_uuid);
+    }
+
+    fn get(&self, old_uuid: Uuid) -> Option<Uuid> {
+        self.old_to_new.get(&old_uuid).copied()
+    }
+
+    fn contains_old(&self, old_uuid: Uuid) -> bool {
+        self.old_to_new.contains_key(&old_uuid)
+    }
+
+    fn contains_new(&self, new_uuid: Uuid) -> bool {
+        self.matched_new.contains(&new_uuid)
+    }
+}
+
+fn match_subtrees(old_ref: &NodeRef, new_ref: &NodeRef, matching: &mut Matching) {
+    let old_uuid = old_ref.lock().unwrap().uuid;
+    let new_uuid = new_ref.lock().unwrap().uuid;
+    matching.insert(old_uuid, new_uuid);
+
+    let old_children = children_of(old_ref);
+    let new_children = children_of(new_ref);
+    let old_signatures: Vec<String> = old_children.iter().map(signature_of_ref).collect();
+    let new_signatures: Vec<String> = new_children.iter().map(signature_of_ref).collect();
+    for (old_index, new_index) in longest_common_subsequence(&old_signatures, &new_signatures) {
+        match_subtrees(&old_children[old_index], &new_children[new_index], matching);
+    }
+}
+
+fn match_remaining_children(old_ref: &NodeRef, new: &TexlaAst, matching: &mut Matching) {
+    let old_children = children_of(old_ref);
+    let old_uuid = old_ref.lock().unwrap().uuid;
+    if let Some(new_uuid) = matching.get(old_uuid) {
+        let unmatched_old: Vec<&NodeRef> = old_children
+            .iter()
+            .filter(|child_ref| !matching.contains_old(child_ref.lock().unwrap().uuid))
+            .collect();
+        let unmatched_new: Vec<NodeRef> = children_of(&new.get_node(new_uuid))
+            .into_iter()
+            .filter(|child_ref| !matching.contains_new(child_ref.lock().unwrap().uuid))
+            .collect();
+        if unmatched_old.len() == unmatched_new.len() {
+            for (old_child_ref, new_child_ref) in unmatched_old.into_iter().zip(&unmatched_new) {
+                match_subtrees(old_child_ref, new_child_ref, matching);
+            }
+        }
+    }
+
+    for child_ref in &old_children {
+        match_remaining_children(child_ref, new, matching);
+    }
+}
+
+pub(crate) fn children_of(node_ref: &NodeRef) -> Vec<NodeRef> {
+    match &node_ref.lock().unwrap().node_type {
+        NodeType::Expandable { children, .. } => children.clone(),
+        NodeType::Leaf { .. } => vec![],
+    }
+}
+
+/// Returns the index pairs of a longest common subsequence of `old` and `new`.
+pub(crate) fn longest_common_subsequence<T: PartialEq>(
+    old: &[T],
+    new: &[T],
+) -> Vec<(usize, usize)> {
+    // usually only few elements change, so the common prefix and suffix are matched directly
+    // and the quadratic table is only built for the part in between
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    // lengths[i][j] is the length of the LCS of old_middle[i..] and new_middle[j..]
+    let mut lengths = vec![vec![0; new_middle.len() + 1]; old_middle.len() + 1];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lengths[i][j] = if old_middle[i] == new_middle[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|index| (index, index)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() && j < new_middle.len() {
+        if old_middle[i] == new_middle[j] {
+            pairs.push((prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs.extend(
+        (0..suffix).map(|offset| (old.len() - suffix + offset, new.len() - suffix + offset)),
+    );
+    pairs
+}
+
+/// Returns all nodes for which `is_matched` is false together with the signatures of their
+/// subtrees. Parents precede their children.
+fn unmatched_subtrees(root: &NodeRef, is_matched: &dyn Fn(Uuid) -> bool) -> Vec<(NodeRef, String)> {
+    let mut unmatched = vec![];
+    collect_unmatched(root, is_matched, &mut unmatched);
+    unmatched
+}
+
+/// Returns the signature of the subtree beneath `node_ref`.
+fn collect_unmatched(
+    node_ref: &NodeRef,
+    is_matched: &dyn Fn(Uuid) -> bool,
+    unmatched: &mut Vec<(NodeRef, String)>,
+) -> String {
+    let (uuid, own_signature, children) = {
+        let node = node_ref.lock().unwrap();
+        let children = match &node.node_type {
+            NodeType::Expandable { children, .. } => children.clone(),
+            NodeType::Leaf { .. } => vec![],
+        };
+        (node.uuid, signature(&node), children)
+    };
+    // reserve the place of this node before its children
+    let index = (!is_matched(uuid)).then(|| {
+        unmatched.push((node_ref.clone(), String::new()));
+        unmatched.len() - 1
+    });
+
+    let children_signatures: Vec<String> = children
+        .iter()
+        .map(|child_ref| collect_unmatched(child_ref, is_matched, unmatched))
+        .collect();
+    let subtree_signature = format!("{own_signature}[{}]", children_signatures.join(","));
+    if let Some(index) = index {
+        unmatched[index].1 = subtree_signature.clone();
+    }
+    subtree_signature
+}
+
+fn signature_of_ref(node_ref: &NodeRef) -> String {
+    signature(&node_ref.lock().unwrap())
+}
+
+/// The content of a node without its children, which is compared to find matching nodes.
+pub(crate) fn signature(node: &Node) -> String {
+    match &node.node_type {
+        NodeType::Expandable { data, .. } => serde_json::to_string(data),
+        NodeType::Leaf { data } => serde_json::to_string(data),
+    }
+    .expect("node data is always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::matching::match_nodes;
+    use crate::node::visit_subtree;
+    use crate::operation::test::find_uuid_by_content;
+    use crate::parser::parse_latex;
+
+    #[test]
+    fn match_identical_asts() {
+        let latex = fs::read_to_string("../test_resources/latex/lots_of_features.tex").unwrap();
+        let old = parse_latex(latex.clone()).expect("Valid Latex");
+        let new = parse_latex(latex).expect("Valid Latex");
+
+        let matching = match_nodes(&old, &new);
+        let mut node_count = 0;
+        visit_subtree(&old.root, &mut |_| node_count += 1);
+        assert_eq!(matching.len(), node_count);
+        let old_uuid = find_uuid_by_content(&old, "\\subsection{Math}").unwrap();
+        let new_uuid = find_uuid_by_content(&new, "\\subsection{Math}").unwrap();
+        assert_eq!(matching[&old_uuid], new_uuid);
+    }
+
+    #[test]
+    fn match_edited_and_moved_nodes() {
+        let old = parse_latex(
+            "\\begin{document}\n\\section{A}\nText a\n\n\\section{B}\nText b\n\n\\section{C}\nText c\n\n\\end{document}\n"
+                .to_string(),
+        )
+        .expect("Valid Latex");
+        let new = parse_latex(
+            "\\begin{document}\n\\section{A2}\nText a\n\n\\section{C}\nText c\n\n\\section{B}\nText b\n\n\\end{document}\n"
+                .to_string(),
+        )
+        .expect("Valid Latex");
+
+        let matching = match_nodes(&old, &new);
+        for (old_content, new_content) in [
+            ("\\section{A}", "\\section{A2}"),
+            ("\\section{B}", "\\section{B}"),
+            ("\\section{C}", "\\section{C}"),
+            ("Text b", "Text b"),
+        ] {
+            let old_uuid = find_uuid_by_content(&old, old_content).unwrap();
+            let new_uuid = find_uuid_by_content(&new, new_content).unwrap();
+            assert_eq!(matching.get(&old_uuid), Some(&new_uuid), "{old_content}");
+        }
+    }
+}
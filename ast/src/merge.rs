@@ -0,0 +1,458 @@
+//! `merge` combines two versions of a document which were both derived from a common base version
+//! (e.g. local and remote changes when pulling) node by node instead of line by line.
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::errors::AstError;
+use crate::latex_constants::{DOCUMENT_BEGIN, DOCUMENT_END};
+use crate::matching::{children_of, longest_common_subsequence, match_nodes, signature};
+use crate::node::{Node, NodeRef};
+use crate::options::StringificationOptions;
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::Uuid;
+use crate::Ast;
+
+// the markers git uses for conflicts
+pub const CONFLICT_BEGIN_MARK: &str = "<<<<<<< ours";
+pub const CONFLICT_SEPARATOR_MARK: &str = "=======";
+pub const CONFLICT_END_MARK: &str = ">>>>>>> theirs";
+
+/// The result of a three-way merge.
+#[derive(Serialize, Debug)]
+pub struct MergeResult {
+    /// The merged LaTeX code. Conflicting parts are enclosed in git conflict markers.
+    pub latex: String,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// A part of the document which was changed differently in both versions.
+/// Each field contains the LaTeX code of the part in one version (empty if it was deleted).
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MergeConflict {
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+/// Merges the changes from `base` to `ours` and from `base` to `theirs`.
+///
+/// Changes of different nodes (including additions, deletions and reorderings of different
+/// siblings) are combined. A conflict arises if
+/// - the content or metadata of a node was changed differently in both versions or
+/// - the children between two unchanged siblings were changed differently in both versions or
+/// - a node was deleted in one version and changed in the other one.
+///
+/// Nodes which were moved to another parent are treated as deleted and added again.
+pub fn merge(base: &TexlaAst, ours: &TexlaAst, theirs: &TexlaAst) -> Result<MergeResult, AstError> {
+    let ours_matching = match_nodes(base, ours);
+    let theirs_matching = match_nodes(base, theirs);
+    let mut merge = Merge {
+        base,
+        ours,
+        theirs,
+        base_of_ours: invert(&ours_matching),
+        base_of_theirs: invert(&theirs_matching),
+        ours_matching,
+        theirs_matching,
+        options: Default::default(),
+        conflicts: vec![],
+    };
+
+    // the roots are always matched
+    let latex = merge.merge_nodes(&base.root, &ours.root, &theirs.root, ours.highest_level)?;
+    Ok(MergeResult {
+        latex,
+        conflicts: merge.conflicts,
+    })
+}
+
+/// Merges three versions of a LaTeX file (see [merge]).
+/// Files included by the main file (without a document environment) are supported as well.
+pub fn merge_latex(base: &str, ours: &str, theirs: &str) -> Result<MergeResult, AstError> {
+    let prefix = format!("{DOCUMENT_BEGIN}\n");
+    let suffix = format!("{DOCUMENT_END}\n");
+    let is_included_file = ![base, ours, theirs]
+        .iter()
+        .any(|latex| latex.contains(DOCUMENT_BEGIN));
+    let parse = |latex: &str| {
+        let latex = latex.replace("\r\n", "\n");
+        if is_included_file {
+            TexlaAst::from_latex(format!("{prefix}{latex}{suffix}"))
+        } else {
+            TexlaAst::from_latex(latex)
+        }
+    };
+
+    let mut result = merge(&parse(base)?, &parse(ours)?, &parse(theirs)?)?;
+    if is_included_file {
+        result.latex = result
+            .latex
+            .strip_prefix(&prefix)
+            .and_then(|latex| latex.strip_suffix(&suffix))
+            .map(String::from)
+            .unwrap_or(result.latex);
+    }
+    Ok(result)
+}
+
+fn invert(matching: &HashMap<Uuid, Uuid>) -> HashMap<Uuid, Uuid> {
+    matching.iter().map(|(from, to)| (*to, *from)).collect()
+}
+
+/// A child of a node in one of the merged versions
+#[derive(PartialEq)]
+enum Child {
+    /// A child which already existed in the base version, identified by its Uuid there
+    Base(Uuid),
+    /// A child which was added (or moved here from another parent), given by its LaTeX code
+    New(String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Ours,
+    Theirs,
+}
+
+struct Merge<'a> {
+    base: &'a TexlaAst,
+    ours: &'a TexlaAst,
+    theirs: &'a TexlaAst,
+    ours_matching: HashMap<Uuid, Uuid>,
+    theirs_matching: HashMap<Uuid, Uuid>,
+    base_of_ours: HashMap<Uuid, Uuid>,
+    base_of_theirs: HashMap<Uuid, Uuid>,
+    options: StringificationOptions,
+    conflicts: Vec<MergeConflict>,
+}
+
+impl Merge<'_> {
+    /// Merges three corresponding nodes and returns the LaTeX code of the merged subtree.
+    fn merge_nodes(
+        &mut self,
+        base_ref: &NodeRef,
+        ours_ref: &NodeRef,
+        theirs_ref: &NodeRef,
+        level: i8,
+    ) -> Result<String, AstError> {
+        let ours_changed = !same_content(base_ref, ours_ref);
+        let theirs_changed = !same_content(base_ref, theirs_ref);
+        let source_ref = match (ours_changed, theirs_changed) {
+            (false, _) => theirs_ref,
+            (true, false) => ours_ref,
+            (true, true) if same_content(ours_ref, theirs_ref) => ours_ref,
+            (true, true) => {
+                return self.conflict(
+                    self.latex(base_ref, level)?,
+                    self.latex(ours_ref, level)?,
+                    self.latex(theirs_ref, level)?,
+                );
+            }
+        };
+
+        let children_level = source_ref.lock().unwrap().node_type.children_level(level);
+        let children_latex = self.merge_children(base_ref, ours_ref, theirs_ref, children_level)?;
+        let source = source_ref.lock().unwrap();
+        Ok(source.to_latex_with_children(level, &self.options, children_latex)?)
+    }
+
+    fn merge_children(
+        &mut self,
+        base_ref: &NodeRef,
+        ours_ref: &NodeRef,
+        theirs_ref: &NodeRef,
+        level: i8,
+    ) -> Result<String, AstError> {
+        let base_children: Vec<Uuid> = children_of(base_ref)
+            .iter()
+            .map(|child_ref| child_ref.lock().unwrap().uuid)
+            .collect();
+        let ours_children = self.children(ours_ref, Side::Ours, &base_children, level)?;
+        let theirs_children = self.children(theirs_ref, Side::Theirs, &base_children, level)?;
+
+        // children which kept their order in both versions separate independent parts
+        let stable_in = |children: &[Child]| -> HashSet<Uuid> {
+            let uuids: Vec<Uuid> = children
+                .iter()
+                .filter_map(|child| match child {
+                    Child::Base(uuid) => Some(*uuid),
+                    Child::New(_) => None,
+                })
+                .collect();
+            longest_common_subsequence(&base_children, &uuids)
+                .into_iter()
+                .map(|(base_index, _)| base_children[base_index])
+                .collect()
+        };
+        let stable_in_ours = stable_in(&ours_children);
+        let anchors: Vec<Uuid> = stable_in(&theirs_children)
+            .intersection(&stable_in_ours)
+            .copied()
+            .collect();
+        let index_of = |children: &[Child], uuid: Uuid| {
+            children
+                .iter()
+                .position(|child| *child == Child::Base(uuid))
+                .expect("anchors exist in all versions")
+        };
+        let mut anchors: Vec<(usize, usize, usize)> = anchors
+            .into_iter()
+            .map(|uuid| {
+                let base_index = base_children
+                    .iter()
+                    .position(|base_uuid| *base_uuid == uuid)
+                    .expect("anchors exist in all versions");
+                (
+                    base_index,
+                    index_of(&ours_children, uuid),
+                    index_of(&theirs_children, uuid),
+                )
+            })
+            .collect();
+        anchors.sort();
+
+        let mut latex = String::new();
+        let (mut base_start, mut ours_start, mut theirs_start) = (0, 0, 0);
+        for (base_index, ours_index, theirs_index) in anchors.into_iter().chain([(
+            base_children.len(),
+            ours_children.len(),
+            theirs_children.len(),
+        )]) {
+            latex += &self.merge_part(
+                &base_children[base_start..base_index],
+                &ours_children[ours_start..ours_index],
+                &theirs_children[theirs_start..theirs_index],
+                &ours_children,
+                &theirs_children,
+                level,
+            )?;
+            if let Some(uuid) = base_children.get(base_index) {
+                latex += &self.merge_child(*uuid, level)?;
+            }
+            (base_start, ours_start, theirs_start) =
+                (base_index + 1, ours_index + 1, theirs_index + 1);
+        }
+        Ok(latex)
+    }
+
+    /// Merges the children between two anchors.
+    fn merge_part(
+        &mut self,
+        base: &[Uuid],
+        ours: &[Child],
+        theirs: &[Child],
+        all_ours: &[Child],
+        all_theirs: &[Child],
+        level: i8,
+    ) -> Result<String, AstError> {
+        let base_children: Vec<Child> = base.iter().map(|uuid| Child::Base(*uuid)).collect();
+        let chosen = if ours == base_children.as_slice() {
+            Some((Side::Theirs, theirs, all_theirs))
+        } else if theirs == base_children.as_slice() || ours == theirs {
+            Some((Side::Ours, ours, all_ours))
+        } else {
+            None
+        };
+
+        if let Some((side, chosen, all_chosen)) = chosen {
+            // a node deleted in the chosen version must not have been changed in the other one
+            let deleted_but_changed = base.iter().any(|uuid| {
+                !all_chosen.contains(&Child::Base(*uuid))
+                    && !self.unchanged_in(
+                        *uuid,
+                        if side == Side::Ours {
+                            Side::Theirs
+                        } else {
+                            Side::Ours
+                        },
+                    )
+            });
+            if !deleted_but_changed {
+                let mut latex = String::new();
+                for child in chosen {
+                    latex += &match child {
+                        Child::Base(uuid) => self.merge_child(*uuid, level)?,
+                        Child::New(child_latex) => child_latex.clone(),
+                    };
+                }
+                return Ok(latex);
+            }
+        }
+
+        let base_latex = self.children_latex(&base_children, Side::Ours, level, true)?;
+        let ours_latex = self.children_latex(ours, Side::Ours, level, false)?;
+        let theirs_latex = self.children_latex(theirs, Side::Theirs, level, false)?;
+        self.conflict(base_latex, ours_latex, theirs_latex)
+    }
+
+    /// Merges a child which exists in the base version and at least one other version.
+    fn merge_child(&mut self, uuid: Uuid, level: i8) -> Result<String, AstError> {
+        let base_ref = self.base.get_node(uuid);
+        match (
+            self.counterpart(uuid, Side::Ours),
+            self.counterpart(uuid, Side::Theirs),
+        ) {
+            (Some(ours_ref), Some(theirs_ref)) => {
+                self.merge_nodes(&base_ref, &ours_ref, &theirs_ref, level)
+            }
+            (Some(node_ref), None) | (None, Some(node_ref)) => self.latex(&node_ref, level),
+            (None, None) => Ok(String::new()),
+        }
+    }
+
+    /// Returns the children of a node in one version in terms of the children of the
+    /// corresponding base node.
+    fn children(
+        &self,
+        node_ref: &NodeRef,
+        side: Side,
+        base_children: &[Uuid],
+        level: i8,
+    ) -> Result<Vec<Child>, AstError> {
+        let base_of = match side {
+            Side::Ours => &self.base_of_ours,
+            Side::Theirs => &self.base_of_theirs,
+        };
+        children_of(node_ref)
+            .iter()
+            .map(|child_ref| {
+                let uuid = child_ref.lock().unwrap().uuid;
+                Ok(match base_of.get(&uuid) {
+                    Some(base_uuid) if base_children.contains(base_uuid) => Child::Base(*base_uuid),
+                    _ => Child::New(self.latex(child_ref, level)?),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the child of the node `side` which corresponds to the base node `uuid`, if it has
+    /// the same parent.
+    fn counterpart(&self, uuid: Uuid, side: Side) -> Option<NodeRef> {
+        let (matching, ast) = match side {
+            Side::Ours => (&self.ours_matching, self.ours),
+            Side::Theirs => (&self.theirs_matching, self.theirs),
+        };
+        let node_ref = ast.get_node(*matching.get(&uuid)?);
+        let parent_uuid = |node_ref: &NodeRef| {
+            let node = node_ref.lock().unwrap();
+            let parent = node.parent.as_ref()?.upgrade()?;
+            let uuid = parent.lock().unwrap().uuid;
+            Some(uuid)
+        };
+        let base_parent = parent_uuid(&self.base.get_node(uuid))?;
+        let parent = parent_uuid(&node_ref)?;
+        (matching.get(&base_parent) == Some(&parent)).then_some(node_ref)
+    }
+
+    /// Whether the subtree of the base node `uuid` is unchanged in the version `side`.
+    fn unchanged_in(&self, uuid: Uuid, side: Side) -> bool {
+        let level = self.ours.highest_level;
+        match self.counterpart(uuid, side) {
+            Some(node_ref) => {
+                self.latex(&node_ref, level).ok()
+                    == self.latex(&self.base.get_node(uuid), level).ok()
+            }
+            None => true, // deleted in both versions
+        }
+    }
+
+    fn children_latex(
+        &self,
+        children: &[Child],
+        side: Side,
+        level: i8,
+        from_base: bool,
+    ) -> Result<String, AstError> {
+        children
+            .iter()
+            .map(|child| match child {
+                Child::Base(uuid) if from_base => self.latex(&self.base.get_node(*uuid), level),
+                Child::Base(uuid) => match self.counterpart(*uuid, side) {
+                    Some(node_ref) => self.latex(&node_ref, level),
+                    None => Ok(String::new()),
+                },
+                Child::New(latex) => Ok(latex.clone()),
+            })
+            .collect()
+    }
+
+    fn latex(&self, node_ref: &NodeRef, level: i8) -> Result<String, AstError> {
+        Ok(node_ref.lock().unwrap().to_latex(level, &self.options)?)
+    }
+
+    /// Records a conflict and returns both versions enclosed in conflict markers.
+    fn conflict(&mut self, base: String, ours: String, theirs: String) -> Result<String, AstError> {
+        let latex = format!(
+            "{CONFLICT_BEGIN_MARK}\n{ours}{CONFLICT_SEPARATOR_MARK}\n{theirs}{CONFLICT_END_MARK}\n"
+        );
+        self.conflicts.push(MergeConflict { base, ours, theirs });
+        Ok(latex)
+    }
+}
+
+/// Whether two nodes have the same content and metadata (ignoring their children).
+fn same_content(first_ref: &NodeRef, second_ref: &NodeRef) -> bool {
+    let first: &Node = &first_ref.lock().unwrap();
+    let second: &Node = &second_ref.lock().unwrap();
+    signature(first) == signature(second) && first.meta_data.data == second.meta_data.data
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::merge::{merge_latex, CONFLICT_BEGIN_MARK};
+
+    const BASE: &str = "\\begin{document}\n\\section{A}\nText a\n\n\\section{B}\nText b\n\n\\section{C}\nText c\n\n\\end{document}\n";
+
+    #[test]
+    fn merge_changes_of_different_nodes() {
+        // ours edits A, deletes C and adds D, theirs edits B and swaps A and B
+        let ours = "\\begin{document}\n\\section{A}\nText a changed\n\n\\section{B}\nText b\n\n\\section{D}\nText d\n\n\\end{document}\n";
+        let theirs = "\\begin{document}\n\\section{B}\nText b changed\n\n\\section{A}\nText a\n\n\\section{C}\nText c\n\n\\end{document}\n";
+
+        let result = merge_latex(BASE, ours, theirs).unwrap();
+        assert!(result.conflicts.is_empty(), "{:?}", result.conflicts);
+        assert_eq!(
+            result.latex,
+            "\\begin{document}\n\\section{B}\nText b changed\n\n\\section{A}\nText a changed\n\n\\section{D}\nText d\n\n\\end{document}\n"
+        );
+    }
+
+    #[test]
+    fn conflicting_changes_of_same_node() {
+        let ours = BASE.replace("Text b", "Text b by us");
+        let theirs = BASE.replace("Text b", "Text b by them");
+
+        let result = merge_latex(BASE, &ours, &theirs).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].base, "Text b\n\n");
+        assert_eq!(result.conflicts[0].ours, "Text b by us\n\n");
+        assert_eq!(result.conflicts[0].theirs, "Text b by them\n\n");
+        assert!(result.latex.contains(CONFLICT_BEGIN_MARK));
+        assert!(result.latex.contains("Text a\n\n"));
+    }
+
+    #[test]
+    fn deleted_and_changed_node_conflicts() {
+        let ours = BASE.replace("\\section{C}\nText c\n\n", "");
+        let theirs = BASE.replace("Text c", "Text c changed");
+
+        let result = merge_latex(BASE, &ours, &theirs).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert!(result.conflicts[0].ours.is_empty());
+    }
+
+    #[test]
+    fn merge_included_file() {
+        let base = "\\subsection{Included}\nFirst\n\nSecond\n\n";
+        let ours = "\\subsection{Included}\nFirst by us\n\nSecond\n\n";
+        let theirs = "\\subsection{Included}\nFirst\n\nSecond by them\n\n";
+
+        let result = merge_latex(base, ours, theirs).unwrap();
+        assert!(result.conflicts.is_empty());
+        assert_eq!(
+            result.latex,
+            "\\subsection{Included}\nFirst by us\n\nSecond by them\n\n"
+        );
+    }
+}
@@ -2,10 +2,13 @@ use std::collections::HashMap;
 use std::string::String;
 use std::sync::{Arc, Mutex, Weak};
 
-use serde::Serialize;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 
+use crate::citation::parse_citations;
 use crate::errors::StringificationError;
 use crate::latex_constants::*;
+use crate::merge::{CONFLICT_BEGIN_MARK, CONFLICT_END_MARK, CONFLICT_SEPARATOR_MARK};
 use crate::meta_data::MetaData;
 use crate::options::StringificationOptions;
 use crate::texla_constants::*;
@@ -31,6 +34,9 @@ impl Node {
         level: i8,
         options: &StringificationOptions,
     ) -> Result<String, StringificationError> {
+        if !options.node_filter.includes(self) {
+            return Ok(String::new());
+        }
         if options.include_metadata && !self.meta_data.data.is_empty() {
             Ok(format!(
                 "{}{}\n{}",
@@ -43,6 +49,23 @@ impl Node {
         }
     }
 
+    /// Like `to_latex()`, but uses the given LaTeX code instead of the code of the children.
+    pub(crate) fn to_latex_with_children(
+        &self,
+        level: i8,
+        options: &StringificationOptions,
+        children_latex: String,
+    ) -> Result<String, StringificationError> {
+        let latex = self
+            .node_type
+            .to_latex_with_children(level, options, children_latex)?;
+        if options.include_metadata && !self.meta_data.data.is_empty() {
+            Ok(format!("{}{}\n{}", METADATA_MARK, self.meta_data, latex))
+        } else {
+            Ok(latex)
+        }
+    }
+
     pub(crate) fn new_leaf(
         data: LeafData,
         uuid_provider: &mut impl UuidProvider,
@@ -91,6 +114,21 @@ impl Node {
     }
 }
 
+/// Calls `f` on the node behind `node_ref` and on all nodes in its subtree in document order.
+pub(crate) fn visit_subtree(node_ref: &NodeRef, f: &mut impl FnMut(&mut Node)) {
+    let children = {
+        let mut node = node_ref.lock().unwrap();
+        f(&mut node);
+        match &node.node_type {
+            NodeType::Expandable { children, .. } => children.clone(),
+            NodeType::Leaf { .. } => vec![],
+        }
+    };
+    for child_ref in &children {
+        visit_subtree(child_ref, f);
+    }
+}
+
 #[derive(Debug, Serialize)]
 #[serde(tag = "type")]
 pub(crate) enum NodeType {
@@ -126,7 +164,7 @@ impl NodeType {
         match self {
             NodeType::Leaf { data } => Ok(data.to_latex(options)),
             NodeType::Expandable { data, .. } => {
-                let children_level = level + data.increases_level() as i8;
+                let children_level = data.children_level(level);
                 data.to_latex(
                     level,
                     options,
@@ -136,15 +174,65 @@ impl NodeType {
         }
     }
 
+    /// Returns the LaTeX code of this node in which the code of all children is replaced by
+    /// [SKIPPED_CONTENT_MARK], which is the format `EditNode` expects.
+    pub(crate) fn to_latex_without_children(
+        &self,
+        level: i8,
+        options: &StringificationOptions,
+    ) -> Result<String, StringificationError> {
+        self.to_latex_with_children(level, options, SKIPPED_CONTENT_MARK.to_string())
+    }
+
+    pub(crate) fn to_latex_with_children(
+        &self,
+        level: i8,
+        options: &StringificationOptions,
+        children_latex: String,
+    ) -> Result<String, StringificationError> {
+        match self {
+            NodeType::Leaf { data } => Ok(data.to_latex(options)),
+            NodeType::Expandable { data, .. } => data.to_latex(level, options, children_latex),
+        }
+    }
+
     pub(crate) fn increases_level(&self) -> bool {
         match self {
             NodeType::Expandable { data, .. } => data.increases_level(),
             NodeType::Leaf { .. } => false,
         }
     }
+
+    /// Returns the level of the children of this node, if this node has the given `level`.
+    pub(crate) fn children_level(&self, level: i8) -> i8 {
+        match self {
+            NodeType::Expandable { data, .. } => data.children_level(level),
+            NodeType::Leaf { .. } => level,
+        }
+    }
+
+    /// Returns the LaTeX code of this node that may contain commands like `\ref` or `\label`.
+    pub(crate) fn latex_content_mut(&mut self) -> Option<&mut String> {
+        match self {
+            NodeType::Expandable {
+                data: ExpandableData::Segment { heading, .. },
+                ..
+            } => Some(heading),
+            NodeType::Leaf {
+                data: LeafData::Text { text },
+            } => Some(text),
+            NodeType::Leaf {
+                data: LeafData::Caption { caption },
+            } => Some(caption),
+            NodeType::Leaf {
+                data: LeafData::Math { content, .. },
+            } => Some(content),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 #[serde(tag = "type")]
 pub(crate) enum ExpandableData {
     Document {
@@ -166,6 +254,14 @@ pub(crate) enum ExpandableData {
         after_children: String,
         increases_level: bool,
     },
+    /// Conflicting changes from a merge. The children are the two `ConflictSide`s.
+    Conflict,
+    /// The version of the conflicting part from one side of a merge.
+    /// Its content is parsed independently, so it has its own `level`.
+    ConflictSide {
+        ours: bool,
+        level: i8,
+    },
 }
 
 impl ExpandableData {
@@ -216,9 +312,23 @@ impl ExpandableData {
                 let children = children_latex;
                 format!("{before_children}\n{children}{after_children}\n")
             }
+            ExpandableData::Conflict => children_latex,
+            ExpandableData::ConflictSide { ours: true, .. } => {
+                format!("{CONFLICT_BEGIN_MARK}\n{children_latex}")
+            }
+            ExpandableData::ConflictSide { ours: false, .. } => {
+                format!("{CONFLICT_SEPARATOR_MARK}\n{children_latex}{CONFLICT_END_MARK}\n")
+            }
         })
     }
 
+    fn children_level(&self, level: i8) -> i8 {
+        match self {
+            ExpandableData::ConflictSide { level, .. } => *level,
+            _ => level + self.increases_level() as i8,
+        }
+    }
+
     fn increases_level(&self) -> bool {
         match self {
             ExpandableData::Segment { .. } => true,
@@ -230,9 +340,11 @@ impl ExpandableData {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 #[serde(tag = "type")]
 pub(crate) enum LeafData {
+    /// Serialized together with the citation commands in the text (see [serialize_text])
+    #[serde(serialize_with = "serialize_text")]
     Text {
         text: String,
     },
@@ -293,6 +405,15 @@ impl LeafData {
     }
 }
 
+/// Adds the citation commands to a Text node. They are found while serializing, because the text
+/// is changed by many operations and a stored list would become outdated.
+fn serialize_text<S: Serializer>(text: &String, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("Text", 2)?;
+    state.serialize_field("text", text)?;
+    state.serialize_field("citations", &parse_citations(text))?;
+    state.end()
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub(crate) enum MathKind {
     DoubleDollars,
@@ -306,9 +427,17 @@ pub(crate) enum MathKind {
 mod tests {
     use std::collections::HashMap;
 
-    use crate::node::{LeafData, Node};
+    use serde_json::json;
+
+    use crate::node::{LeafData, Node, NodeType};
+    use crate::operation::edit_node::EditNode;
+    use crate::operation::test::find_uuid_by_content;
+    use crate::operation::Operation;
     use crate::options::StringificationOptions;
+    use crate::parser::parse_latex;
+    use crate::texla_ast::TexlaAst;
     use crate::uuid_provider::TexlaUuidProvider;
+    use crate::Ast;
 
     #[test]
     fn print_text() {
@@ -330,4 +459,68 @@ mod tests {
             Ok("Test\n\n".to_string())
         );
     }
+
+    #[test]
+    fn serialize_citations_of_current_text() {
+        let mut data = LeafData::Text {
+            text: "See \\cite{a}.".to_string(),
+        };
+        if let LeafData::Text { text } = &mut data {
+            text.insert_str(0, "Now ");
+        }
+
+        let json = serde_json::to_value(&data).unwrap();
+        assert_eq!(json["type"], "Text");
+        assert_eq!(json["citations"][0]["keys"][0], "a");
+        assert_eq!(json["citations"][0]["range"]["start"], 8);
+    }
+
+    // citations are edited as part of the text of their node, so the serialized ranges only have
+    // to match the serialized text of the same version of the Ast
+    #[test]
+    fn serialize_citations_with_edited_text() {
+        let latex = "\\begin{document}\nSee \\citep[p.~5]{knuth84}.\n\\end{document}\n";
+        let mut ast = parse_latex(latex.to_string()).expect("Valid Latex");
+        let serialize = |ast: &TexlaAst, content: &str| {
+            let uuid = find_uuid_by_content(ast, content).unwrap();
+            match &ast.get_node(uuid).lock().unwrap().node_type {
+                NodeType::Leaf { data } => serde_json::to_value(data).unwrap(),
+                NodeType::Expandable { .. } => panic!("the text should be a leaf"),
+            }
+        };
+        let uuid = find_uuid_by_content(&ast, "See").unwrap();
+
+        assert_eq!(
+            serialize(&ast, "See"),
+            json!({
+                "type": "Text",
+                "text": "See \\citep[p.~5]{knuth84}.",
+                "citations": [{
+                    "command": "\\citep",
+                    "keys": ["knuth84"],
+                    "prenote": null,
+                    "postnote": "p.~5",
+                    "range": { "start": 4, "end": 25 },
+                }],
+            })
+        );
+
+        let operation = EditNode {
+            target: uuid,
+            raw_latex: "As \\cite{a} shows, see \\citep[p.~5]{knuth84}.".to_string(),
+        };
+        operation.execute_on(&mut ast).unwrap();
+        // reparse
+        ast = parse_latex(ast.to_latex(Default::default()).unwrap()).expect("Valid Latex");
+        let json = serialize(&ast, "As");
+        assert_eq!(json["citations"][0]["keys"], json!(["a"]));
+        assert_eq!(
+            json["citations"][1]["range"],
+            json!({ "start": 23, "end": 44 })
+        );
+        assert_eq!(
+            &json["text"].as_str().unwrap()[23..44],
+            "\\citep[p.~5]{knuth84}"
+        );
+    }
 }
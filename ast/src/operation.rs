@@ -1,18 +1,24 @@
 use std::fmt::Debug;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::OperationError;
 use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::{Position, Uuid};
 use crate::Ast;
 
 pub mod add_node;
+pub mod copy_node;
 pub mod delete_metadata;
 pub mod delete_node;
 pub mod edit_metadata;
 pub mod edit_node;
+pub mod extract_to_file;
+pub mod inline_file;
 pub mod merge_nodes;
 pub mod move_node;
+pub mod rename_label;
+pub mod resolve_conflict;
 
 /// Structs that implement this Trait can modify an [Ast] in some way.
 /// This specifies the Operation Interface in the Strategy pattern.
@@ -22,12 +28,13 @@ where
 {
     // if struggling with lifetimes, let execute_on consume self
     /// Execute this Operation on some [Ast].
-    fn execute_on(&self, ast: &mut A) -> Result<(), OperationError>;
+    /// Returns the Uuid of a newly created Node if the client needs to know about it.
+    fn execute_on(&self, ast: &mut A) -> Result<Option<Uuid>, OperationError>;
 }
 
 /// Enum to represent the different Operations.
 /// This Representation is used since rust currently doesn't support serialization of trait objects directly.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum JsonOperation {
     EditNode {
@@ -51,6 +58,21 @@ pub enum JsonOperation {
     MergeNodes {
         arguments: merge_nodes::MergeNodes,
     },
+    CopyNode {
+        arguments: copy_node::CopyNode,
+    },
+    ExtractToFile {
+        arguments: extract_to_file::ExtractToFile,
+    },
+    InlineFile {
+        arguments: inline_file::InlineFile,
+    },
+    RenameLabel {
+        arguments: rename_label::RenameLabel,
+    },
+    ResolveConflict {
+        arguments: resolve_conflict::ResolveConflict,
+    },
 }
 
 // we do this, just because serde_traitobject requires nightly
@@ -80,12 +102,85 @@ impl JsonOperation {
             JsonOperation::MergeNodes {
                 arguments: operation,
             } => Box::new(operation),
+            JsonOperation::CopyNode {
+                arguments: operation,
+            } => Box::new(operation),
+            JsonOperation::ExtractToFile {
+                arguments: operation,
+            } => Box::new(operation),
+            JsonOperation::InlineFile {
+                arguments: operation,
+            } => Box::new(operation),
+            JsonOperation::RenameLabel {
+                arguments: operation,
+            } => Box::new(operation),
+            JsonOperation::ResolveConflict {
+                arguments: operation,
+            } => Box::new(operation),
         }
     }
+
+    /// Whether this Operation creates or dissolves files, which then have to be created or
+    /// deleted on disk. Copies of subtrees containing files create new files as well.
+    pub fn affects_files(&self) -> bool {
+        matches!(
+            self,
+            JsonOperation::ExtractToFile { .. }
+                | JsonOperation::InlineFile { .. }
+                | JsonOperation::CopyNode { .. }
+        )
+    }
+
+    /// Whether this Operation resolves a merge conflict, which may allow to finish the merge.
+    pub fn resolves_conflict(&self) -> bool {
+        matches!(self, JsonOperation::ResolveConflict { .. })
+    }
+
+    /// Replaces every Uuid this Operation refers to by `f(uuid)`.
+    /// This is used to apply an Operation to another Ast representing the same document.
+    pub fn map_uuids(&mut self, f: &mut impl FnMut(Uuid) -> Uuid) {
+        match self {
+            JsonOperation::EditNode { arguments } => arguments.target = f(arguments.target),
+            JsonOperation::MoveNode { arguments } => {
+                arguments.target = f(arguments.target);
+                map_position(&mut arguments.destination, f);
+            }
+            JsonOperation::AddNode { arguments } => map_position(&mut arguments.destination, f),
+            JsonOperation::DeleteNode { arguments } => arguments.target = f(arguments.target),
+            JsonOperation::EditMetadata { arguments } => arguments.target = f(arguments.target),
+            JsonOperation::DeleteMetadata { arguments } => arguments.target = f(arguments.target),
+            JsonOperation::MergeNodes { arguments } => {
+                arguments.second_node = f(arguments.second_node)
+            }
+            JsonOperation::CopyNode { arguments } => {
+                arguments.target = f(arguments.target);
+                map_position(&mut arguments.destination, f);
+            }
+            JsonOperation::ExtractToFile { arguments } => arguments.target = f(arguments.target),
+            JsonOperation::InlineFile { arguments } => arguments.target = f(arguments.target),
+            JsonOperation::RenameLabel { .. } => {}
+            JsonOperation::ResolveConflict { arguments } => arguments.target = f(arguments.target),
+        }
+    }
+
+    /// Returns all Uuids this Operation refers to.
+    pub fn uuids(&self) -> Vec<Uuid> {
+        let mut uuids = vec![];
+        self.clone().map_uuids(&mut |uuid| {
+            uuids.push(uuid);
+            uuid
+        });
+        uuids
+    }
+}
+
+fn map_position(position: &mut Position, f: &mut impl FnMut(Uuid) -> Uuid) {
+    position.parent = f(position.parent);
+    position.after_sibling = position.after_sibling.map(f);
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use super::*;
     use crate::node::{NodeRef, NodeType};
     use crate::uuid_provider::Uuid;
@@ -106,14 +201,31 @@ mod test {
         // if this test runs, the deserialization worked
     }
 
-    pub(in crate::operation) fn find_uuid_by_content(
-        ast: &TexlaAst,
-        content: &str,
-    ) -> Option<Uuid> {
+    #[test]
+    fn map_uuids_and_to_json() {
+        let json = r#"
+        {
+            "type": "MoveNode",
+            "arguments": {
+                "target": 1,
+                "destination": { "parent": 2, "after_sibling": 3 }
+            }
+        }
+        "#;
+        let mut operation: super::JsonOperation = serde_json::from_str(json).unwrap();
+        assert_eq!(operation.uuids(), vec![1, 2, 3]);
+
+        operation.map_uuids(&mut |uuid| uuid * 10);
+        let json = serde_json::to_string(&operation).unwrap();
+        let operation: super::JsonOperation = serde_json::from_str(&json).unwrap();
+        assert_eq!(operation.uuids(), vec![10, 20, 30]);
+    }
+
+    pub(crate) fn find_uuid_by_content(ast: &TexlaAst, content: &str) -> Option<Uuid> {
         find_uuid_by_content_recursive(&ast.root, content)
     }
 
-    pub(in crate::operation) fn find_uuid_by_content_recursive(
+    pub(crate) fn find_uuid_by_content_recursive(
         node_ref: &NodeRef,
         content: &str,
     ) -> Option<Uuid> {
@@ -142,16 +254,13 @@ mod test {
         None
     }
 
-    pub(in crate::operation) fn get_node_and_count_children(
-        ast: &TexlaAst,
-        content: &str,
-    ) -> usize {
+    pub(crate) fn get_node_and_count_children(ast: &TexlaAst, content: &str) -> usize {
         let node_uuid = find_uuid_by_content(ast, content).expect("Failed to find");
         let node_ref = ast.get_node(node_uuid);
         count_children_of_node(&node_ref)
     }
 
-    pub(in crate::operation) fn count_children_of_node(node_ref: &NodeRef) -> usize {
+    pub(crate) fn count_children_of_node(node_ref: &NodeRef) -> usize {
         match &node_ref.lock().unwrap().node_type {
             NodeType::Expandable { children, .. } => children.len(),
             _ => 0, // Return 0 for non-Expandable nodes
@@ -1,24 +1,26 @@
 use std::sync::{Arc, Mutex};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::OperationError;
 use crate::meta_data::MetaData;
 use crate::node::{ExpandableData, Node, NodeType};
 use crate::operation::Operation;
 use crate::texla_ast::TexlaAst;
-use crate::uuid_provider::{Position, UuidProvider};
+use crate::uuid_provider::{Position, Uuid, UuidProvider};
 
 /// Tries to add a node represented by `raw_latex` into the [Ast] at the given [Position].
 /// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AddNode {
     pub destination: Position,
     pub raw_latex: String,
 }
 
 impl Operation<TexlaAst> for AddNode {
-    fn execute_on(&self, ast: &mut TexlaAst) -> Result<(), OperationError> {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        ast.validate_position(self.destination)?;
+
         // create new node
         let uuid = ast.uuid_provider.new_uuid();
 
@@ -40,7 +42,7 @@ impl Operation<TexlaAst> for AddNode {
         // insert into ast
         ast.insert_node_at_position(new_node_ref, self.destination);
 
-        Ok(())
+        Ok(None)
     }
 }
 
@@ -112,4 +114,35 @@ mod tests {
         assert!(!original_latex_single_string.contains(subsubsection_to_be_added_raw_latex));
         assert!(new_latex_single_string.contains(subsubsection_to_be_added_raw_latex));
     }
+
+    #[test]
+    fn test_add_node_to_leaf_fails() {
+        let leaf_raw_latex = "another Block of text\naaaaa";
+
+        let original_latex_single_string = lf(fs::read_to_string(
+            "../test_resources/latex/simple_for_operation_testing.tex",
+        )
+        .unwrap());
+        let mut ast = parse_latex(original_latex_single_string).expect("Valid Latex");
+        let latex_before = ast.to_latex(Default::default()).unwrap();
+
+        let leaf_uuid = find_uuid_by_content(&ast, leaf_raw_latex).expect("Failed to find");
+        let operation = AddNode {
+            destination: Position {
+                parent: leaf_uuid,
+                after_sibling: None,
+            },
+            raw_latex: "\\subsubsection{Subsubtitle}".to_string(),
+        };
+
+        assert_eq!(
+            operation.execute_on(&mut ast),
+            Err(OperationError::InvalidParent { uuid: leaf_uuid })
+        );
+        assert_eq!(
+            ast.to_latex(Default::default()).unwrap(),
+            latex_before,
+            "The ast should not be modified by a failed operation"
+        );
+    }
 }
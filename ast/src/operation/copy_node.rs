@@ -0,0 +1,257 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::OperationError;
+use crate::node::{visit_subtree, ExpandableData, NodeType};
+use crate::operation::Operation;
+use crate::reference_index::{rename_label_in_node, ReferenceIndex};
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::{Position, Uuid};
+
+const COPY_SUFFIX: &str = "-copy";
+
+/// Copy an existing Node including its whole subtree to a new [Position].
+/// The Node is specified by its `target` Uuid. Every Node of the copy receives a fresh Uuid.
+/// Files in the copy are renamed, since every file can only be part of the document once.
+/// If `rewrite_labels` is set, all labels in the copy (and references to them within the copy) are renamed so that they do not collide with existing ones.
+/// The Uuid of the copy is returned.
+/// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CopyNode {
+    pub target: Uuid,
+    pub destination: Position,
+    #[serde(default)]
+    pub rewrite_labels: bool,
+}
+
+impl Operation<TexlaAst> for CopyNode {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        ast.validate_node(self.target)?;
+        ast.validate_position(self.destination)?;
+
+        let index = self.rewrite_labels.then(|| ReferenceIndex::new(ast));
+        let mut file_paths = ast.file_paths();
+
+        let node_ref = ast.get_node(self.target);
+        let parent_ref = ast.get_node(self.destination.parent);
+        let copy_ref = ast.copy_subtree(&node_ref, Some(Arc::downgrade(&parent_ref)));
+
+        // a file can only be included once, so the copied files are saved as new files
+        visit_subtree(&copy_ref, &mut |node| {
+            if let NodeType::Expandable {
+                data: ExpandableData::File { path },
+                ..
+            } = &mut node.node_type
+            {
+                *path = unique_name(path, &mut file_paths);
+            }
+        });
+
+        if let Some(index) = index {
+            let copied_labels = index.labels_in_subtree(&node_ref);
+            let mut labels: HashSet<String> = index.labels.into_keys().collect();
+            let copied_labels = copied_labels
+                .into_iter()
+                .map(|label| {
+                    let new_label = unique_name(&label, &mut labels);
+                    (label, new_label)
+                })
+                .collect::<Vec<_>>();
+            visit_subtree(&copy_ref, &mut |node| {
+                for (old, new) in &copied_labels {
+                    rename_label_in_node(node, old, new);
+                }
+            });
+        }
+
+        let uuid = copy_ref.lock().unwrap().uuid;
+        ast.insert_node_at_position(copy_ref, self.destination);
+
+        Ok(Some(uuid))
+    }
+}
+
+/// Finds a name (of a label or file) based on `name` which is not contained in `names` yet and
+/// reserves it.
+fn unique_name(name: &str, names: &mut HashSet<String>) -> String {
+    let mut candidate = format!("{name}{COPY_SUFFIX}");
+    let mut counter = 2;
+    while names.contains(&candidate) {
+        candidate = format!("{name}{COPY_SUFFIX}{counter}");
+        counter += 1;
+    }
+    names.insert(candidate.clone());
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::operation::test::count_children_of_node;
+    use crate::operation::test::find_uuid_by_content;
+    use crate::operation::test::get_node_and_count_children;
+    use crate::parser::parse_latex;
+    use crate::Ast;
+
+    use super::*;
+
+    fn lf(s: String) -> String {
+        s.replace("\r\n", "\n")
+    }
+
+    #[test]
+    fn test_copy_node() {
+        let subsection_to_be_copied_raw_latex = "\\subsection{Subtitle}";
+        let section_containing_subsection_raw_latex = "\\section{Title1}";
+
+        let original_latex_single_string = lf(fs::read_to_string(
+            "../test_resources/latex/simple_for_operation_testing.tex",
+        )
+        .unwrap());
+        let mut ast = parse_latex(original_latex_single_string.clone()).expect("Valid Latex");
+
+        let target_uuid =
+            find_uuid_by_content(&ast, subsection_to_be_copied_raw_latex).expect("Failed to find");
+        let parent_uuid = find_uuid_by_content(&ast, section_containing_subsection_raw_latex)
+            .expect("Failed to find");
+
+        let title1_children_count_before =
+            get_node_and_count_children(&ast, section_containing_subsection_raw_latex);
+        let subtitle_children_count_before =
+            get_node_and_count_children(&ast, subsection_to_be_copied_raw_latex);
+
+        let operation = Box::new(CopyNode {
+            target: target_uuid,
+            destination: Position {
+                parent: parent_uuid,
+                after_sibling: Some(target_uuid),
+            },
+            rewrite_labels: false,
+        });
+
+        let copy_uuid = ast
+            .execute(operation)
+            .expect("should succeed")
+            .expect("should return the uuid of the copy");
+        assert_ne!(target_uuid, copy_uuid, "The copy should have a fresh uuid");
+        let copy_path = ast.node_path(copy_uuid).expect("copy should be in the ast");
+
+        // reparse
+        let new_latex_single_string = ast.to_latex(Default::default()).unwrap();
+        ast = parse_latex(new_latex_single_string.clone()).expect("Valid Latex");
+
+        let title1_children_count_after =
+            get_node_and_count_children(&ast, section_containing_subsection_raw_latex);
+        let copy_children_count_after = {
+            let copy_uuid = ast
+                .node_at_path(&copy_path)
+                .expect("copy should survive reparsing");
+            count_children_of_node(&ast.get_node(copy_uuid))
+        };
+
+        assert_eq!(
+            title1_children_count_before + 1,
+            title1_children_count_after,
+            "The parent node should have one more child after the operation"
+        );
+        assert_eq!(
+            subtitle_children_count_before, copy_children_count_after,
+            "The copy should have the same children as the original"
+        );
+        assert_eq!(
+            new_latex_single_string
+                .matches(subsection_to_be_copied_raw_latex)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_copy_node_rewrite_labels() {
+        let subsection_to_be_copied_raw_latex = "\\subsection{Math}";
+        let section_containing_subsection_raw_latex = "\\section{Features}";
+
+        let original_latex_single_string =
+            lf(fs::read_to_string("../test_resources/latex/lots_of_features.tex").unwrap());
+        let mut ast = parse_latex(original_latex_single_string).expect("Valid Latex");
+
+        let target_uuid =
+            find_uuid_by_content(&ast, subsection_to_be_copied_raw_latex).expect("Failed to find");
+        let parent_uuid = find_uuid_by_content(&ast, section_containing_subsection_raw_latex)
+            .expect("Failed to find");
+
+        for _ in 0..2 {
+            let operation = Box::new(CopyNode {
+                target: target_uuid,
+                destination: Position {
+                    parent: parent_uuid,
+                    after_sibling: Some(target_uuid),
+                },
+                rewrite_labels: true,
+            });
+            ast.execute(operation).expect("should succeed");
+        }
+
+        let new_latex_single_string = ast.to_latex(Default::default()).unwrap();
+        assert_eq!(new_latex_single_string.matches("\\label{math}").count(), 1);
+        assert_eq!(
+            new_latex_single_string
+                .matches("\\label{math-copy}")
+                .count(),
+            1
+        );
+        assert_eq!(
+            new_latex_single_string
+                .matches("\\label{math-copy2}")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_copy_node_with_files() {
+        let latex_single_string =
+            lf(fs::read_to_string("../test_resources/latex/latex_single_string.txt").unwrap());
+        let mut ast = parse_latex(latex_single_string).expect("Valid Latex");
+
+        let parent_uuid = |uuid| {
+            ast.get_node(uuid)
+                .lock()
+                .unwrap()
+                .parent
+                .as_ref()
+                .and_then(|parent| parent.upgrade())
+                .map(|parent| parent.lock().unwrap().uuid)
+                .unwrap()
+        };
+        let subsection_uuid =
+            find_uuid_by_content(&ast, "\\subsection{First subsection of second section}")
+                .expect("Failed to find");
+        let target_uuid = parent_uuid(subsection_uuid);
+        let parent_uuid = parent_uuid(target_uuid);
+        let file_count_before = ast.file_paths().len();
+
+        for _ in 0..2 {
+            let operation = Box::new(CopyNode {
+                target: target_uuid,
+                destination: Position {
+                    parent: parent_uuid,
+                    after_sibling: Some(target_uuid),
+                },
+                rewrite_labels: false,
+            });
+            ast.execute(operation).expect("should succeed");
+        }
+
+        let new_latex_single_string = ast.to_latex(Default::default()).unwrap();
+        let file_paths = parse_latex(new_latex_single_string)
+            .expect("Valid Latex")
+            .file_paths();
+        assert_eq!(file_paths.len(), file_count_before + 2);
+        assert!(file_paths.contains("sections/section2/subsection1-copy"));
+        assert!(file_paths.contains("sections/section2/subsection1-copy2"));
+    }
+}
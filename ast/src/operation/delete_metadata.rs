@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::OperationError;
 use crate::operation::Operation;
@@ -8,19 +8,20 @@ use crate::uuid_provider::Uuid;
 /// Tries to delete a key-value pair from the Metadata Hashmap of some Node.
 /// The Node is specified by its `target` Uuid, the key value pair is specified by its `key`.
 /// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DeleteMetadata {
     pub target: Uuid,
     pub key: String,
 }
 
 impl Operation<TexlaAst> for DeleteMetadata {
-    fn execute_on(&self, ast: &mut TexlaAst) -> Result<(), OperationError> {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        ast.validate_node(self.target)?;
         let node_ref = ast.get_node(self.target);
         let mut node = node_ref.lock().unwrap();
         node.meta_data.data.remove(&self.key);
 
-        Ok(())
+        Ok(None)
     }
 }
 
@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::OperationError;
 use crate::operation::Operation;
@@ -8,16 +8,17 @@ use crate::uuid_provider::Uuid;
 /// Delete some Node from the [Ast].
 /// The Node is specified by its `target` Uuid.
 /// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DeleteNode {
     pub target: Uuid,
 }
 
 impl Operation<TexlaAst> for DeleteNode {
-    fn execute_on(&self, ast: &mut TexlaAst) -> Result<(), OperationError> {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        ast.validate_removal(self.target)?;
         let node_ref = &ast.get_node(self.target);
         ast.remove_node(node_ref);
-        Ok(())
+        Ok(None)
     }
 }
 
@@ -87,4 +88,43 @@ mod tests {
             "Section Title should have one less child"
         );
     }
+
+    #[test]
+    fn test_delete_unknown_node_fails() {
+        let original_latex_single_string = lf(fs::read_to_string(
+            "../test_resources/latex/simple_for_operation_testing.tex",
+        )
+        .unwrap());
+        let mut ast = parse_latex(original_latex_single_string).expect("Valid Latex");
+        let latex_before = ast.to_latex(Default::default()).unwrap();
+
+        let operation = DeleteNode { target: u64::MAX };
+
+        assert_eq!(
+            operation.execute_on(&mut ast),
+            Err(OperationError::UnknownNode { uuid: u64::MAX })
+        );
+        assert_eq!(
+            ast.to_latex(Default::default()).unwrap(),
+            latex_before,
+            "The ast should not be modified by a failed operation"
+        );
+    }
+
+    #[test]
+    fn test_delete_root_fails() {
+        let original_latex_single_string = lf(fs::read_to_string(
+            "../test_resources/latex/simple_for_operation_testing.tex",
+        )
+        .unwrap());
+        let mut ast = parse_latex(original_latex_single_string).expect("Valid Latex");
+
+        let root_uuid = ast.root.lock().unwrap().uuid;
+        let operation = DeleteNode { target: root_uuid };
+
+        assert_eq!(
+            operation.execute_on(&mut ast),
+            Err(OperationError::RootDeletion)
+        );
+    }
 }
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::OperationError;
 use crate::operation::Operation;
@@ -11,19 +11,20 @@ use crate::uuid_provider::Uuid;
 /// The Node is specified by its `target` Uuid.
 /// This sets all the keys in `new` to their value in `new`. If these keys didn't exist before, the are created.
 /// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EditMetadata {
     pub target: Uuid,
     pub new: HashMap<String, String>,
 }
 
 impl Operation<TexlaAst> for EditMetadata {
-    fn execute_on(&self, ast: &mut TexlaAst) -> Result<(), OperationError> {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        ast.validate_node(self.target)?;
         let node_ref = ast.get_node(self.target);
         let mut node = node_ref.lock().unwrap();
         node.meta_data.edit(self.new.clone());
 
-        Ok(())
+        Ok(None)
     }
 }
 
@@ -1,6 +1,6 @@
 use std::sync::{Arc, Mutex};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::OperationError;
 use crate::meta_data::MetaData;
@@ -14,14 +14,15 @@ use crate::uuid_provider::Uuid;
 /// The Node is specified by its `target` Uuid.
 /// The new state of the Node is specified through the `raw_latex` LaTeX String.
 /// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EditNode {
     pub target: Uuid,
     pub raw_latex: String,
 }
 
 impl Operation<TexlaAst> for EditNode {
-    fn execute_on(&self, ast: &mut TexlaAst) -> Result<(), OperationError> {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        ast.validate_node(self.target)?;
         let node_ref = ast.get_node(self.target);
 
         // create new node from old node
@@ -57,6 +58,13 @@ impl Operation<TexlaAst> for EditNode {
             }))
         };
 
+        // the children now belong to the new node
+        if let NodeType::Expandable { children, .. } = &new_node_ref.lock().unwrap().node_type {
+            for child_ref in children {
+                child_ref.lock().unwrap().parent = Some(Arc::downgrade(&new_node_ref));
+            }
+        }
+
         if node_ref.lock().unwrap().parent.as_ref().is_some() {
             // update node in ast
             let position = ast.remove_node(&node_ref);
@@ -66,7 +74,7 @@ impl Operation<TexlaAst> for EditNode {
             ast.root = new_node_ref;
         }
 
-        Ok(())
+        Ok(None)
     }
 }
 
@@ -0,0 +1,173 @@
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::OperationError;
+use crate::latex_constants::LATEX_FILE_EXTENSION;
+use crate::meta_data::MetaData;
+use crate::node::{ExpandableData, Node, NodeType};
+use crate::operation::Operation;
+use crate::texla_ast::{is_inside_project, TexlaAst};
+use crate::uuid_provider::{Uuid, UuidProvider};
+
+/// Move an existing Node into a new file which is included at the Node's former position.
+/// The Node is specified by its `target` Uuid, the file by its LaTeX `path` (relative to the main file).
+/// The file itself is written when the Ast is saved.
+/// The Uuid of the new File Node is returned.
+/// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExtractToFile {
+    pub target: Uuid,
+    pub path: String,
+}
+
+impl Operation<TexlaAst> for ExtractToFile {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        ast.validate_removal(self.target)?;
+
+        // the file extension is optional in LaTeX and is added when saving anyway
+        let path = self
+            .path
+            .trim()
+            .trim_end_matches(&format!(".{LATEX_FILE_EXTENSION}"))
+            .to_string();
+        if path.is_empty() {
+            return Err(OperationError::InvalidArgument {
+                message: "the path of the new file must not be empty".to_string(),
+            });
+        }
+        // the file has to be inside the project, so that it is written to the repository
+        if !is_inside_project(&path) {
+            return Err(OperationError::InvalidArgument {
+                message: format!("the file '{path}' has to be inside the project directory"),
+            });
+        }
+        if ast.file_paths().contains(&path) {
+            return Err(OperationError::InvalidArgument {
+                message: format!("the file '{path}' is already part of the document"),
+            });
+        }
+
+        let node_ref = ast.get_node(self.target);
+        let position = ast.remove_node(&node_ref);
+
+        let uuid = ast.uuid_provider.new_uuid();
+        let file_node_ref = Arc::new(Mutex::new(Node {
+            uuid,
+            node_type: NodeType::Expandable {
+                data: ExpandableData::File { path },
+                children: vec![node_ref.clone()],
+            },
+            meta_data: MetaData::new(),
+            parent: Some(Arc::downgrade(&ast.get_node(position.parent))),
+            raw_latex: String::new(), // shouldn't matter since it gets re-parsed instantly
+        }));
+        node_ref.lock().unwrap().parent = Some(Arc::downgrade(&file_node_ref));
+        let node_uuid = node_ref.lock().unwrap().uuid;
+        ast.portal.insert(node_uuid, Arc::downgrade(&node_ref));
+
+        ast.insert_node_at_position(file_node_ref, position);
+
+        Ok(Some(uuid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::operation::test::find_uuid_by_content;
+    use crate::operation::test::get_node_and_count_children;
+    use crate::parser::parse_latex;
+    use crate::Ast;
+
+    use super::*;
+
+    fn lf(s: String) -> String {
+        s.replace("\r\n", "\n")
+    }
+
+    #[test]
+    fn test_extract_to_file() {
+        let subsection_to_be_extracted_raw_latex = "\\subsection{Subtitle}";
+        let section_containing_subsection_raw_latex = "\\section{Title1}";
+
+        let original_latex_single_string = lf(fs::read_to_string(
+            "../test_resources/latex/simple_for_operation_testing.tex",
+        )
+        .unwrap());
+        let mut ast = parse_latex(original_latex_single_string).expect("Valid Latex");
+
+        let target_uuid = find_uuid_by_content(&ast, subsection_to_be_extracted_raw_latex)
+            .expect("Failed to find");
+        let title1_children_count_before =
+            get_node_and_count_children(&ast, section_containing_subsection_raw_latex);
+
+        let operation = Box::new(ExtractToFile {
+            target: target_uuid,
+            path: "chapters/subtitle.tex".to_string(),
+        });
+
+        ast.execute(operation).expect("should succeed");
+        // reparse
+        let new_latex_single_string = ast.to_latex(Default::default()).unwrap();
+        ast = parse_latex(new_latex_single_string.clone()).expect("Valid Latex");
+
+        let title1_children_count_after =
+            get_node_and_count_children(&ast, section_containing_subsection_raw_latex);
+
+        assert_eq!(
+            title1_children_count_before, title1_children_count_after,
+            "The file should take the place of the extracted node"
+        );
+        assert!(new_latex_single_string
+            .contains("% TEXLA FILE BEGIN {chapters/subtitle}\n\\subsection{Subtitle}"));
+        assert!(ast.file_paths().contains("chapters/subtitle"));
+    }
+
+    #[test]
+    fn test_extract_to_existing_file_fails() {
+        let latex_single_string =
+            lf(fs::read_to_string("../test_resources/latex/latex_single_string.txt").unwrap());
+        let mut ast = parse_latex(latex_single_string).expect("Valid Latex");
+
+        let target_uuid =
+            find_uuid_by_content(&ast, "We can still write text here.").expect("Failed to find");
+
+        let operation = Box::new(ExtractToFile {
+            target: target_uuid,
+            path: "sections/section1".to_string(),
+        });
+
+        assert!(ast.execute(operation).is_err());
+    }
+
+    #[test]
+    fn test_extract_outside_of_project_fails() {
+        let latex_single_string = lf(fs::read_to_string(
+            "../test_resources/latex/simple_for_operation_testing.tex",
+        )
+        .unwrap());
+        let mut ast = parse_latex(latex_single_string).expect("Valid Latex");
+        let target_uuid = find_uuid_by_content(&ast, "\\subsection{Subtitle}").unwrap();
+
+        for path in [
+            "/etc/subtitle",
+            "C:\\subtitle",
+            "chapters/../../subtitle",
+            "..",
+        ] {
+            let operation = ExtractToFile {
+                target: target_uuid,
+                path: path.to_string(),
+            };
+            assert!(
+                matches!(
+                    operation.execute_on(&mut ast),
+                    Err(OperationError::InvalidArgument { .. })
+                ),
+                "{path}"
+            );
+        }
+    }
+}
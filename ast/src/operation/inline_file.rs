@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::OperationError;
+use crate::node::{ExpandableData, NodeType};
+use crate::operation::Operation;
+use crate::texla_ast::{is_inside_project, TexlaAst};
+use crate::uuid_provider::{Position, Uuid};
+
+/// Dissolve a File Node by putting its children at its position.
+/// The File Node is specified by its `target` Uuid.
+/// Deleting the file itself is up to the caller, since the Ast does not know about the file system.
+/// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InlineFile {
+    pub target: Uuid,
+}
+
+impl Operation<TexlaAst> for InlineFile {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        ast.validate_node(self.target)?;
+        let node_ref = ast.get_node(self.target);
+        let (path, children) = match &node_ref.lock().unwrap().node_type {
+            NodeType::Expandable {
+                data: ExpandableData::File { path },
+                children,
+            } => (path.clone(), children.clone()),
+            _ => {
+                return Err(OperationError::InvalidArgument {
+                    message: "only File nodes can be inlined".to_string(),
+                });
+            }
+        };
+        // the caller deletes the file, which must not be outside of the repository
+        if !is_inside_project(&path) {
+            return Err(OperationError::InvalidArgument {
+                message: format!("the file '{path}' has to be inside the project directory"),
+            });
+        }
+
+        let mut position = ast.remove_node(&node_ref);
+        let parent_ref = ast.get_node(position.parent);
+        for child_ref in children {
+            let child_uuid = {
+                let mut child = child_ref.lock().unwrap();
+                child.parent = Some(Arc::downgrade(&parent_ref));
+                child.uuid
+            };
+            ast.insert_node_at_position(child_ref, position);
+            position = Position {
+                parent: position.parent,
+                after_sibling: Some(child_uuid),
+            };
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::operation::test::find_uuid_by_content;
+    use crate::operation::test::get_node_and_count_children;
+    use crate::parser::parse_latex;
+    use crate::Ast;
+
+    use super::*;
+
+    fn lf(s: String) -> String {
+        s.replace("\r\n", "\n")
+    }
+
+    #[test]
+    fn test_inline_file() {
+        let file_to_be_inlined_raw_latex = "% TEXLA FILE BEGIN {sections/section2/no_segments}";
+        let section_containing_file_raw_latex = "\\section{Second section}";
+
+        let latex_single_string =
+            lf(fs::read_to_string("../test_resources/latex/latex_single_string.txt").unwrap());
+        let mut ast = parse_latex(latex_single_string).expect("Valid Latex");
+
+        let target_uuid =
+            find_uuid_by_content(&ast, file_to_be_inlined_raw_latex).expect("Failed to find");
+        let file_children_count = get_node_and_count_children(&ast, file_to_be_inlined_raw_latex);
+        let section_children_count_before =
+            get_node_and_count_children(&ast, section_containing_file_raw_latex);
+
+        let operation = Box::new(InlineFile {
+            target: target_uuid,
+        });
+
+        ast.execute(operation).expect("should succeed");
+        // reparse
+        let new_latex_single_string = ast.to_latex(Default::default()).unwrap();
+        ast = parse_latex(new_latex_single_string.clone()).expect("Valid Latex");
+
+        let section_children_count_after =
+            get_node_and_count_children(&ast, section_containing_file_raw_latex);
+
+        assert_eq!(
+            section_children_count_before - 1 + file_children_count,
+            section_children_count_after,
+            "The children of the file should be moved into its parent"
+        );
+        assert!(!new_latex_single_string.contains("sections/section2/no_segments"));
+        assert!(new_latex_single_string
+            .contains("This file only contains leaf elements and especially no segments."));
+        assert!(!ast.file_paths().contains("sections/section2/no_segments"));
+    }
+
+    #[test]
+    fn test_inline_non_file_fails() {
+        let latex_single_string =
+            lf(fs::read_to_string("../test_resources/latex/latex_single_string.txt").unwrap());
+        let mut ast = parse_latex(latex_single_string).expect("Valid Latex");
+
+        let target_uuid =
+            find_uuid_by_content(&ast, "\\section{Second section}").expect("Failed to find");
+
+        let operation = Box::new(InlineFile {
+            target: target_uuid,
+        });
+
+        assert!(ast.execute(operation).is_err());
+    }
+
+    #[test]
+    fn test_inline_file_outside_of_project_fails() {
+        let latex_single_string =
+            lf(fs::read_to_string("../test_resources/latex/latex_single_string.txt").unwrap())
+                .replace("sections/section2/no_segments", "../no_segments");
+        let mut ast = parse_latex(latex_single_string).expect("Valid Latex");
+
+        let target_uuid = find_uuid_by_content(&ast, "% TEXLA FILE BEGIN {../no_segments}")
+            .expect("Failed to find");
+
+        let operation = InlineFile {
+            target: target_uuid,
+        };
+
+        assert!(matches!(
+            operation.execute_on(&mut ast),
+            Err(OperationError::InvalidArgument { .. })
+        ));
+        assert!(ast.file_paths().contains("../no_segments"));
+    }
+}
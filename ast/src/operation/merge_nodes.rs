@@ -1,7 +1,9 @@
-use serde::Deserialize;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
 
 use crate::errors::OperationError;
-use crate::node::{LeafData, NodeType};
+use crate::node::{LeafData, NodeRef, NodeType};
 use crate::operation::Operation;
 use crate::texla_ast::TexlaAst;
 use crate::uuid_provider::Uuid;
@@ -10,51 +12,64 @@ use crate::uuid_provider::Uuid;
 /// Only the second node is specified through its uuid.
 /// The first node precedes implicitly.
 /// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MergeNodes {
     pub second_node: Uuid,
 }
 
 impl Operation<TexlaAst> for MergeNodes {
-    fn execute_on(&self, ast: &mut TexlaAst) -> Result<(), OperationError> {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        ast.validate_node(self.second_node)?;
         let second_node_ref = ast.get_node(self.second_node);
-        let latex = {
-            let second_node = second_node_ref.lock().unwrap();
-            match &second_node.node_type {
-                NodeType::Leaf {
-                    data: LeafData::Text { text },
-                } => text.clone(),
-                _ => {
-                    return Err(OperationError {
-                        message: "only Text nodes can be merged".to_string(),
-                    });
-                }
-            }
+        let latex = match &second_node_ref.lock().unwrap().node_type {
+            NodeType::Leaf {
+                data: LeafData::Text { text },
+            } => text.clone(),
+            _ => return Err(not_mergeable()),
         };
 
-        let first_uuid = ast
-            .remove_node(&second_node_ref)
-            .after_sibling
-            .ok_or(OperationError {
+        // the predecessor is validated before anything is changed
+        let first_node_ref =
+            previous_sibling(&second_node_ref).ok_or(OperationError::InvalidArgument {
                 message: "no predecessor found to merge into".to_string(),
             })?;
-        let first_node_ref = ast.get_node(first_uuid);
-        let mut first_node = first_node_ref.lock().unwrap();
-
-        match &mut first_node.node_type {
+        if !matches!(
+            first_node_ref.lock().unwrap().node_type,
             NodeType::Leaf {
-                data: LeafData::Text { text },
-            } => {
-                text.push_str(&format!("\n{latex}"));
-            }
-            _ => {
-                return Err(OperationError {
-                    message: "only Text nodes can be merged".to_string(),
-                })
+                data: LeafData::Text { .. }
             }
+        ) {
+            return Err(not_mergeable());
         }
 
-        Ok(())
+        ast.remove_node(&second_node_ref);
+        if let NodeType::Leaf {
+            data: LeafData::Text { text },
+        } = &mut first_node_ref.lock().unwrap().node_type
+        {
+            text.push_str(&format!("\n{latex}"));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Returns the sibling in front of the node, if there is one.
+fn previous_sibling(node_ref: &NodeRef) -> Option<NodeRef> {
+    let parent_ref = node_ref.lock().unwrap().parent.as_ref()?.upgrade()?;
+    let parent = parent_ref.lock().unwrap();
+    let NodeType::Expandable { children, .. } = &parent.node_type else {
+        return None;
+    };
+    let index = children
+        .iter()
+        .position(|child_ref| Arc::ptr_eq(child_ref, node_ref))?;
+    index.checked_sub(1).map(|index| children[index].clone())
+}
+
+fn not_mergeable() -> OperationError {
+    OperationError::InvalidArgument {
+        message: "only Text nodes can be merged".to_string(),
     }
 }
 
@@ -122,4 +137,36 @@ mod tests {
             "The parent node should have one more child after the operation"
         );
     }
+
+    #[test]
+    fn merge_without_predecessor() {
+        let latex = lf(fs::read_to_string(
+            "../test_resources/latex/simple_for_operation_testing.tex",
+        )
+        .unwrap());
+        let mut ast = parse_latex(latex).expect("Valid Latex");
+        let latex = ast.to_latex(Default::default()).unwrap();
+        let first_child = find_uuid_by_content(&ast, "another Block of text").unwrap();
+
+        let result = ast.execute(Box::new(MergeNodes {
+            second_node: first_child,
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(ast.to_latex(Default::default()).unwrap(), latex);
+    }
+
+    #[test]
+    fn merge_into_non_text() {
+        let latex = "\\begin{document}\n\\begin{equation}\nx = 1\n\\end{equation}\nAfter\n\n\\end{document}\n"
+            .to_string();
+        let mut ast = parse_latex(latex.clone()).expect("Valid Latex");
+        let text = find_uuid_by_content(&ast, "After").unwrap();
+
+        let result = ast.execute(Box::new(MergeNodes { second_node: text }));
+
+        assert!(result.is_err());
+        assert_eq!(ast.to_latex(Default::default()).unwrap(), latex);
+        assert!(ast.get_node(text).lock().unwrap().parent.is_some());
+    }
 }
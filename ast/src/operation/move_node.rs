@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::OperationError;
 use crate::operation::Operation;
@@ -8,18 +8,19 @@ use crate::uuid_provider::{Position, Uuid};
 /// Move an existing Node to a new [Position].
 /// The Node is specified by its `target` Uuid.
 /// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MoveNode {
     pub target: Uuid,
     pub destination: Position,
 }
 
 impl Operation<TexlaAst> for MoveNode {
-    fn execute_on(&self, ast: &mut TexlaAst) -> Result<(), OperationError> {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        ast.validate_move(self.target, self.destination)?;
         let node_ref = ast.get_node(self.target);
         ast.remove_node(&node_ref);
         ast.insert_node_at_position(node_ref.clone(), self.destination);
-        Ok(())
+        Ok(None)
     }
 }
 
@@ -160,4 +161,43 @@ mod tests {
             "The subtitle node should have the same number of children after the operation"
         );
     }
+
+    // Move section title 1 into its own subsection
+    #[test]
+    fn test_move_into_descendant_fails() {
+        let section_to_be_moved = "\\section{Title1}";
+        let subsection_to_be_moved_to_content = "\\subsection{Subtitle}";
+
+        let original_latex_single_string = lf(fs::read_to_string(
+            "../test_resources/latex/simple_for_operation_testing.tex",
+        )
+        .unwrap());
+        let mut ast = parse_latex(original_latex_single_string).expect("Valid Latex");
+        let latex_before = ast.to_latex(Default::default()).unwrap();
+
+        let target_uuid = find_uuid_by_content(&ast, section_to_be_moved).expect("Failed to find");
+        let parent_uuid =
+            find_uuid_by_content(&ast, subsection_to_be_moved_to_content).expect("Failed to find");
+
+        let operation = MoveNode {
+            target: target_uuid,
+            destination: Position {
+                parent: parent_uuid,
+                after_sibling: None,
+            },
+        };
+
+        assert_eq!(
+            operation.execute_on(&mut ast),
+            Err(OperationError::Cycle {
+                target: target_uuid,
+                destination: parent_uuid,
+            })
+        );
+        assert_eq!(
+            ast.to_latex(Default::default()).unwrap(),
+            latex_before,
+            "The ast should not be modified by a failed operation"
+        );
+    }
 }
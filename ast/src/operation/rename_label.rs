@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::OperationError;
+use crate::node::visit_subtree;
+use crate::operation::Operation;
+use crate::reference_index::{rename_label_in_node, ReferenceIndex};
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::Uuid;
+
+/// Rename a label and update all references to it (`\ref`, `\eqref`, `\autoref`, `\cref`, ...).
+/// The label is specified by its `old` name. All files of the document are updated at once.
+/// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RenameLabel {
+    pub old: String,
+    pub new: String,
+}
+
+impl Operation<TexlaAst> for RenameLabel {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        let new = self.new.trim();
+        if new.is_empty() || new.contains([',', '{', '}']) {
+            return Err(OperationError::InvalidArgument {
+                message: format!("'{new}' is not a valid label"),
+            });
+        }
+
+        let index = ReferenceIndex::new(ast);
+        if !index.labels.contains_key(&self.old) {
+            return Err(OperationError::InvalidArgument {
+                message: format!("there is no label '{}'", self.old),
+            });
+        }
+        if index.labels.contains_key(new) {
+            return Err(OperationError::InvalidArgument {
+                message: format!("the label '{new}' already exists"),
+            });
+        }
+
+        visit_subtree(&ast.root, &mut |node| {
+            rename_label_in_node(node, &self.old, new);
+        });
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::parser::parse_latex;
+    use crate::Ast;
+
+    use super::*;
+
+    fn lf(s: String) -> String {
+        s.replace("\r\n", "\n")
+    }
+
+    #[test]
+    fn test_rename_label() {
+        let original_latex_single_string =
+            lf(fs::read_to_string("../test_resources/latex/references.tex").unwrap());
+        let mut ast = parse_latex(original_latex_single_string.clone()).expect("Valid Latex");
+
+        let operation = Box::new(RenameLabel {
+            old: "sec:intro".to_string(),
+            new: "sec:introduction".to_string(),
+        });
+
+        ast.execute(operation).expect("should succeed");
+        // reparse
+        let new_latex_single_string = ast.to_latex(Default::default()).unwrap();
+        ast = parse_latex(new_latex_single_string.clone()).expect("Valid Latex");
+
+        let index = ReferenceIndex::new(&ast);
+        assert!(!index.labels.contains_key("sec:intro"));
+        assert!(!index.references.contains_key("sec:intro"));
+        assert!(index.labels.contains_key("sec:introduction"));
+        assert_eq!(index.references["sec:introduction"].len(), 2);
+
+        assert!(new_latex_single_string.contains("\\label{sec:introduction}"));
+        assert!(new_latex_single_string.contains("\\autoref{sec:introduction}"));
+        assert!(new_latex_single_string.contains("\\cref{sec:introduction, sec:missing}"));
+    }
+
+    #[test]
+    fn test_rename_label_in_math() {
+        let original_latex_single_string =
+            lf(fs::read_to_string("../test_resources/latex/references.tex").unwrap());
+        let mut ast = parse_latex(original_latex_single_string).expect("Valid Latex");
+
+        let operation = Box::new(RenameLabel {
+            old: "eq:energy".to_string(),
+            new: "eq:einstein".to_string(),
+        });
+
+        ast.execute(operation).expect("should succeed");
+        let new_latex_single_string = ast.to_latex(Default::default()).unwrap();
+
+        assert!(new_latex_single_string.contains("\\label{eq:einstein}"));
+        assert!(new_latex_single_string.contains("\\eqref{eq:einstein}"));
+    }
+
+    #[test]
+    fn test_rename_to_existing_label_fails() {
+        let original_latex_single_string =
+            lf(fs::read_to_string("../test_resources/latex/references.tex").unwrap());
+        let mut ast = parse_latex(original_latex_single_string).expect("Valid Latex");
+
+        let operation = Box::new(RenameLabel {
+            old: "sec:intro".to_string(),
+            new: "eq:energy".to_string(),
+        });
+
+        assert!(ast.execute(operation).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::OperationError;
+use crate::meta_data::MetaData;
+use crate::node::{ExpandableData, Node, NodeType};
+use crate::operation::Operation;
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::{Uuid, UuidProvider};
+
+/// Resolve a merge conflict by replacing the Conflict Node with one of its sides or with manually
+/// merged LaTeX code.
+/// The Conflict Node is specified by its `target` Uuid.
+/// This Struct is a Strategy. It can be created explicitly and should be used on an Ast via the `execute_on()` method.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResolveConflict {
+    pub target: Uuid,
+    pub resolution: Resolution,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    Manual { raw_latex: String },
+}
+
+impl Operation<TexlaAst> for ResolveConflict {
+    fn execute_on(&self, ast: &mut TexlaAst) -> Result<Option<Uuid>, OperationError> {
+        ast.validate_removal(self.target)?;
+        let node_ref = ast.get_node(self.target);
+        let sides = match &node_ref.lock().unwrap().node_type {
+            NodeType::Expandable {
+                data: ExpandableData::Conflict,
+                children,
+            } => children.clone(),
+            _ => {
+                return Err(OperationError::InvalidArgument {
+                    message: "only Conflict nodes can be resolved".to_string(),
+                });
+            }
+        };
+
+        let raw_latex = match &self.resolution {
+            Resolution::Manual { raw_latex } => raw_latex.clone(),
+            resolution => {
+                let chosen_side = sides
+                    .iter()
+                    .find(|side_ref| {
+                        matches!(
+                            side_ref.lock().unwrap().node_type,
+                            NodeType::Expandable {
+                                data: ExpandableData::ConflictSide { ours, .. },
+                                ..
+                            } if ours == (*resolution == Resolution::Ours)
+                        )
+                    })
+                    .expect("a conflict has both sides")
+                    .lock()
+                    .unwrap();
+                // the side is inserted as LaTeX code, because its level may differ from the
+                // level at the position of the conflict
+                let level = chosen_side.node_type.children_level(0);
+                chosen_side
+                    .node_type
+                    .children_to_latex(level, &Default::default())
+                    .map_err(|err| OperationError::InvalidArgument {
+                        message: err.to_string(),
+                    })?
+            }
+        };
+
+        let position = ast.remove_node(&node_ref);
+        if !raw_latex.trim().is_empty() {
+            let new_node_ref = Arc::new(Mutex::new(Node {
+                uuid: ast.uuid_provider.new_uuid(),
+                node_type: NodeType::Expandable {
+                    data: ExpandableData::Dummy {
+                        before_children: raw_latex.trim_end_matches('\n').to_string(),
+                        after_children: "".to_string(),
+                        increases_level: false,
+                    },
+                    children: vec![],
+                },
+                meta_data: MetaData::new(),
+                parent: None,             // set when inserting
+                raw_latex: String::new(), // shouldn't matter since it gets re-parsed instantly
+            }));
+            ast.insert_node_at_position(new_node_ref, position);
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_latex;
+    use crate::Ast;
+
+    use super::*;
+
+    const CONFLICTED: &str = "\\begin{document}\n\\section{A}\nText a\n\n<<<<<<< ours\n\\section{B}\nText b by us\n\n=======\n\\section{B}\nText b by them\n\n>>>>>>> theirs\n\\end{document}\n";
+
+    fn conflict_uuid(ast: &TexlaAst) -> Uuid {
+        let mut uuid = None;
+        crate::node::visit_subtree(&ast.root, &mut |node| {
+            if let NodeType::Expandable {
+                data: ExpandableData::Conflict,
+                ..
+            } = node.node_type
+            {
+                uuid = Some(node.uuid);
+            }
+        });
+        uuid.expect("the conflict should be parsed")
+    }
+
+    #[test]
+    fn parse_conflict() {
+        let ast = parse_latex(CONFLICTED.to_string()).expect("Valid Latex");
+        assert!(ast.has_conflicts());
+        assert_eq!(ast.to_latex(Default::default()).unwrap(), CONFLICTED);
+    }
+
+    #[test]
+    fn resolve_conflict() {
+        for (resolution, expected) in [
+            (Resolution::Ours, "Text b by us"),
+            (Resolution::Theirs, "Text b by them"),
+            (
+                Resolution::Manual {
+                    raw_latex: "\\section{B}\nText b by both\n\n".to_string(),
+                },
+                "Text b by both",
+            ),
+        ] {
+            let mut ast = parse_latex(CONFLICTED.to_string()).expect("Valid Latex");
+            let operation = Box::new(ResolveConflict {
+                target: conflict_uuid(&ast),
+                resolution,
+            });
+            ast.execute(operation).expect("should succeed");
+
+            let ast = parse_latex(ast.to_latex(Default::default()).unwrap()).expect("Valid Latex");
+            assert!(!ast.has_conflicts());
+            let latex = ast.to_latex(Default::default()).unwrap();
+            assert!(
+                latex.contains(&format!("\\section{{B}}\n{expected}")),
+                "{latex}"
+            );
+        }
+    }
+}
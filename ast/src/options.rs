@@ -1,4 +1,11 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::node::Node;
+use crate::uuid_provider::Uuid;
+
+/// The metadata key whose value lists the variants a node belongs to, separated by spaces (e.g.
+/// `variant:long appendix`).
+pub const VARIANT_METADATA_KEY: &str = "variant";
 
 /// StringificationOptions is used to specify how a given [super::Ast] should be converted to raw LaTeX Code.
 #[derive(Deserialize, Debug)]
@@ -7,6 +14,9 @@ pub struct StringificationOptions {
     pub include_comments: bool,
     /// Whether or not to include comments used by TeXLa internally to save metadata about Elements in the input.
     pub include_metadata: bool,
+    /// Which nodes (together with their subtrees) to include in the output.
+    #[serde(default)]
+    pub node_filter: NodeFilter,
 }
 
 impl Default for StringificationOptions {
@@ -14,6 +24,38 @@ impl Default for StringificationOptions {
         Self {
             include_comments: true,
             include_metadata: true,
+            node_filter: Default::default(),
         }
     }
 }
+
+/// NodeFilter selects nodes by the variants they are tagged with. Nodes without variants are
+/// included unless they are excluded explicitly.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct NodeFilter {
+    /// If not empty, tagged nodes are only included if they belong to one of these variants.
+    #[serde(default)]
+    pub include_variants: Vec<String>,
+    /// Nodes belonging to one of these variants are excluded.
+    #[serde(default)]
+    pub exclude_variants: Vec<String>,
+    /// Nodes which are excluded regardless of their variants.
+    #[serde(default)]
+    pub exclude_nodes: Vec<Uuid>,
+}
+
+impl NodeFilter {
+    pub(crate) fn includes(&self, node: &Node) -> bool {
+        if self.exclude_nodes.contains(&node.uuid) {
+            return false;
+        }
+        let Some(variants) = node.meta_data.data.get(VARIANT_METADATA_KEY) else {
+            return true;
+        };
+        let variants: Vec<&str> = variants.split_whitespace().collect();
+        let listed = |list: &[String]| list.iter().any(|v| variants.contains(&v.as_str()));
+
+        !listed(&self.exclude_variants)
+            && (self.include_variants.is_empty() || listed(&self.include_variants))
+    }
+}
@@ -0,0 +1,238 @@
+//! `outline` computes the table of contents of a document, which is much smaller than the whole
+//! Ast and can be used for navigation.
+use serde::Serialize;
+
+use crate::latex_constants::*;
+use crate::node::{ExpandableData, LeafData, NodeRef, NodeType};
+use crate::reference_index::find_keys;
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::Uuid;
+
+/// The deepest level which is numbered, like with the default `secnumdepth` of LaTeX
+const NUMBERED_LEVELS_END: i8 = 3;
+const PART_LEVEL: i8 = -1;
+const ROMAN_NUMERALS: [(u32, &str); 13] = [
+    (1000, "M"),
+    (900, "CM"),
+    (500, "D"),
+    (400, "CD"),
+    (100, "C"),
+    (90, "XC"),
+    (50, "L"),
+    (40, "XL"),
+    (10, "X"),
+    (9, "IX"),
+    (5, "V"),
+    (4, "IV"),
+    (1, "I"),
+];
+
+/// A segment of the document together with the segments beneath it.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct OutlineEntry {
+    pub uuid: Uuid,
+    /// The heading as LaTeX code
+    pub heading: String,
+    /// The level of the segment (see [SEGMENT_LEVELS]), e.g. `1` for a section
+    pub level: i8,
+    /// Whether the segment is numbered (not starred)
+    pub counted: bool,
+    /// The number LaTeX shows, e.g. `2.1` or `A.1` in the appendix. Parts get roman numerals,
+    /// starred segments and segments below subsubsections have none.
+    pub number: Option<String>,
+    /// The labels referring to this segment
+    pub labels: Vec<String>,
+    pub children: Vec<OutlineEntry>,
+}
+
+/// Computes the outline of the whole document.
+pub fn outline(ast: &TexlaAst) -> Vec<OutlineEntry> {
+    let mut numbering = SegmentNumbering {
+        // chapters are not numbered within parts
+        top_level: ast.highest_level.max(PART_LEVEL + 1),
+        ..Default::default()
+    };
+    let mut entries = vec![];
+    collect_entries(&ast.root, ast.highest_level, &mut numbering, &mut entries);
+    entries
+}
+
+#[derive(Debug, Default)]
+struct SegmentNumbering {
+    /// The level of the segments whose numbers have no prefix
+    top_level: i8,
+    parts: u32,
+    /// The current number on every level starting with `top_level`
+    segments: Vec<u32>,
+    /// Whether `\appendix` was found, after which the top level is numbered by letters
+    appendix: bool,
+}
+
+impl SegmentNumbering {
+    fn next(&mut self, level: i8) -> Option<String> {
+        if level == PART_LEVEL {
+            self.parts += 1;
+            return Some(roman(self.parts));
+        }
+        if level > NUMBERED_LEVELS_END || level < self.top_level {
+            return None;
+        }
+        let depth = (level - self.top_level) as usize;
+        self.segments.resize(depth + 1, 0);
+        self.segments[depth] += 1;
+        let numbers = self.segments.iter().enumerate().map(|(depth, number)| {
+            match depth == 0 && self.appendix {
+                true => letter(*number),
+                false => number.to_string(),
+            }
+        });
+        Some(numbers.collect::<Vec<String>>().join("."))
+    }
+
+    fn start_appendix(&mut self) {
+        self.appendix = true;
+        self.segments.clear();
+    }
+}
+
+fn collect_entries(
+    node_ref: &NodeRef,
+    level: i8,
+    numbering: &mut SegmentNumbering,
+    entries: &mut Vec<OutlineEntry>,
+) {
+    let node = node_ref.lock().unwrap();
+    let (data, children) = match &node.node_type {
+        NodeType::Leaf {
+            data: LeafData::Text { text },
+        } => {
+            if !find_commands(text, APPENDIX).is_empty() {
+                numbering.start_appendix();
+            }
+            return;
+        }
+        NodeType::Leaf { .. } => return,
+        NodeType::Expandable { data, children } => (data.clone(), children.clone()),
+    };
+    let uuid = node.uuid;
+    let children_level = node.node_type.children_level(level);
+    drop(node);
+
+    let ExpandableData::Segment { heading, counted } = data else {
+        for child_ref in &children {
+            collect_entries(child_ref, children_level, numbering, entries);
+        }
+        return;
+    };
+
+    let number = counted.then(|| numbering.next(level)).flatten();
+    let mut labels: Vec<String> = find_keys(&heading, &[LABEL])
+        .into_iter()
+        .map(|label| heading[label].to_string())
+        .collect();
+    // labels directly after the heading refer to the segment as well
+    for child_ref in &children {
+        match &child_ref.lock().unwrap().node_type {
+            NodeType::Leaf {
+                data: LeafData::Label { label },
+            } => labels.push(label.clone()),
+            NodeType::Leaf {
+                data: LeafData::Comment { .. },
+            } => {}
+            _ => break,
+        }
+    }
+    let mut entry = OutlineEntry {
+        uuid,
+        heading,
+        level,
+        counted,
+        number,
+        labels,
+        children: vec![],
+    };
+    for child_ref in &children {
+        collect_entries(child_ref, children_level, numbering, &mut entry.children);
+    }
+    entries.push(entry);
+}
+
+/// Returns the positions of `command` in `latex` (not followed by further letters).
+fn find_commands(latex: &str, command: &str) -> Vec<usize> {
+    latex
+        .match_indices(command)
+        .filter(|(start, _)| {
+            !latex[start + command.len()..].starts_with(|c: char| c.is_ascii_alphabetic())
+        })
+        .map(|(start, _)| start)
+        .collect()
+}
+
+fn roman(mut number: u32) -> String {
+    let mut roman = String::new();
+    for (value, numeral) in ROMAN_NUMERALS {
+        while number >= value {
+            roman += numeral;
+            number -= value;
+        }
+    }
+    roman
+}
+
+/// `A` to `Z` like LaTeX's `\Alph`, numbers beyond that are kept as they are.
+fn letter(number: u32) -> String {
+    match number {
+        1..=26 => char::from(b'A' + (number - 1) as u8).to_string(),
+        _ => number.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::outline::{outline, roman};
+    use crate::parser::parse_latex;
+
+    #[test]
+    fn number_segments() {
+        let latex = "\\begin{document}\n\\section{Intro}\n\\label{sec:intro}\nText\n\n\\subsection{Motivation \\label{sec:motivation}}\n\\subsection*{Aside}\n\\subsection{Goals}\n\\subsubsection{First}\n\\paragraph{Detail}\n\\section{Method}\n\\appendix\n\n\\section{Proofs}\n\\subsection{Lemma}\n\\section{Data}\n\\end{document}\n";
+        let ast = parse_latex(latex.to_string()).expect("Valid Latex");
+
+        let outline = outline(&ast);
+
+        let numbers: Vec<Option<&str>> = outline.iter().map(|e| e.number.as_deref()).collect();
+        assert_eq!(numbers, vec![Some("1"), Some("2"), Some("A"), Some("B")]);
+        let intro = &outline[0];
+        assert_eq!(intro.heading, "Intro");
+        assert_eq!(intro.level, 1);
+        assert_eq!(intro.labels, vec!["sec:intro"]);
+        let subsections: Vec<Option<&str>> =
+            intro.children.iter().map(|e| e.number.as_deref()).collect();
+        assert_eq!(subsections, vec![Some("1.1"), None, Some("1.2")]);
+        assert_eq!(intro.children[0].labels, vec!["sec:motivation"]);
+        assert!(!intro.children[1].counted);
+        let first = &intro.children[2].children[0];
+        assert_eq!(first.number.as_deref(), Some("1.2.1"));
+        // paragraphs are not numbered
+        assert_eq!(first.children[0].number, None);
+        assert_eq!(outline[2].children[0].number.as_deref(), Some("A.1"));
+    }
+
+    #[test]
+    fn number_parts_and_chapters() {
+        let latex = "\\begin{document}\n\\part{One}\n\\chapter{A}\n\\section{B}\n\\part{Two}\n\\chapter{C}\n\\end{document}\n";
+        let ast = parse_latex(latex.to_string()).expect("Valid Latex");
+
+        let outline = outline(&ast);
+
+        assert_eq!(outline[0].number.as_deref(), Some("I"));
+        assert_eq!(outline[0].children[0].number.as_deref(), Some("1"));
+        assert_eq!(
+            outline[0].children[0].children[0].number.as_deref(),
+            Some("1.1")
+        );
+        assert_eq!(outline[1].number.as_deref(), Some("II"));
+        // chapters are not reset by parts
+        assert_eq!(outline[1].children[0].number.as_deref(), Some("2"));
+        assert_eq!(roman(1994), "MCMXCIV");
+    }
+}
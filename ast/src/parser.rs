@@ -6,6 +6,7 @@ use chumsky::prelude::*;
 use chumsky::text::newline;
 use chumsky::Parser;
 
+use crate::conflict::{extract_conflicts, insert_conflicts};
 use crate::errors::ParseError;
 use crate::latex_constants::*;
 use crate::node::{ExpandableData, LeafData, MathKind, Node, NodeRef, NodeRefWeak};
@@ -27,6 +28,7 @@ struct LatexParser {
 pub(crate) fn parse_latex(string: String) -> Result<TexlaAst, ParseError> {
     // To further improve performance, the parser could be reused instead of creating it every time.
     // This could be realized by using reference arguments instead of attributes.
+    let (string, conflicts) = extract_conflicts(&string)?;
     let parser = LatexParser::new();
     let root = match parser.parser().parse(string.clone()) {
         Ok(root) => root,
@@ -47,12 +49,14 @@ pub(crate) fn parse_latex(string: String) -> Result<TexlaAst, ParseError> {
         }
     };
     let highest_level = parser.highest_level(&string);
-    Ok(TexlaAst {
+    let mut ast = TexlaAst {
         portal: parser.portal.into_inner(),
         uuid_provider: parser.uuid_provider.into_inner(),
         root,
         highest_level,
-    })
+    };
+    insert_conflicts(&mut ast, conflicts)?;
+    Ok(ast)
 }
 
 impl LatexParser {
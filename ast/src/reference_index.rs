@@ -0,0 +1,203 @@
+//! `reference_index` keeps track of labels and the references to them (`\ref`, `\eqref`, ...).
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::latex_constants::*;
+use crate::node::{visit_subtree, LeafData, Node, NodeRef, NodeType};
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::Uuid;
+
+/// Maps every label to the nodes defining it and every referenced label to the nodes referencing it.
+/// Both lists are in document order. The index spans all files of the document.
+#[derive(Debug, Default, Serialize)]
+pub struct ReferenceIndex {
+    pub labels: HashMap<String, Vec<Uuid>>,
+    pub references: HashMap<String, Vec<Uuid>>,
+}
+
+impl ReferenceIndex {
+    pub fn new(ast: &TexlaAst) -> Self {
+        let mut index = Self::default();
+        visit_subtree(&ast.root, &mut |node| index.add(node));
+        index
+    }
+
+    fn add(&mut self, node: &mut Node) {
+        let uuid = node.uuid;
+        if let NodeType::Leaf {
+            data: LeafData::Label { label },
+        } = &node.node_type
+        {
+            self.labels.entry(label.clone()).or_default().push(uuid);
+        }
+
+        if let Some(content) = node.node_type.latex_content_mut() {
+            for label in find_keys(content, &[LABEL]) {
+                let label = content[label].to_string();
+                self.labels.entry(label).or_default().push(uuid);
+            }
+            for reference in find_keys(content, &REFERENCE_COMMANDS) {
+                let reference = content[reference].to_string();
+                self.references.entry(reference).or_default().push(uuid);
+            }
+        }
+    }
+
+    /// Returns the node defining the given label. If there are multiple, the first one is used.
+    pub fn target(&self, label: &str) -> Option<Uuid> {
+        self.labels.get(label)?.first().copied()
+    }
+
+    /// Returns the labels defined in the subtree beneath `node_ref` (sorted alphabetically).
+    pub(crate) fn labels_in_subtree(&self, node_ref: &NodeRef) -> Vec<String> {
+        let mut uuids = HashSet::new();
+        visit_subtree(node_ref, &mut |node| {
+            uuids.insert(node.uuid);
+        });
+        let mut labels: Vec<String> = self
+            .labels
+            .iter()
+            .filter(|(_, nodes)| nodes.iter().any(|uuid| uuids.contains(uuid)))
+            .map(|(label, _)| label.clone())
+            .collect();
+        labels.sort();
+        labels
+    }
+}
+
+/// Renames `old` to `new` in the label of `node` and in all labels and references in its LaTeX
+/// content.
+pub(crate) fn rename_label_in_node(node: &mut Node, old: &str, new: &str) {
+    if let NodeType::Leaf {
+        data: LeafData::Label { label },
+    } = &mut node.node_type
+    {
+        if label == old {
+            *label = new.to_string();
+        }
+    }
+
+    if let Some(content) = node.node_type.latex_content_mut() {
+        let mut commands = REFERENCE_COMMANDS.to_vec();
+        commands.push(LABEL);
+        // replace from back to front to keep the ranges valid
+        for key in find_keys(content, &commands).into_iter().rev() {
+            if &content[key.clone()] == old {
+                content.replace_range(key, new);
+            }
+        }
+    }
+}
+
+/// A command found by [find_commands] together with the byte ranges of its arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CommandMatch {
+    /// The whole command including its arguments
+    pub(crate) range: Range<usize>,
+    /// The end of the name of the command including a star
+    pub(crate) name_end: usize,
+    /// The contents of the optional arguments
+    pub(crate) options: Vec<Range<usize>>,
+    /// The content of the argument in braces
+    pub(crate) argument: Range<usize>,
+}
+
+/// Finds all occurrences of the given commands in `latex` which have an argument in braces.
+/// Starred variants and optional arguments (e.g. `\cite[p. 3]{key}`) are supported.
+pub(crate) fn find_commands(latex: &str, commands: &[&str]) -> Vec<CommandMatch> {
+    let mut matches = vec![];
+    for (start, _) in latex.match_indices(KEYWORD_PREFIX) {
+        let rest = &latex[start..];
+        let command = match commands.iter().find(|command| {
+            rest.starts_with(*command)
+                && !rest[command.len()..].starts_with(|c: char| c.is_ascii_alphabetic())
+        }) {
+            Some(command) => command,
+            None => continue,
+        };
+
+        let mut position = start + command.len();
+        position += latex[position..].len() - latex[position..].trim_start_matches('*').len();
+        let name_end = position;
+        let mut options = vec![];
+        while latex[position..].trim_start().starts_with(OPTIONS_BEGIN) {
+            let options_start = position + latex[position..].find(OPTIONS_BEGIN).unwrap() + 1;
+            match latex[options_start..].find(OPTIONS_END) {
+                Some(options_length) => {
+                    options.push(options_start..options_start + options_length);
+                    position = options_start + options_length + 1;
+                }
+                None => break,
+            }
+        }
+        if !latex[position..].trim_start().starts_with(BLOCK_BEGIN) {
+            continue;
+        }
+        let argument_start = position + latex[position..].find(BLOCK_BEGIN).unwrap() + 1;
+        let argument_end = match latex[argument_start..].find(BLOCK_END) {
+            Some(argument_length) => argument_start + argument_length,
+            None => continue,
+        };
+
+        matches.push(CommandMatch {
+            range: start..argument_end + 1,
+            name_end,
+            options,
+            argument: argument_start..argument_end,
+        });
+    }
+    matches
+}
+
+/// Finds all arguments of the given commands in `latex` and returns the byte ranges of the comma
+/// separated keys in them, e.g. `a` and `b` in `\cref{a, b}`.
+pub(crate) fn find_keys(latex: &str, commands: &[&str]) -> Vec<Range<usize>> {
+    let mut keys = vec![];
+    for command in find_commands(latex, commands) {
+        let mut key_start = command.argument.start;
+        for key in latex[command.argument].split(',') {
+            let leading_whitespace = key.len() - key.trim_start().len();
+            let range = key_start + leading_whitespace..key_start + key.trim_end().len();
+            if !range.is_empty() {
+                keys.push(range);
+            }
+            key_start += key.len() + 1;
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::operation::test::find_uuid_by_content;
+    use crate::parser::parse_latex;
+    use crate::reference_index::{find_keys, ReferenceIndex};
+
+    #[test]
+    fn find_keys_in_latex() {
+        let latex = r"see \ref{a} and \cref{b, c}, \Cref*[x]{d} but not \refstepcounter{e}";
+        let keys: Vec<&str> = find_keys(latex, &["\\ref", "\\cref", "\\Cref"])
+            .into_iter()
+            .map(|range| &latex[range])
+            .collect();
+        assert_eq!(keys, vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn index_labels_and_references() {
+        let latex = fs::read_to_string("../test_resources/latex/references.tex").unwrap();
+        let ast = parse_latex(latex).expect("Valid Latex");
+        let index = ReferenceIndex::new(&ast);
+
+        let introduction_label = find_uuid_by_content(&ast, "\\label{sec:intro}").unwrap();
+        assert_eq!(index.target("sec:intro"), Some(introduction_label));
+        assert!(index.labels.contains_key("eq:energy"));
+        assert_eq!(index.references["sec:intro"].len(), 2);
+        assert_eq!(index.references["eq:energy"].len(), 1);
+        assert_eq!(index.target("sec:missing"), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! `source_map` relates the lines of the LaTeX files written for an Ast back to its nodes, so that
+//! messages of a TeX engine can be shown at the node which produced them.
+use std::collections::HashMap;
+
+use crate::errors::AstError;
+use crate::latex_constants::LATEX_FILE_EXTENSION;
+use crate::node::{ExpandableData, NodeRef, NodeType};
+use crate::options::StringificationOptions;
+use crate::texla_ast::TexlaAst;
+use crate::uuid_provider::Uuid;
+
+/// Stands in for the code of the children while the code around them is stringified.
+const CHILDREN_MARK: &str = "\u{0}";
+
+/// The nodes the lines of each file belong to, as the files are written by the storage manager
+/// (the code of each included file is replaced by `\input{...}` in the including file).
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    root: Uuid,
+    /// For each file (`None` for the main file): the node which produced each line
+    files: HashMap<Option<String>, Vec<Uuid>>,
+}
+
+impl SourceMap {
+    /// Returns the innermost node which produced the given line (starting at 1) of the given file.
+    /// `file` is the path of an included file as it is used in `\input{...}` (optionally with
+    /// a leading `./` and the `.tex` extension) or `None` for the main file.
+    /// Lines which cannot be attributed to a node belong to the root node.
+    pub fn node_at(&self, file: Option<&str>, line: usize) -> Uuid {
+        self.files
+            .get(&file.map(normalize_path))
+            .and_then(|lines| {
+                lines
+                    .get(line.saturating_sub(1))
+                    // the last line of a file may have no line break
+                    .or(lines.last())
+            })
+            .copied()
+            .unwrap_or(self.root)
+    }
+
+    /// Returns the file (`None` for the main file) and the first and last line produced by the
+    /// node with the given Uuid. The lines in between may belong to its descendants.
+    pub fn lines_of(&self, uuid: Uuid) -> Option<(Option<String>, usize, usize)> {
+        self.files.iter().find_map(|(file, lines)| {
+            let first = lines.iter().position(|owner| *owner == uuid)?;
+            let last = lines.iter().rposition(|owner| *owner == uuid)?;
+            Some((file.clone(), first + 1, last + 1))
+        })
+    }
+
+    pub fn root(&self) -> Uuid {
+        self.root
+    }
+}
+
+impl TexlaAst {
+    /// Creates the [SourceMap] for the LaTeX code stringified with the given options.
+    pub fn source_map(&self, options: &StringificationOptions) -> Result<SourceMap, AstError> {
+        let mut builder = SourceMapBuilder {
+            options,
+            files: HashMap::from([(None, vec![])]),
+        };
+        builder.node(&self.root, self.highest_level, &None)?;
+        Ok(SourceMap {
+            root: self.root.lock().unwrap().uuid,
+            files: builder.files,
+        })
+    }
+}
+
+struct SourceMapBuilder<'a> {
+    options: &'a StringificationOptions,
+    files: HashMap<Option<String>, Vec<Uuid>>,
+}
+
+impl SourceMapBuilder<'_> {
+    fn node(
+        &mut self,
+        node_ref: &NodeRef,
+        level: i8,
+        file: &Option<String>,
+    ) -> Result<(), AstError> {
+        let node = node_ref.lock().unwrap();
+        let uuid = node.uuid;
+        let children = match &node.node_type {
+            NodeType::Leaf { .. } => {
+                let latex = node.to_latex(level, self.options)?;
+                self.lines(file, uuid, &latex);
+                return Ok(());
+            }
+            NodeType::Expandable {
+                data: ExpandableData::File { path },
+                children,
+            } => {
+                // the file is written on its own and included by a single line
+                let included = Some(normalize_path(path));
+                let children = children.clone();
+                let children_level = node.node_type.children_level(level);
+                drop(node);
+                self.lines(file, uuid, "\n");
+                self.files.insert(included.clone(), vec![]);
+                for child_ref in &children {
+                    self.node(child_ref, children_level, &included)?;
+                }
+                return Ok(());
+            }
+            NodeType::Expandable { children, .. } => children.clone(),
+        };
+        let children_level = node.node_type.children_level(level);
+        let latex = node.to_latex_with_children(level, self.options, CHILDREN_MARK.to_string())?;
+        drop(node);
+
+        let (before_children, after_children) = latex.split_once(CHILDREN_MARK).unwrap_or_default();
+        self.lines(file, uuid, before_children);
+        for child_ref in &children {
+            self.node(child_ref, children_level, file)?;
+        }
+        self.lines(file, uuid, after_children);
+        Ok(())
+    }
+
+    /// Attributes each line ended in `latex` to the node with the given Uuid.
+    fn lines(&mut self, file: &Option<String>, uuid: Uuid, latex: &str) {
+        let lines = self.files.entry(file.clone()).or_default();
+        lines.extend(latex.matches('\n').map(|_| uuid));
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    let path = path.strip_prefix("./").unwrap_or(path);
+    path.strip_suffix(&format!(".{LATEX_FILE_EXTENSION}"))
+        .unwrap_or(path)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::operation::test::find_uuid_by_content;
+    use crate::parser::parse_latex;
+
+    #[test]
+    fn lines_of_nodes() {
+        let latex = "\\documentclass{article}\n\\begin{document}\n\\section{Intro}\nSome text\n\n\\begin{equation}\ne = mc^2\n\\end{equation}\n% TEXLA FILE BEGIN {sections/results}\n\\section{Results}\nMore text\n\n% TEXLA FILE END {sections/results}\n\\end{document}\n";
+        let ast = parse_latex(latex.to_string()).expect("Valid Latex");
+        let source_map = ast.source_map(&Default::default()).unwrap();
+
+        let intro = find_uuid_by_content(&ast, "Intro").unwrap();
+        let text = find_uuid_by_content(&ast, "Some text").unwrap();
+        let equation = find_uuid_by_content(&ast, "e = mc^2").unwrap();
+        let results = find_uuid_by_content(&ast, "Results").unwrap();
+        let more_text = find_uuid_by_content(&ast, "More text").unwrap();
+
+        assert_eq!(source_map.node_at(None, 1), source_map.root());
+        assert_eq!(source_map.node_at(None, 3), intro);
+        assert_eq!(source_map.node_at(None, 4), text);
+        assert_eq!(source_map.node_at(None, 7), equation);
+        assert_eq!(source_map.node_at(Some("sections/results"), 1), results);
+        assert_eq!(
+            source_map.node_at(Some("./sections/results.tex"), 2),
+            more_text
+        );
+        // the included file takes a single line in the main file
+        assert_eq!(source_map.node_at(None, 10), source_map.root());
+        assert_eq!(source_map.lines_of(equation), Some((None, 6, 8)));
+        assert_eq!(
+            source_map.lines_of(more_text),
+            Some((Some("sections/results".to_string()), 2, 3))
+        );
+    }
+}
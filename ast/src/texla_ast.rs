@@ -1,15 +1,18 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
 use serde::Serialize;
 
-use crate::errors::AstError;
-use crate::node::{NodeRef, NodeRefWeak, NodeType};
+use crate::errors::{AstError, OperationError};
+use crate::meta_data::MetaData;
+use crate::node::{visit_subtree, ExpandableData, Node, NodeRef, NodeRefWeak, NodeType};
 use crate::operation::Operation;
 use crate::options::StringificationOptions;
-use crate::uuid_provider::{Position, TexlaUuidProvider, Uuid};
+use crate::uuid_provider::{Position, TexlaUuidProvider, Uuid, UuidProvider};
 use crate::{parser, Ast};
 
+const PATH_SEPARATORS: [char; 2] = ['/', '\\'];
+
 /// `TexlaAst` Implements [Ast] and can represent LaTex Documents which follow a number of specifications in the Pflichtenheft Document.
 #[derive(Debug, Serialize, Clone)]
 pub struct TexlaAst {
@@ -27,6 +30,8 @@ pub struct TexlaAst {
 /// - A parent weak reference must be valid and must be an Expandable Node.
 /// - A portal weak reference must be valid.
 /// - No non-existing UUIDs are queried.
+///
+/// Operations ensure these invariants by calling the `validate_*()` methods before modifying the Ast.
 impl TexlaAst {
     pub(crate) fn get_node(&self, uuid: Uuid) -> NodeRef {
         self.portal
@@ -54,8 +59,12 @@ impl TexlaAst {
                     + 1
             }
         };
-        self.portal
-            .insert(node_ref.lock().unwrap().uuid, Arc::downgrade(&node_ref));
+        {
+            let mut node = node_ref.lock().unwrap();
+            // keep the tree consistent for further operations before the next reparse
+            node.parent = Some(Arc::downgrade(&parent_ref));
+            self.portal.insert(node.uuid, Arc::downgrade(&node_ref));
+        }
         parent_children.insert(index, node_ref);
     }
 
@@ -83,6 +92,220 @@ impl TexlaAst {
             parent: parent.uuid, // the order of properties matters here because of borrowing
         }
     }
+
+    /// Checks that there is a Node with the given Uuid.
+    pub(crate) fn validate_node(&self, uuid: Uuid) -> Result<(), OperationError> {
+        match self
+            .portal
+            .get(&uuid)
+            .and_then(|node_ref| node_ref.upgrade())
+        {
+            Some(_) => Ok(()),
+            None => Err(OperationError::UnknownNode { uuid }),
+        }
+    }
+
+    /// Checks that the Node with the given Uuid exists and can be removed, i.e. is not the root.
+    pub(crate) fn validate_removal(&self, uuid: Uuid) -> Result<(), OperationError> {
+        self.validate_node(uuid)?;
+        match self.get_node(uuid).lock().unwrap().parent {
+            Some(_) => Ok(()),
+            None => Err(OperationError::RootDeletion),
+        }
+    }
+
+    /// Checks that a Node can be inserted at the given [Position].
+    pub(crate) fn validate_position(&self, position: Position) -> Result<(), OperationError> {
+        self.validate_node(position.parent)?;
+        let parent_ref = self.get_node(position.parent);
+        let parent = parent_ref.lock().unwrap();
+        let children = match &parent.node_type {
+            NodeType::Expandable { children, .. } => children,
+            NodeType::Leaf { .. } => {
+                return Err(OperationError::InvalidParent {
+                    uuid: position.parent,
+                })
+            }
+        };
+        match position.after_sibling {
+            Some(sibling)
+                if !children
+                    .iter()
+                    .any(|child_ref| child_ref.lock().unwrap().uuid == sibling) =>
+            {
+                Err(OperationError::UnknownSibling {
+                    parent: position.parent,
+                    sibling,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the Node with the Uuid `target` can be moved to the given [Position].
+    /// In particular, the Node must not be moved into its own subtree.
+    pub(crate) fn validate_move(
+        &self,
+        target: Uuid,
+        position: Position,
+    ) -> Result<(), OperationError> {
+        self.validate_removal(target)?;
+        self.validate_position(position)?;
+        if position.after_sibling == Some(target) {
+            return Err(OperationError::InvalidArgument {
+                message: "a node cannot be moved behind itself".to_string(),
+            });
+        }
+
+        let mut ancestor_ref = Some(self.get_node(position.parent));
+        while let Some(node_ref) = ancestor_ref {
+            let node = node_ref.lock().unwrap();
+            if node.uuid == target {
+                return Err(OperationError::Cycle {
+                    target,
+                    destination: position.parent,
+                });
+            }
+            ancestor_ref = node.parent.as_ref().and_then(|parent| parent.upgrade());
+        }
+        Ok(())
+    }
+
+    /// Creates a deep copy of the subtree beneath `node_ref` in which every node has a fresh Uuid.
+    /// The copy is registered in the portal, but not inserted anywhere.
+    pub(crate) fn copy_subtree(
+        &mut self,
+        node_ref: &NodeRef,
+        parent: Option<NodeRefWeak>,
+    ) -> NodeRef {
+        let node = node_ref.lock().unwrap();
+        let copy_ref = Arc::new(Mutex::new(Node {
+            uuid: self.uuid_provider.new_uuid(),
+            node_type: match &node.node_type {
+                NodeType::Leaf { data } => NodeType::Leaf { data: data.clone() },
+                NodeType::Expandable { data, .. } => NodeType::Expandable {
+                    data: data.clone(),
+                    children: vec![],
+                },
+            },
+            meta_data: MetaData {
+                data: node.meta_data.data.clone(),
+            },
+            parent,
+            raw_latex: node.raw_latex.clone(),
+        }));
+
+        if let NodeType::Expandable { children, .. } = &node.node_type {
+            let children_copies = children
+                .iter()
+                .map(|child_ref| self.copy_subtree(child_ref, Some(Arc::downgrade(&copy_ref))))
+                .collect();
+            if let NodeType::Expandable { children, .. } = &mut copy_ref.lock().unwrap().node_type {
+                *children = children_copies;
+            }
+        }
+
+        let uuid = copy_ref.lock().unwrap().uuid;
+        self.portal.insert(uuid, Arc::downgrade(&copy_ref));
+        copy_ref
+    }
+
+    /// Returns the child indices leading from the root to the node with the given Uuid.
+    /// In contrast to Uuids, these paths survive reparsing as long as the structure is unchanged.
+    pub fn node_path(&self, uuid: Uuid) -> Option<Vec<usize>> {
+        let mut path = vec![];
+        let mut node_ref = self.portal.get(&uuid)?.upgrade()?;
+        loop {
+            let parent_ref = match &node_ref.lock().unwrap().parent {
+                Some(parent_ref_weak) => parent_ref_weak.upgrade()?,
+                None => break,
+            };
+            let index = match &parent_ref.lock().unwrap().node_type {
+                NodeType::Expandable { children, .. } => children
+                    .iter()
+                    .position(|child_ref| Arc::ptr_eq(child_ref, &node_ref))?,
+                NodeType::Leaf { .. } => return None,
+            };
+            path.push(index);
+            node_ref = parent_ref;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Returns the Uuid of the node at the given path of child indices (see `node_path()`).
+    pub fn node_at_path(&self, path: &[usize]) -> Option<Uuid> {
+        let mut node_ref = self.root.clone();
+        for index in path {
+            let child_ref = match &node_ref.lock().unwrap().node_type {
+                NodeType::Expandable { children, .. } => children.get(*index)?.clone(),
+                NodeType::Leaf { .. } => return None,
+            };
+            node_ref = child_ref;
+        }
+        let uuid = node_ref.lock().unwrap().uuid;
+        Some(uuid)
+    }
+
+    /// Returns the level of the node with the given Uuid, which is needed to stringify it on its
+    /// own (see [crate::latex_constants::SEGMENT_LEVELS]).
+    pub(crate) fn node_level(&self, uuid: Uuid) -> Option<i8> {
+        let mut node_ref = self.root.clone();
+        let mut level = self.highest_level;
+        for index in self.node_path(uuid)? {
+            let child_ref = {
+                let node = node_ref.lock().unwrap();
+                level = node.node_type.children_level(level);
+                match &node.node_type {
+                    NodeType::Expandable { children, .. } => children.get(index)?.clone(),
+                    NodeType::Leaf { .. } => return None,
+                }
+            };
+            node_ref = child_ref;
+        }
+        Some(level)
+    }
+
+    /// Returns the LaTeX paths of all files the document consists of (except the main file).
+    pub fn file_paths(&self) -> HashSet<String> {
+        self.portal
+            .values()
+            .filter_map(|node_ref_weak| node_ref_weak.upgrade())
+            .filter_map(|node_ref| match &node_ref.lock().unwrap().node_type {
+                NodeType::Expandable {
+                    data: ExpandableData::File { path },
+                    ..
+                } => Some(path.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether this Ast contains unresolved merge conflicts.
+    pub fn has_conflicts(&self) -> bool {
+        let mut has_conflicts = false;
+        visit_subtree(&self.root, &mut |node| {
+            has_conflicts |= matches!(
+                node.node_type,
+                NodeType::Expandable {
+                    data: ExpandableData::Conflict,
+                    ..
+                }
+            );
+        });
+        has_conflicts
+    }
+}
+
+/// Whether a LaTeX `path` of a file used by the document (relative to the main file) stays inside
+/// the project directory. Only such files are part of the repository, and only they may be read
+/// or written on behalf of the document.
+pub fn is_inside_project(path: &str) -> bool {
+    let is_absolute = path.starts_with(PATH_SEPARATORS) || path.chars().nth(1) == Some(':');
+    !is_absolute
+        && !path
+            .split(PATH_SEPARATORS)
+            .any(|component| component == "..")
 }
 
 impl Ast for TexlaAst {
@@ -98,7 +321,10 @@ impl Ast for TexlaAst {
             .to_latex(self.highest_level, &options)?)
     }
 
-    fn execute(&mut self, operation: Box<dyn Operation<TexlaAst>>) -> Result<(), AstError> {
+    fn execute(
+        &mut self,
+        operation: Box<dyn Operation<TexlaAst>>,
+    ) -> Result<Option<Uuid>, AstError> {
         Ok(operation.execute_on(self)?)
     }
 }
@@ -107,7 +333,7 @@ impl Ast for TexlaAst {
 mod tests {
     use std::fs;
 
-    use crate::options::StringificationOptions;
+    use crate::options::{NodeFilter, StringificationOptions};
     use crate::parser::parse_latex;
     use crate::Ast;
 
@@ -185,8 +411,55 @@ mod tests {
             .to_latex(StringificationOptions {
                 include_comments: false,
                 include_metadata: false,
+                ..Default::default()
             })
             .unwrap();
         assert_eq!(lf(out), lf(formatted_latex));
     }
+
+    #[test]
+    fn filter_variants() {
+        let latex = "\\begin{document}\n\\section{Intro}\nText\n\n% TEXLA METADATA (variant:long,)\n\\section{Details}\nLong text\n\n% TEXLA METADATA (variant:long appendix,)\n\\section{Proofs}\nProofs\n\n% TEXLA METADATA (variant:short,)\n\\section{Summary}\nShort text\n\n\\end{document}\n";
+        let ast = parse_latex(latex.to_string()).expect("Valid Latex");
+        let sections = |node_filter: NodeFilter| {
+            let out = ast
+                .to_latex(StringificationOptions {
+                    include_comments: true,
+                    include_metadata: false,
+                    node_filter,
+                })
+                .unwrap();
+            out.lines()
+                .filter(|line| line.starts_with("\\section"))
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            sections(NodeFilter::default()),
+            vec![
+                "\\section{Intro}",
+                "\\section{Details}",
+                "\\section{Proofs}",
+                "\\section{Summary}"
+            ]
+        );
+        let short = NodeFilter {
+            include_variants: vec!["short".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            sections(short),
+            vec!["\\section{Intro}", "\\section{Summary}"]
+        );
+        let long_without_appendix = NodeFilter {
+            include_variants: vec!["long".to_string()],
+            exclude_variants: vec!["appendix".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            sections(long_without_appendix),
+            vec!["\\section{Intro}", "\\section{Details}"]
+        );
+    }
 }
@@ -1,44 +1,46 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use serde::{Deserialize, Serialize};
 
-pub(crate) type Uuid = u64;
+pub type Uuid = u64;
 
 static JS_MAX_SAFE_INTEGER: Uuid = 2u64.pow(53);
 static MAX_UUID: Uuid = JS_MAX_SAFE_INTEGER;
 
-/// This uses unsafe code but is safe, because UUIDs do not need to be unique across ASTs that are
-/// built at the same time, as they cannot belong to the same client.
-/// Cleaner: https://stackoverflow.com/questions/27791532/how-do-i-create-a-global-mutable-singleton
-static mut HIGHEST_UUID: Uuid = 0;
+/// The highest UUID handed out so far, shared by all ASTs of the process.
+///
+/// UUIDs are unique across every AST that is ever built in this process, including ASTs built
+/// concurrently by different clients, background builds and exports. Translating the UUIDs of a
+/// stale AST into the current one (see `TexlaState::translate_uuid`) relies on this, because
+/// otherwise a stale UUID could also name an unrelated node of another AST version.
+static HIGHEST_UUID: AtomicU64 = AtomicU64::new(0);
 
 pub(crate) trait UuidProvider {
     fn new_uuid(&mut self) -> Uuid;
 }
 
 #[derive(Debug, Clone, Serialize)]
-pub(crate) struct TexlaUuidProvider {
-    highest_uuid: Uuid,
-}
+pub(crate) struct TexlaUuidProvider;
 
 impl UuidProvider for TexlaUuidProvider {
-    /// For a justification of unsafe, see [HIGHEST_UUID]
     fn new_uuid(&mut self) -> Uuid {
-        unsafe {
-            HIGHEST_UUID += 1;
-            HIGHEST_UUID %= MAX_UUID;
-            HIGHEST_UUID
-        }
+        let uuid = HIGHEST_UUID.fetch_add(1, Ordering::Relaxed) + 1;
+        // the frontend cannot represent larger UUIDs exactly, and wrapping around would break
+        // the uniqueness of UUIDs
+        assert!(uuid < MAX_UUID, "ran out of UUIDs");
+        uuid
     }
 }
 
 impl TexlaUuidProvider {
     pub(crate) fn new() -> Self {
-        TexlaUuidProvider { highest_uuid: 0 }
+        TexlaUuidProvider
     }
 }
 
 /// Represents a Position in an [Ast]. The Position points between to nodes or behind a node in order to allow specifying positions which are not currently occupied.
 /// As a result this can not be used to specify the position of a node that is already in the Ast.
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct Position {
     pub parent: Uuid,
     pub after_sibling: Option<Uuid>,
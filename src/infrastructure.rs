@@ -1,8 +1,11 @@
+pub mod build_manager;
 mod dir_watcher;
 pub mod errors;
 pub mod export_manager;
 pub(crate) mod file_path;
+pub mod journal;
 mod pull_timer;
 pub mod storage_manager;
+pub mod synctex;
 pub mod vcs_manager;
 mod work_session;
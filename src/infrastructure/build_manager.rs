@@ -0,0 +1,492 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use ast::latex_constants::{LATEX_FILE_EXTENSION, LATEX_PATH_SEPARATOR};
+
+use crate::infrastructure::errors::InfrastructureError;
+use crate::infrastructure::file_path::{texla_directory, FilePath, TEXLA_DIRECTORY};
+use crate::infrastructure::synctex::{PdfLocation, SyncTex};
+
+pub(crate) const BUILD_DIRECTORY: &str = "build";
+const LOG_EXTENSION: &str = "log";
+const PDF_EXTENSION: &str = "pdf";
+const SYNCTEX_EXTENSION: &str = "synctex.gz";
+
+/// The number of lines after an error which are searched for its line number
+const ERROR_CONTEXT_LINES: usize = 10;
+const FILE_EXTENSIONS: [&str; 4] = [".tex", ".sty", ".cls", ".bbl"];
+
+/// The local TeX engines TeXLa can build the PDF with
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TexEngine {
+    Latexmk,
+    Pdflatex,
+    Lualatex,
+    Tectonic,
+}
+
+impl TexEngine {
+    fn program(&self) -> &'static str {
+        match self {
+            TexEngine::Latexmk => "latexmk",
+            TexEngine::Pdflatex => "pdflatex",
+            TexEngine::Lualatex => "lualatex",
+            TexEngine::Tectonic => "tectonic",
+        }
+    }
+
+    fn arguments(&self, output_directory: &str, main_file: &str) -> Vec<String> {
+        match self {
+            TexEngine::Latexmk => vec![
+                "-pdf".to_string(),
+                "-interaction=nonstopmode".to_string(),
+                "-file-line-error".to_string(),
+                "-synctex=1".to_string(),
+                format!("-outdir={output_directory}"),
+                main_file.to_string(),
+            ],
+            TexEngine::Pdflatex | TexEngine::Lualatex => vec![
+                "-interaction=nonstopmode".to_string(),
+                "-file-line-error".to_string(),
+                "-synctex=1".to_string(),
+                format!("-output-directory={output_directory}"),
+                main_file.to_string(),
+            ],
+            TexEngine::Tectonic => vec![
+                "--keep-logs".to_string(),
+                "--synctex".to_string(),
+                "--outdir".to_string(),
+                output_directory.to_string(),
+                main_file.to_string(),
+            ],
+        }
+    }
+
+    /// The number of times the engine has to be run to resolve references.
+    /// latexmk and tectonic rerun the engine themselves when necessary.
+    fn passes(&self) -> usize {
+        match self {
+            TexEngine::Pdflatex | TexEngine::Lualatex => 2,
+            TexEngine::Latexmk | TexEngine::Tectonic => 1,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum LogEntryKind {
+    Error,
+    Warning,
+    /// Overfull and underfull boxes
+    BadBox,
+}
+
+/// An error or a warning found in the log of the TeX engine.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub kind: LogEntryKind,
+    pub message: String,
+    /// The file the entry refers to, if the log states it
+    pub file: Option<String>,
+    pub line: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BuildResult {
+    pub success: bool,
+    /// Where the PDF can be downloaded, if one was created
+    pub pdf_url: Option<String>,
+    pub entries: Vec<LogEntry>,
+}
+
+pub trait BuildManager {
+    /// Builds the PDF of the document and calls `progress` with a message before each step.
+    fn build(&self, progress: &dyn Fn(String)) -> Result<BuildResult, InfrastructureError>;
+    /// Returns where the given lines of a file (`None` for the main file) were typeset in the
+    /// last build.
+    fn pdf_location(
+        &self,
+        file: Option<&str>,
+        first_line: usize,
+        last_line: usize,
+    ) -> Result<Option<PdfLocation>, InfrastructureError>;
+    /// Returns the file (`None` for the main file) and the line which produced the content at the
+    /// given point of the PDF of the last build.
+    fn source_location(
+        &self,
+        location: PdfLocation,
+    ) -> Result<Option<(Option<String>, usize)>, InfrastructureError>;
+}
+
+#[derive(Clone)]
+pub struct TexlaBuildManager {
+    main_file: FilePath,
+    engine: TexEngine,
+    // builds use the same directory, so they must not run at the same time
+    build_lock: Arc<Mutex<()>>,
+}
+
+impl TexlaBuildManager {
+    pub fn new(main_file: FilePath, engine: TexEngine) -> Self {
+        Self {
+            main_file,
+            engine,
+            build_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn engine(&self) -> TexEngine {
+        self.engine
+    }
+
+    fn output_path(&self, extension: &str) -> PathBuf {
+        self.main_file
+            .directory
+            .join(TEXLA_DIRECTORY)
+            .join(BUILD_DIRECTORY)
+            .join(&self.main_file.filename)
+            .with_extension(extension)
+    }
+
+    fn synctex(&self) -> Result<SyncTex, InfrastructureError> {
+        let path = self.output_path(SYNCTEX_EXTENSION);
+        if !path.is_file() {
+            return Err(InfrastructureError::from(std::io::Error::new(
+                ErrorKind::NotFound,
+                "The document has not been built with SyncTeX yet",
+            )));
+        }
+        SyncTex::read(&path)
+    }
+
+    /// Converts the path of an input file in the SyncTeX file (which may be absolute and contain
+    /// `./`) to a path relative to the project directory.
+    fn relative_input_path(&self, input: &str) -> String {
+        let input = Path::new(input);
+        let directory = &self.main_file.directory;
+        let relative = directory
+            .canonicalize()
+            .ok()
+            .and_then(|canonical| input.strip_prefix(canonical).ok())
+            .or_else(|| input.strip_prefix(directory).ok())
+            .unwrap_or(input);
+        relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(LATEX_PATH_SEPARATOR)
+    }
+
+    fn is_main_file(&self, relative_path: &str) -> bool {
+        relative_path == self.main_file.filename
+    }
+}
+
+impl BuildManager for TexlaBuildManager {
+    fn build(&self, progress: &dyn Fn(String)) -> Result<BuildResult, InfrastructureError> {
+        // a panicked build leaves nothing inconsistent behind, so later builds can still run
+        let _build_lock = self
+            .build_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        fs::create_dir_all(texla_directory(&self.main_file.directory)?.join(BUILD_DIRECTORY))?;
+        // the engine runs inside the project directory, so that relative paths are resolved
+        let output_directory = format!("{TEXLA_DIRECTORY}/{BUILD_DIRECTORY}");
+        let program = self.engine.program();
+
+        let mut success = false;
+        for pass in 1..=self.engine.passes() {
+            progress(format!(
+                "Running {program} (pass {pass} of {})",
+                self.engine.passes()
+            ));
+            let output = Command::new(program)
+                .current_dir(&self.main_file.directory)
+                .args(
+                    self.engine
+                        .arguments(&output_directory, &self.main_file.filename),
+                )
+                .output()
+                .map_err(|err| match err.kind() {
+                    ErrorKind::NotFound => InfrastructureError::from(std::io::Error::new(
+                        ErrorKind::NotFound,
+                        format!("'{program}' is not installed"),
+                    )),
+                    _ => InfrastructureError::from(err),
+                })?;
+            success = output.status.success();
+            if !success {
+                // later passes would only repeat the errors
+                break;
+            }
+        }
+
+        progress("Reading the log".to_string());
+        let entries = fs::read_to_string(self.output_path(LOG_EXTENSION))
+            .map(|log| parse_log(&log))
+            .unwrap_or_default();
+        let pdf_url = self.output_path(PDF_EXTENSION).is_file().then(|| {
+            let pdf_name = self
+                .output_path(PDF_EXTENSION)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+            format!("/user-assets/{output_directory}/{pdf_name}")
+        });
+
+        Ok(BuildResult {
+            success: success && pdf_url.is_some(),
+            pdf_url,
+            entries,
+        })
+    }
+
+    fn pdf_location(
+        &self,
+        file: Option<&str>,
+        first_line: usize,
+        last_line: usize,
+    ) -> Result<Option<PdfLocation>, InfrastructureError> {
+        let without_extension = |path: &str| {
+            let extension = format!(".{LATEX_FILE_EXTENSION}");
+            path.strip_suffix(&extension).unwrap_or(path).to_string()
+        };
+        let is_file = |input: &str| {
+            let input = self.relative_input_path(input);
+            match file {
+                Some(file) => without_extension(&input) == without_extension(file),
+                None => self.is_main_file(&input),
+            }
+        };
+        Ok(self.synctex()?.forward(is_file, first_line, last_line))
+    }
+
+    fn source_location(
+        &self,
+        location: PdfLocation,
+    ) -> Result<Option<(Option<String>, usize)>, InfrastructureError> {
+        let synctex = self.synctex()?;
+        Ok(synctex
+            .backward(location.page, location.x, location.y)
+            .map(|(input, line)| {
+                let input = self.relative_input_path(input);
+                let file = (!self.is_main_file(&input)).then_some(input);
+                (file, line)
+            }))
+    }
+}
+
+/// Finds errors, warnings and bad boxes in the log of a TeX engine.
+/// Errors are found in both the `-file-line-error` format (`./main.tex:12: ...`) and the classic
+/// one (`! ...` followed by `l.12 ...`).
+/// Entries which do not state their file belong to the file the engine was reading at that point,
+/// which is tracked by the parentheses the engine prints around the content of each file.
+pub fn parse_log(log: &str) -> Vec<LogEntry> {
+    let lines: Vec<&str> = log.lines().collect();
+    let mut entries = vec![];
+    let mut open_files: Vec<Option<String>> = vec![];
+
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        index += 1;
+        let current_file = open_files.iter().rev().flatten().next().cloned();
+        let entry_count = entries.len();
+
+        if let Some((file, line_number, message)) = file_line_error(line) {
+            entries.push(LogEntry {
+                kind: LogEntryKind::Error,
+                message: message.to_string(),
+                file: Some(file.to_string()),
+                line: Some(line_number),
+            });
+        } else if let Some(message) = line.strip_prefix("! ") {
+            let line_number = lines[index..]
+                .iter()
+                .take(ERROR_CONTEXT_LINES)
+                .find_map(|line| number_after(line, "l."));
+            entries.push(LogEntry {
+                kind: LogEntryKind::Error,
+                message: message.to_string(),
+                file: None,
+                line: line_number,
+            });
+        } else if is_warning(line) {
+            // warnings are continued in the following lines until an empty line
+            let mut message = line.trim().to_string();
+            while index < lines.len() && !lines[index].trim().is_empty() {
+                message += " ";
+                message += continuation(lines[index]);
+                index += 1;
+            }
+            let line_number = number_after(&message, "on input line ");
+            entries.push(LogEntry {
+                kind: LogEntryKind::Warning,
+                message,
+                file: None,
+                line: line_number,
+            });
+        } else if line.starts_with("Overfull \\") || line.starts_with("Underfull \\") {
+            let line_number = number_after(line, "at lines ")
+                .or_else(|| number_after(line, "at line "))
+                .or_else(|| number_after(line, "detected at line "));
+            entries.push(LogEntry {
+                kind: LogEntryKind::BadBox,
+                message: line.trim().to_string(),
+                file: None,
+                line: line_number,
+            });
+        } else {
+            track_files(line, &mut open_files);
+        }
+
+        for entry in &mut entries[entry_count..] {
+            entry.file = entry.file.take().or_else(|| current_file.clone());
+        }
+    }
+
+    entries
+}
+
+/// Pushes the files opened and pops the ones closed in `line` of a log.
+/// Other parentheses are tracked as well (as `None`), so that they do not close a file.
+fn track_files(line: &str, open_files: &mut Vec<Option<String>>) {
+    let mut rest = line;
+    while let Some(position) = rest.find(['(', ')']) {
+        if rest[position..].starts_with(')') {
+            open_files.pop();
+            rest = &rest[position + 1..];
+        } else {
+            rest = &rest[position + 1..];
+            let name_length = rest.find([' ', '(', ')']).unwrap_or(rest.len());
+            let name = &rest[..name_length];
+            let is_file = FILE_EXTENSIONS
+                .iter()
+                .any(|extension| name.ends_with(extension));
+            open_files.push(is_file.then(|| name.to_string()));
+            rest = &rest[name_length..];
+        }
+    }
+}
+
+/// Splits an error in the `-file-line-error` format into file, line number and message.
+fn file_line_error(line: &str) -> Option<(&str, usize, &str)> {
+    for (colon, _) in line.match_indices(':') {
+        let file = &line[..colon];
+        if !FILE_EXTENSIONS
+            .iter()
+            .any(|extension| file.ends_with(extension))
+        {
+            continue;
+        }
+        let rest = &line[colon + 1..];
+        let (number, message) = rest.split_once(": ")?;
+        return Some((file, number.parse().ok()?, message));
+    }
+    None
+}
+
+/// Removes the package name packages put in front of the continuation lines of their warnings,
+/// e.g. `(hyperref)`.
+fn continuation(line: &str) -> &str {
+    let line = line.trim();
+    match line.strip_prefix('(').and_then(|rest| rest.split_once(')')) {
+        Some((name, rest)) if !name.contains(' ') => rest.trim(),
+        _ => line,
+    }
+}
+
+fn is_warning(line: &str) -> bool {
+    (line.starts_with("LaTeX ") || line.starts_with("Package ") || line.starts_with("Class "))
+        && line.contains("Warning:")
+}
+
+/// Parses the number following the first occurrence of `prefix` in `text`.
+fn number_after(text: &str, prefix: &str) -> Option<usize> {
+    let start = text.find(prefix)? + prefix.len();
+    let digits: String = text[start..]
+        .chars()
+        .take_while(|char| char.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::infrastructure::build_manager::{parse_log, LogEntry, LogEntryKind};
+
+    #[test]
+    fn parse_latex_log() {
+        let log = r"This is pdfTeX, Version 3.141592653-2.6-1.40.25 (TeX Live 2023) (preloaded format=pdflatex)
+(./main.tex
+LaTeX2e <2022-11-01> patch level 1
+(/usr/share/texlive/texmf-dist/tex/latex/base/article.cls
+Document Class: article 2022/07/02 v1.4n Standard LaTeX document class
+(/usr/share/texlive/texmf-dist/tex/latex/base/size10.clo))
+(./sections/intro.tex
+./sections/intro.tex:12: Undefined control sequence.
+l.12 \foo
+
+)
+
+! Missing $ inserted.
+<inserted text>
+                $
+l.27 a_
+       b
+LaTeX Warning: Reference `sec:missing' on page 1 undefined on input line 31.
+
+Package hyperref Warning: Token not allowed in a PDF string (Unicode):
+(hyperref)                removing `math shift' on input line 40.
+
+Overfull \hbox (15.0pt too wide) in paragraph at lines 44--46
+[]\OT1/cmr/m/n/10 Some very long line
+";
+
+        assert_eq!(
+            parse_log(log),
+            vec![
+                LogEntry {
+                    kind: LogEntryKind::Error,
+                    message: "Undefined control sequence.".to_string(),
+                    file: Some("./sections/intro.tex".to_string()),
+                    line: Some(12),
+                },
+                LogEntry {
+                    kind: LogEntryKind::Error,
+                    message: "Missing $ inserted.".to_string(),
+                    file: Some("./main.tex".to_string()),
+                    line: Some(27),
+                },
+                LogEntry {
+                    kind: LogEntryKind::Warning,
+                    message: "LaTeX Warning: Reference `sec:missing' on page 1 undefined on input line 31.".to_string(),
+                    file: Some("./main.tex".to_string()),
+                    line: Some(31),
+                },
+                LogEntry {
+                    kind: LogEntryKind::Warning,
+                    message: "Package hyperref Warning: Token not allowed in a PDF string (Unicode): removing `math shift' on input line 40.".to_string(),
+                    file: Some("./main.tex".to_string()),
+                    line: Some(40),
+                },
+                LogEntry {
+                    kind: LogEntryKind::BadBox,
+                    message: "Overfull \\hbox (15.0pt too wide) in paragraph at lines 44--46".to_string(),
+                    file: Some("./main.tex".to_string()),
+                    line: Some(44),
+                },
+            ]
+        );
+    }
+}
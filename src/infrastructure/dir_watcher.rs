@@ -9,6 +9,7 @@ use tokio::task::JoinHandle;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt;
 
+use crate::infrastructure::file_path::TEXLA_DIRECTORY;
 use crate::infrastructure::storage_manager::TexlaStorageManager;
 use crate::infrastructure::vcs_manager::GitManager;
 
@@ -84,12 +85,13 @@ impl DirectoryWatcher {
             // or we are still waiting for the frontend to finish its operation => ignore it
             false
         } else {
-            let only_git_files = event
-                .paths
-                .iter()
-                .all(|p| p.components().any(|c| c.as_os_str() == ".git"));
+            // changes by git or by TeXLa itself (e.g. to the journal) are not interesting
+            let only_internal_files = event.paths.iter().all(|p| {
+                p.components()
+                    .any(|c| c.as_os_str() == ".git" || c.as_os_str() == TEXLA_DIRECTORY)
+            });
 
-            !only_git_files
+            !only_internal_files
         }
     }
 
@@ -1,5 +1,7 @@
 use std::fmt::{Debug, Display, Formatter};
 
+use ast::errors::AstError;
+
 #[derive(Debug, PartialEq)]
 pub struct InfrastructureError {
     message: String,
@@ -35,6 +37,14 @@ impl From<ExportZipError> for InfrastructureError {
     }
 }
 
+impl From<AstError> for InfrastructureError {
+    fn from(value: AstError) -> Self {
+        Self {
+            message: value.to_string(),
+        }
+    }
+}
+
 impl From<std::io::Error> for InfrastructureError {
     fn from(err: std::io::Error) -> Self {
         Self {
@@ -51,6 +61,14 @@ impl From<zip::result::ZipError> for InfrastructureError {
     }
 }
 
+impl From<tokio::task::JoinError> for InfrastructureError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        Self {
+            message: format!("The build panicked: {err}"),
+        }
+    }
+}
+
 impl From<notify::Error> for InfrastructureError {
     fn from(err: notify::Error) -> Self {
         Self {
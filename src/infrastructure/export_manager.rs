@@ -1,72 +1,763 @@
+use std::collections::HashMap;
+use std::fs;
 use std::fs::File;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::io;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 
+use serde::{Deserialize, Serialize};
 use zip::write::FileOptions;
 use zip::CompressionMethod::Deflated;
 
-use crate::infrastructure::errors::InfrastructureError;
+use ast::export::anonymization::{to_anonymized, AnonymizationReport};
+use ast::export::html::to_html;
+use ast::export::markdown::to_markdown;
+use ast::export::standalone::to_standalone;
+use ast::export::submission::to_submission;
+use ast::latex_constants::LATEX_PATH_SEPARATOR;
+use ast::options::{NodeFilter, StringificationOptions};
+use ast::texla_ast::{is_inside_project, TexlaAst};
+
+use crate::infrastructure::build_manager::BUILD_DIRECTORY;
+use crate::infrastructure::errors::{ExportZipError, InfrastructureError};
+use crate::infrastructure::file_path::{texla_directory, TEXLA_DIRECTORY};
+
+const EXPORT_DIRECTORY: &str = "export";
+const HTML_DIRECTORY: &str = "html";
+const HTML_FILE: &str = "index.html";
+const MARKDOWN_DIRECTORY: &str = "markdown";
+const MARKDOWN_FILE: &str = "document.md";
+const STANDALONE_MAIN_FILE: &str = "main.tex";
+const STANDALONE_ZIP_FILE: &str = "standalone.zip";
+const SUBMISSION_MAIN_FILE: &str = "main.tex";
+const SUBMISSION_BBL_FILE: &str = "main.bbl";
+const SUBMISSION_ZIP_FILE: &str = "submission.zip";
+const ANONYMIZED_ZIP_FILE: &str = "anonymized.zip";
+const ZIP_FILE: &str = "export.zip";
+/// The directory in the TeXLa directory in which downloads are prepared
+const DOWNLOAD_DIRECTORY: &str = "downloads";
+/// The route under which prepared downloads are served
+pub const DOWNLOAD_URL: &str = "/downloads";
+/// How long a prepared download can be fetched before it is deleted
+const DOWNLOAD_LIFETIME: Duration = Duration::from_secs(60 * 60);
+/// The number of random bytes of a download token
+const DOWNLOAD_TOKEN_LENGTH: usize = 16;
+/// Lists all files which are tracked by git or not ignored by a `.gitignore`
+const GIT_LIST_FILES: [&str; 5] = [
+    "ls-files",
+    "--cached",
+    "--others",
+    "--exclude-standard",
+    "-z",
+];
+/// Files created by TeX engines and editors which are never exported
+const DEFAULT_EXPORT_EXCLUDES: [&str; 15] = [
+    "*.aux",
+    "*.log",
+    "*.out",
+    "*.toc",
+    "*.lof",
+    "*.lot",
+    "*.fls",
+    "*.fdb_latexmk",
+    "*.synctex.gz",
+    "*.blg",
+    "*.bcf",
+    "*.run.xml",
+    "*.nav",
+    "*.snm",
+    "*~",
+];
+const PROFILE_NAME_SEPARATOR: char = '=';
+const PROFILE_VARIANT_SEPARATOR: char = ',';
+const PROFILE_EXCLUDE_PREFIX: char = '-';
+/// The image formats arXiv accepts for documents compiled with pdfLaTeX
+const SUBMISSION_IMAGE_EXTENSIONS: [&str; 4] = ["pdf", "png", "jpg", "jpeg"];
+const BBL_EXTENSION: &str = "bbl";
+/// Extensions tried by `\includegraphics` if the path of an image has none
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "gif", "svg"];
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// All project files as a ZIP archive
+    #[default]
+    Zip,
+    /// The document as a single HTML page
+    Html,
+    /// The document or a part of it as Markdown
+    Markdown,
+    /// A part of the document as a compilable ZIP archive of its own
+    Standalone,
+    /// The document flattened into a single file as expected by arXiv and most journals
+    Submission,
+    /// A submission without any data about the authors for double-blind review
+    Anonymized,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExportRequest {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// The Uuid of the node whose subtree should be exported (if the format supports this)
+    #[serde(default)]
+    pub target: Option<u64>,
+    /// The name of the export profile whose node filter replaces the one of the options
+    #[serde(default)]
+    pub profile: Option<String>,
+    #[serde(flatten)]
+    pub options: StringificationOptions,
+}
+
+/// A named selection of variants of the document, e.g. `short=short,-appendix` includes only the
+/// nodes tagged as `short` (or untagged nodes) and excludes those tagged as `appendix`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ExportProfile {
+    pub name: String,
+    pub node_filter: NodeFilter,
+}
+
+impl FromStr for ExportProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, variants) = s
+            .split_once(PROFILE_NAME_SEPARATOR)
+            .ok_or(format!("expected '<name>=<variants>', found '{s}'"))?;
+        let mut node_filter = NodeFilter::default();
+        for variant in variants.split(PROFILE_VARIANT_SEPARATOR).map(str::trim) {
+            match variant.strip_prefix(PROFILE_EXCLUDE_PREFIX) {
+                Some(excluded) => node_filter.exclude_variants.push(excluded.to_string()),
+                None if !variant.is_empty() => {
+                    node_filter.include_variants.push(variant.to_string())
+                }
+                None => {}
+            }
+        }
+        Ok(Self {
+            name: name.trim().to_string(),
+            node_filter,
+        })
+    }
+}
 
 pub trait ExportManager {
-    fn zip_files(&mut self) -> Result<String, InfrastructureError>;
+    /// `documents` are the LaTeX files of the document as they should be exported, which replace
+    /// the files in the project directory.
+    fn zip_files(&mut self, documents: &[(PathBuf, String)])
+        -> Result<String, InfrastructureError>;
+    fn export_html(&mut self, ast: &TexlaAst) -> Result<String, InfrastructureError>;
+    fn export_markdown(
+        &mut self,
+        ast: &TexlaAst,
+        target: Option<u64>,
+    ) -> Result<String, InfrastructureError>;
+    fn export_standalone(
+        &mut self,
+        ast: &TexlaAst,
+        target: u64,
+        options: &StringificationOptions,
+    ) -> Result<String, InfrastructureError>;
+    fn export_submission(
+        &mut self,
+        ast: &TexlaAst,
+        node_filter: &NodeFilter,
+    ) -> Result<String, InfrastructureError>;
+    /// Like a submission, but without any data revealing the authors, which is listed in the
+    /// report instead.
+    fn export_anonymized(
+        &mut self,
+        ast: &TexlaAst,
+        node_filter: &NodeFilter,
+    ) -> Result<(String, AnonymizationReport), InfrastructureError>;
+    fn export_profiles(&self) -> &[ExportProfile];
+    /// Removes the prepared download with the given token, so that it can only be fetched once.
+    fn take_download(&mut self, token: &str) -> Option<Download>;
+}
+
+/// An export written to the download directory, waiting to be downloaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    pub path: PathBuf,
+    /// The name the file is offered with
+    pub file_name: String,
+    pub created: SystemTime,
 }
 
 pub struct TexlaExportManager {
     main_file_directory: PathBuf,
+    /// Patterns of files which are not part of ZIP exports (in addition to the build artifacts)
+    export_excludes: Vec<String>,
+    export_profiles: Vec<ExportProfile>,
+    downloads: HashMap<String, Download>,
 }
 
 impl TexlaExportManager {
-    pub fn new(main_file_directory: PathBuf) -> Self {
+    pub fn new(
+        main_file_directory: PathBuf,
+        export_excludes: Vec<String>,
+        export_profiles: Vec<ExportProfile>,
+    ) -> Self {
         Self {
             main_file_directory,
+            export_excludes,
+            export_profiles,
+            downloads: HashMap::new(),
+        }
+    }
+
+    /// Creates an empty directory for an export in the TeXLa directory (which is not part of ZIP
+    /// exports).
+    fn create_export_directory(
+        &self,
+        format_directory: &str,
+    ) -> Result<PathBuf, InfrastructureError> {
+        let directory = texla_directory(&self.main_file_directory)?
+            .join(EXPORT_DIRECTORY)
+            .join(format_directory);
+        if directory.exists() {
+            fs::remove_dir_all(&directory)?;
+        }
+        fs::create_dir_all(&directory)?;
+        Ok(directory)
+    }
+
+    /// Copies an image of the document into `directory` and returns its path relative to it.
+    fn copy_image(&self, path: &str, directory: &Path) -> String {
+        let source = match self.find_image(path) {
+            Some(source) => source,
+            None => return String::new(),
+        };
+        let relative = archive_path(&source);
+        let destination = directory.join(&relative);
+
+        let copied = destination
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::copy(self.main_file_directory.join(&source), &destination));
+        if let Err(err) = copied {
+            println!("Could not copy image '{path}': {err}");
+        }
+
+        relative
+    }
+
+    /// Returns the bibliography compiled by the last build (or by the user in the project
+    /// directory).
+    fn find_bbl(&self) -> Option<PathBuf> {
+        let build_directory = self
+            .main_file_directory
+            .join(TEXLA_DIRECTORY)
+            .join(BUILD_DIRECTORY);
+        [build_directory, self.main_file_directory.clone()]
+            .iter()
+            .filter_map(|directory| fs::read_dir(directory).ok())
+            .flat_map(|entries| {
+                let mut paths: Vec<PathBuf> = entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == BBL_EXTENSION))
+                    .collect();
+                paths.sort();
+                paths
+            })
+            .next()
+    }
+
+    /// Creates the directory for downloads in the TeXLa directory, which only the current user can
+    /// access.
+    fn create_download_directory(&self) -> Result<PathBuf, InfrastructureError> {
+        let directory = texla_directory(&self.main_file_directory)?.join(DOWNLOAD_DIRECTORY);
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&directory)?;
+        Ok(directory)
+    }
+
+    /// Reserves a file in the download directory for a download with the given name and returns
+    /// its one-time URL together with the path the export has to be written to.
+    fn create_download(
+        &mut self,
+        file_name: &str,
+    ) -> Result<(String, PathBuf), InfrastructureError> {
+        let directory = self.create_download_directory()?;
+        self.remove_expired_downloads();
+
+        let token = download_token()?;
+        let path = directory.join(format!("{token}-{file_name}"));
+        self.downloads.insert(
+            token.clone(),
+            Download {
+                path: path.clone(),
+                file_name: file_name.to_string(),
+                created: SystemTime::now(),
+            },
+        );
+        Ok((format!("{DOWNLOAD_URL}/{token}"), path))
+    }
+
+    /// Deletes the downloads which have not been fetched in time.
+    fn remove_expired_downloads(&mut self) {
+        self.downloads.retain(|_, download| {
+            let expired = is_expired(download.created);
+            if expired {
+                fs::remove_file(&download.path).ok();
+            }
+            !expired
+        });
+    }
+
+    /// Returns the paths (relative to the project directory) of all files which are part of a ZIP
+    /// export: files ignored by git, hidden files and files matching an exclude pattern are left
+    /// out.
+    fn project_files(&self) -> Vec<PathBuf> {
+        let git_files = Command::new("git")
+            .args(GIT_LIST_FILES)
+            .current_dir(&self.main_file_directory)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .split('\0')
+                    .filter(|path| !path.is_empty())
+                    .map(PathBuf::from)
+                    .collect::<Vec<_>>()
+            });
+        // without git (or outside of a repository) all files are considered
+        let files = git_files.unwrap_or_else(|| {
+            walkdir::WalkDir::new(&self.main_file_directory)
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter_map(|entry| {
+                    let path = entry.path().strip_prefix(&self.main_file_directory).ok()?;
+                    Some(path.to_path_buf())
+                })
+                .collect()
+        });
+
+        let mut files: Vec<PathBuf> = files
+            .into_iter()
+            // git still lists tracked files which have been deleted
+            .filter(|path| self.main_file_directory.join(path).is_file())
+            .filter(|path| {
+                let name = archive_path(path);
+                !name
+                    .split(LATEX_PATH_SEPARATOR)
+                    .any(|part| part.starts_with('.'))
+                    && !self.is_excluded(&name)
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Returns the path relative to the project directory of a file inside of it.
+    fn relative_path(&self, path: &Path) -> Option<PathBuf> {
+        if let Ok(relative) = path.strip_prefix(&self.main_file_directory) {
+            return Some(relative.to_path_buf());
+        }
+        // the storage manager uses canonical paths for included files
+        let directory = self.main_file_directory.canonicalize().ok()?;
+        path.strip_prefix(directory).ok().map(Path::to_path_buf)
+    }
+
+    /// Patterns with a `/` are matched against the path from the project directory on (or one of
+    /// its parent directories), all others against each file and directory name.
+    fn is_excluded(&self, path: &str) -> bool {
+        DEFAULT_EXPORT_EXCLUDES
+            .iter()
+            .copied()
+            .chain(self.export_excludes.iter().map(String::as_str))
+            .any(|pattern| {
+                let pattern = pattern.trim_matches('/');
+                if pattern.contains('/') || pattern.is_empty() {
+                    let mut prefix = String::new();
+                    path.split('/').any(|part| {
+                        if !prefix.is_empty() {
+                            prefix += "/";
+                        }
+                        prefix += part;
+                        glob_match(pattern, &prefix)
+                    })
+                } else {
+                    path.split('/').any(|part| glob_match(pattern, part))
+                }
+            })
+    }
+
+    /// Writes a flattened document with its images and compiled bibliography into a ZIP archive,
+    /// unless arXiv would reject it.
+    fn submission_zip(
+        &mut self,
+        file_name: &str,
+        latex: String,
+        mut images: Vec<(String, Option<PathBuf>)>,
+    ) -> Result<String, InfrastructureError> {
+        let mut files = vec![(SUBMISSION_MAIN_FILE.to_string(), latex.into_bytes())];
+        let mut problems = vec![];
+        images.sort();
+        images.dedup();
+        for (name, source) in images {
+            let source = match source {
+                Some(source) => source,
+                None => {
+                    problems.push(format!(
+                        "image '{name}' was skipped, since it is outside of the project directory"
+                    ));
+                    continue;
+                }
+            };
+            match fs::read(self.main_file_directory.join(&source)) {
+                Ok(content) => files.push((name, content)),
+                Err(err) => problems.push(format!("image '{name}' cannot be read ({err})")),
+            }
         }
+        // arXiv does not run BibTeX, so the bibliography must be compiled beforehand
+        if let Some(bbl) = self.find_bbl() {
+            files.push((SUBMISSION_BBL_FILE.to_string(), fs::read(bbl)?));
+        }
+
+        problems.extend(
+            files
+                .iter()
+                .filter_map(|(name, _)| submission_problem(name)),
+        );
+        if !problems.is_empty() {
+            return Err(InfrastructureError::from(ExportZipError {
+                message: format!("The submission would be rejected: {}", problems.join("; ")),
+            }));
+        }
+
+        let (url, path) = self.create_download(file_name)?;
+        write_zip(&path, &files)?;
+        Ok(url)
+    }
+
+    /// Returns the path of an image relative to the project directory (the path in the LaTeX code
+    /// may omit the extension). Images outside the project directory are skipped, so that exports
+    /// cannot contain arbitrary files of the system.
+    fn find_image(&self, path: &str) -> Option<PathBuf> {
+        if !is_inside_project(path) {
+            println!("Skipped image '{path}', since it is outside of the project directory");
+            return None;
+        }
+        let path = PathBuf::from(path);
+        if path.extension().is_none() {
+            for extension in IMAGE_EXTENSIONS {
+                let candidate = path.with_extension(extension);
+                if self.main_file_directory.join(&candidate).is_file() {
+                    return Some(candidate);
+                }
+            }
+        }
+        Some(path)
     }
 }
 
 impl ExportManager for TexlaExportManager {
-    fn zip_files(&mut self) -> Result<String, InfrastructureError> {
-        let file = File::create(self.main_file_directory.join("export.zip"))?;
+    fn zip_files(
+        &mut self,
+        documents: &[(PathBuf, String)],
+    ) -> Result<String, InfrastructureError> {
+        let documents: HashMap<PathBuf, &str> = documents
+            .iter()
+            .filter_map(|(path, content)| Some((self.relative_path(path)?, content.as_str())))
+            .collect();
+        let mut files = self.project_files();
+        // files just created by an operation may not have been saved yet
+        files.extend(
+            documents
+                .keys()
+                .filter(|path| !files.contains(path) && !self.is_excluded(&archive_path(path)))
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        let (url, path) = self.create_download(ZIP_FILE)?;
 
         let option = FileOptions::default()
             .compression_method(Deflated) // default zip method.
             .unix_permissions(0o755); // shouldn't cause any errors in windows, should work on linux and mac.
 
-        let mut zip = zip::ZipWriter::new(file);
+        let mut zip = zip::ZipWriter::new(File::create(path)?);
 
-        let walkdir = walkdir::WalkDir::new(self.main_file_directory.clone()).into_iter();
+        for relative_path in files {
+            zip.start_file(archive_path(&relative_path), option)?;
+            match documents.get(&relative_path) {
+                Some(content) => zip.write_all(content.as_bytes())?,
+                None => {
+                    // copied in chunks, so that large images need not fit into memory
+                    let mut file = File::open(self.main_file_directory.join(&relative_path))?;
+                    io::copy(&mut file, &mut zip)?;
+                }
+            }
+        }
 
-        for entry in walkdir {
-            let entry = entry.expect("walkdir gave error");
-            let path = entry.path();
-            if path.is_file() {
-                let name = path
-                    .strip_prefix(&self.main_file_directory)
-                    .expect("walkdir gave file outside main_file_directory")
-                    .to_str()
-                    .expect("found non-utf8 file name");
+        zip.finish()?;
 
-                //skip hidden files
-                if name.split('/').any(|part| part.starts_with('.')) {
-                    continue;
-                }
+        Ok(url)
+    }
 
-                if name.ends_with(".zip") {
-                    continue;
-                }
+    fn export_html(&mut self, ast: &TexlaAst) -> Result<String, InfrastructureError> {
+        let directory = self.create_export_directory(HTML_DIRECTORY)?;
+        let html = to_html(ast, &|path| self.copy_image(path, &directory));
+        fs::write(directory.join(HTML_FILE), html)?;
 
-                if !name.starts_with('.') && !name.ends_with('~') {
-                    let mut file = File::open(path)?;
-                    zip.start_file(name, option)?;
+        Ok(format!(
+            "/user-assets/{TEXLA_DIRECTORY}/{EXPORT_DIRECTORY}/{HTML_DIRECTORY}/{HTML_FILE}"
+        ))
+    }
 
-                    let mut buffer = Vec::new(); // could be problematic if files are too big.
-                    file.read_to_end(&mut buffer)?;
-                    zip.write_all(&buffer)?;
-                }
+    fn export_markdown(
+        &mut self,
+        ast: &TexlaAst,
+        target: Option<u64>,
+    ) -> Result<String, InfrastructureError> {
+        let directory = self.create_export_directory(MARKDOWN_DIRECTORY)?;
+        let markdown = to_markdown(ast, target, &|path| self.copy_image(path, &directory))?;
+        fs::write(directory.join(MARKDOWN_FILE), markdown)?;
+
+        Ok(format!(
+            "/user-assets/{TEXLA_DIRECTORY}/{EXPORT_DIRECTORY}/{MARKDOWN_DIRECTORY}/{MARKDOWN_FILE}"
+        ))
+    }
+
+    fn export_standalone(
+        &mut self,
+        ast: &TexlaAst,
+        target: u64,
+        options: &StringificationOptions,
+    ) -> Result<String, InfrastructureError> {
+        let standalone = to_standalone(ast, target, options)?;
+        let mut files = vec![(
+            STANDALONE_MAIN_FILE.to_string(),
+            standalone.latex.into_bytes(),
+        )];
+
+        for image in &standalone.images {
+            let path = match self.find_image(image) {
+                Some(path) => path,
+                None => continue,
+            };
+            match fs::read(self.main_file_directory.join(&path)) {
+                Ok(content) => files.push((archive_path(&path), content)),
+                Err(err) => println!("Could not export image '{image}': {err}"),
+            }
+        }
+        for bibliography in &standalone.bibliographies {
+            if !is_inside_project(bibliography) {
+                println!(
+                    "Skipped bibliography '{bibliography}', since it is outside of the project directory"
+                );
+                continue;
+            }
+            match fs::read_to_string(self.main_file_directory.join(bibliography)) {
+                Ok(content) => files.push((
+                    bibliography.clone(),
+                    filter_bib_entries(&content, &standalone.citations).into_bytes(),
+                )),
+                Err(err) => println!("Could not export bibliography '{bibliography}': {err}"),
             }
         }
 
-        Ok("/user-assets/export.zip".to_string())
+        let (url, path) = self.create_download(STANDALONE_ZIP_FILE)?;
+        write_zip(&path, &files)?;
+        Ok(url)
+    }
+
+    fn export_submission(
+        &mut self,
+        ast: &TexlaAst,
+        node_filter: &NodeFilter,
+    ) -> Result<String, InfrastructureError> {
+        let mut images = vec![];
+        let latex = to_submission(ast, node_filter, &mut |path| {
+            let source = self.find_image(path);
+            let name = source.as_deref().map_or(path.to_string(), archive_path);
+            images.push((name.clone(), source));
+            name
+        })?;
+
+        self.submission_zip(SUBMISSION_ZIP_FILE, latex, images)
     }
+
+    fn export_anonymized(
+        &mut self,
+        ast: &TexlaAst,
+        node_filter: &NodeFilter,
+    ) -> Result<(String, AnonymizationReport), InfrastructureError> {
+        let mut images = vec![];
+        let anonymized = to_anonymized(ast, node_filter, &mut |path| {
+            let source = self.find_image(path);
+            let name = source.as_deref().map_or(path.to_string(), archive_path);
+            images.push((name.clone(), source));
+            name
+        })?;
+
+        let url = self.submission_zip(ANONYMIZED_ZIP_FILE, anonymized.latex, images)?;
+        Ok((url, anonymized.report))
+    }
+
+    fn export_profiles(&self) -> &[ExportProfile] {
+        &self.export_profiles
+    }
+
+    fn take_download(&mut self, token: &str) -> Option<Download> {
+        let download = self.downloads.remove(token)?;
+        if is_expired(download.created) {
+            fs::remove_file(&download.path).ok();
+            return None;
+        }
+        Some(download)
+    }
+}
+
+/// Whether the path (relative to the main file directory) is part of the HTML export, which is
+/// the only generated web page.
+pub fn is_html_export(path: &str) -> bool {
+    path.starts_with(&format!(
+        "{TEXLA_DIRECTORY}/{EXPORT_DIRECTORY}/{HTML_DIRECTORY}/"
+    ))
+}
+
+/// Creates a token for a download URL which other websites cannot guess.
+fn download_token() -> Result<String, InfrastructureError> {
+    let mut bytes = [0u8; DOWNLOAD_TOKEN_LENGTH];
+    getrandom::getrandom(&mut bytes).map_err(|err| ExportZipError {
+        message: format!("Could not create a download token: {err}"),
+    })?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+fn is_expired(created: SystemTime) -> bool {
+    created.elapsed().is_ok_and(|age| age > DOWNLOAD_LIFETIME)
+}
+
+/// Matches `text` against a pattern in which `*` stands for any number of characters (except `/`)
+/// and `?` for a single one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(pattern: &[char], text: &[char]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some(('*', rest)) => (0..=text.len())
+                .take_while(|skipped| *skipped == 0 || text[skipped - 1] != '/')
+                .any(|skipped| matches(rest, &text[skipped..])),
+            Some(('?', rest)) => {
+                text.first().is_some_and(|c| *c != '/') && matches(rest, &text[1..])
+            }
+            Some((c, rest)) => text.first() == Some(c) && matches(rest, &text[1..]),
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches(&pattern, &text)
+}
+
+/// Converts a path relative to the project directory into one inside of an archive.
+fn archive_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(LATEX_PATH_SEPARATOR)
+}
+
+fn write_zip(path: &Path, files: &[(String, Vec<u8>)]) -> Result<(), InfrastructureError> {
+    let option = FileOptions::default().compression_method(Deflated);
+    let mut zip = zip::ZipWriter::new(File::create(path)?);
+    for (name, content) in files {
+        zip.start_file(name, option)?;
+        zip.write_all(content)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Returns why arXiv would reject a file with the given name, if it would.
+fn submission_problem(name: &str) -> Option<String> {
+    let is_allowed = |char: char| char.is_ascii_alphanumeric() || "_+-.,=/".contains(char);
+    if !name.chars().all(is_allowed) {
+        return Some(format!(
+            "'{name}' contains characters other than a-z, A-Z, 0-9 and _+-.,="
+        ));
+    }
+    let extension = Path::new(name)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if name != SUBMISSION_MAIN_FILE
+        && name != SUBMISSION_BBL_FILE
+        && !SUBMISSION_IMAGE_EXTENSIONS.contains(&extension.as_str())
+    {
+        return Some(format!(
+            "'{name}' is no image pdfLaTeX can include ({})",
+            SUBMISSION_IMAGE_EXTENSIONS.join(", ")
+        ));
+    }
+    None
+}
+
+/// Keeps only the entries of a BibTeX file with the given keys (and the string definitions and
+/// preambles the entries may need).
+fn filter_bib_entries(bib: &str, keys: &[String]) -> String {
+    let mut filtered = String::new();
+    let mut rest = bib;
+    while let Some(start) = rest.find('@') {
+        rest = &rest[start..];
+        let Some(open) = rest.find(['{', '(']) else {
+            break;
+        };
+        let kind = rest[1..open].trim().to_lowercase();
+        // entries end at the delimiter matching the opening one, brackets inside of field values
+        // (in braces or quotes) do not count
+        let close = if rest[open..].starts_with('(') {
+            ')'
+        } else {
+            '}'
+        };
+        let mut depth = 0;
+        let mut in_quotes = false;
+        let end = rest[open + 1..]
+            .char_indices()
+            .find_map(|(index, char)| {
+                match char {
+                    '"' if depth == 0 => in_quotes = !in_quotes,
+                    '{' if !in_quotes => depth += 1,
+                    '}' if !in_quotes && depth > 0 => depth -= 1,
+                    _ if char == close && !in_quotes && depth == 0 => {
+                        return Some(open + 1 + index + 1);
+                    }
+                    _ => {}
+                }
+                None
+            })
+            .unwrap_or(rest.len());
+        let entry = &rest[..end];
+        let key = entry[open + 1..]
+            .split(',')
+            .next()
+            .unwrap_or_default()
+            .trim();
+
+        let is_needed = match kind.as_str() {
+            "string" | "preamble" => true,
+            "comment" => false,
+            _ => keys.iter().any(|cited| cited == key),
+        };
+        if is_needed {
+            filtered += entry;
+            filtered += "\n\n";
+        }
+        rest = &rest[end..];
+    }
+    filtered
 }
 
 // export.zip in test_resources/latex/pflichtenheft is irrelevant
@@ -74,12 +765,27 @@ impl ExportManager for TexlaExportManager {
 mod tests {
     use std::collections::HashSet;
     use std::fs;
-    use std::path::Path;
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
 
     use zip::ZipArchive;
 
-    use crate::infrastructure::export_manager::{ExportManager, TexlaExportManager};
-    use crate::infrastructure::file_path::FilePath;
+    use ast::texla_ast::TexlaAst;
+    use ast::Ast;
+
+    use crate::infrastructure::export_manager::{
+        filter_bib_entries, glob_match, Download, ExportManager, ExportProfile, TexlaExportManager,
+        DOWNLOAD_URL,
+    };
+    use crate::infrastructure::file_path::{FilePath, TEXLA_DIRECTORY};
+
+    fn take_download(manager: &mut TexlaExportManager, url: &str) -> Download {
+        let token = url
+            .strip_prefix(&format!("{DOWNLOAD_URL}/"))
+            .expect("The export is served as a download");
+        manager.take_download(token).unwrap()
+    }
 
     #[test]
     fn test_zip_files() {
@@ -93,16 +799,17 @@ mod tests {
 
         fs::create_dir(path_to_new_test_directory).unwrap();
 
-        let created_zip_path = "test_resources/latex/pflichtenheft/export.zip";
         let copied_zip_path = "test_resources/latex/pflichtenheft_zip/export_copy.zip";
         let main_file = FilePath::from("test_resources/latex/pflichtenheft/main.tex");
 
         // create zip of test_resources/latex/pflichtenheft
-        let mut manager = TexlaExportManager::new(main_file.directory);
-        manager.zip_files().unwrap();
+        let mut manager = TexlaExportManager::new(main_file.directory, vec![], vec![]);
+        let url = manager.zip_files(&[]).unwrap();
+        let download = take_download(&mut manager, &url);
 
-        // copy zip created by zip_files() function to pflichtenheft_zip directory
-        fs::copy(created_zip_path, copied_zip_path).unwrap();
+        // move zip created by zip_files() function to pflichtenheft_zip directory
+        fs::copy(&download.path, copied_zip_path).unwrap();
+        fs::remove_file(download.path).unwrap();
 
         // unpack and delete zip
         let mut copied_zip = ZipArchive::new(fs::File::open(copied_zip_path).unwrap()).unwrap();
@@ -124,9 +831,8 @@ mod tests {
                     .unwrap()
                     .to_string()
             })
-            .filter(|name| name != "export.zip")
-            // ignore default save path for export.zip which is only used to redirect result zip to
-            // frontend and should not be compared
+            // the downloads are prepared in the TeXLa directory, which is not exported
+            .filter(|name| name != TEXLA_DIRECTORY)
             .collect();
 
         let unzipped_files: HashSet<_> = unzipped_dir
@@ -147,4 +853,297 @@ mod tests {
         // delete pflichtenheft_zip directory
         fs::remove_dir_all(path_to_new_test_directory).unwrap();
     }
+
+    #[test]
+    fn test_zip_files_excludes() {
+        let directory = Path::new("test_resources/latex/zip_excludes");
+        fs::remove_dir_all(directory).ok();
+        fs::create_dir_all(directory.join("drafts/old")).unwrap();
+        fs::create_dir_all(directory.join("figures")).unwrap();
+        fs::write(directory.join("main.tex"), "\\begin{document}").unwrap();
+        fs::write(directory.join("main.aux"), "").unwrap();
+        fs::write(directory.join("main.synctex.gz"), "").unwrap();
+        fs::write(directory.join("main.tex~"), "").unwrap();
+        fs::write(directory.join("drafts/notes.tex"), "").unwrap();
+        fs::write(directory.join("drafts/old/notes.tex"), "").unwrap();
+        fs::write(directory.join("figures/plot.pdf"), "").unwrap();
+        fs::write(directory.join("figures/plot.svg"), "").unwrap();
+        fs::write(directory.join("generated.pdf"), "").unwrap();
+        fs::write(directory.join(".gitignore"), "generated.pdf\n").unwrap();
+
+        let mut manager = TexlaExportManager::new(
+            directory.to_path_buf(),
+            vec!["drafts/old".to_string(), "*.svg".to_string()],
+            vec![],
+        );
+        let documents = vec![
+            (
+                directory.join("main.tex"),
+                "\\begin{document}\n".to_string(),
+            ),
+            // not saved yet
+            (directory.join("chapters/new.tex"), "New".to_string()),
+        ];
+        let url = manager.zip_files(&documents).unwrap();
+        let download = take_download(&mut manager, &url);
+        assert_eq!(download.file_name, "export.zip");
+
+        let mut zip = ZipArchive::new(fs::File::open(&download.path).unwrap()).unwrap();
+        let mut names: Vec<&str> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "chapters/new.tex",
+                "drafts/notes.tex",
+                "figures/plot.pdf",
+                "main.tex"
+            ]
+        );
+        let mut latex = String::new();
+        zip.by_name("main.tex")
+            .unwrap()
+            .read_to_string(&mut latex)
+            .unwrap();
+        assert_eq!(latex, "\\begin{document}\n");
+        // the working files are not modified
+        assert_eq!(
+            fs::read_to_string(directory.join("main.tex")).unwrap(),
+            "\\begin{document}"
+        );
+        assert!(!directory.join("chapters").exists());
+
+        // the download can only be taken once
+        let token = url.rsplit('/').next().unwrap();
+        assert!(manager.take_download(token).is_none());
+        fs::remove_file(download.path).unwrap();
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn expire_downloads() {
+        let mut manager = TexlaExportManager::new(PathBuf::from("test_resources"), vec![], vec![]);
+        let (url, path) = manager.create_download("old.zip").unwrap();
+        fs::write(&path, "").unwrap();
+        let token = url.rsplit('/').next().unwrap().to_string();
+        assert_eq!(token.len(), 32);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let directory = fs::metadata(path.parent().unwrap()).unwrap();
+            assert_eq!(directory.permissions().mode() & 0o777, 0o700);
+        }
+        manager.downloads.get_mut(&token).unwrap().created -= Duration::from_secs(2 * 60 * 60);
+
+        let (other_url, other_path) = manager.create_download("new.zip").unwrap();
+        assert_ne!(url, other_url);
+        assert!(!manager.downloads.contains_key(&token));
+        assert!(!path.exists());
+        assert_eq!(take_download(&mut manager, &other_url).path, other_path);
+    }
+
+    #[test]
+    fn parse_export_profile() {
+        let profile: ExportProfile = "short = short, -appendix".parse().unwrap();
+        assert_eq!(profile.name, "short");
+        assert_eq!(profile.node_filter.include_variants, vec!["short"]);
+        assert_eq!(profile.node_filter.exclude_variants, vec!["appendix"]);
+        assert!("short".parse::<ExportProfile>().is_err());
+    }
+
+    #[test]
+    fn match_glob_patterns() {
+        assert!(glob_match("*.aux", "main.aux"));
+        assert!(glob_match("ch?pter", "chapter"));
+        assert!(!glob_match("*.aux", "main.tex"));
+        assert!(!glob_match("drafts/*", "drafts/old/notes.tex"));
+        assert!(glob_match("drafts/*/*", "drafts/old/notes.tex"));
+    }
+
+    #[test]
+    fn filter_bib_entries_with_brackets_in_fields() {
+        let bib = "@article{smile, title = {Smile :)}}\n\
+                   @book(paren, note = {(see p. 3}, title = \"A {B} (c\")\n\
+                   @misc{other, title = \"x)\" }\n\
+                   @misc{cited, title = {Cited}}\n";
+        let keys = ["smile", "paren", "cited"].map(String::from);
+
+        let filtered = filter_bib_entries(bib, &keys);
+        assert_eq!(
+            filtered,
+            "@article{smile, title = {Smile :)}}\n\n\
+             @book(paren, note = {(see p. 3}, title = \"A {B} (c\")\n\n\
+             @misc{cited, title = {Cited}}\n\n"
+        );
+    }
+
+    #[test]
+    fn test_export_html() {
+        let directory = Path::new("test_resources/latex/html_export");
+        fs::remove_dir_all(directory).ok();
+        fs::create_dir_all(directory.join("images")).unwrap();
+        fs::copy(
+            "test_resources/latex/image.png",
+            directory.join("images/image.png"),
+        )
+        .unwrap();
+
+        let ast = TexlaAst::from_latex(
+            "\\begin{document}\n\\section{Pictures}\n\\includegraphics{images/image}\n\\includegraphics{../image}\n\\end{document}\n"
+                .to_string(),
+        )
+        .unwrap();
+        let mut manager = TexlaExportManager::new(directory.to_path_buf(), vec![], vec![]);
+        let url = manager.export_html(&ast).unwrap();
+
+        assert_eq!(url, "/user-assets/.texla/export/html/index.html");
+        let export_directory = directory.join(".texla/export/html");
+        let html = fs::read_to_string(export_directory.join("index.html")).unwrap();
+        assert!(html.contains("<img src=\"images/image.png\""));
+        assert!(export_directory.join("images/image.png").is_file());
+        // images outside of the project directory are skipped
+        assert!(!export_directory.join("image.png").exists());
+        assert!(!html.contains("src=\"image.png\""));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_export_standalone() {
+        let directory = Path::new("test_resources/latex/standalone_export");
+        fs::remove_dir_all(directory).ok();
+        fs::create_dir_all(directory.join("images")).unwrap();
+        fs::copy(
+            "test_resources/latex/image.png",
+            directory.join("images/image.png"),
+        )
+        .unwrap();
+        fs::write(
+            directory.join("refs.bib"),
+            "@string{tug = \"TeX Users Group\"}\n@book{knuth84, title = {The {\\TeX}book}}\n@article{lamport94, publisher = tug}\n",
+        )
+        .unwrap();
+
+        let ast = TexlaAst::from_latex(
+            "\\begin{document}\n\\section{Pictures}\n\\includegraphics{images/image}\nSee \\cite{knuth84}.\n\n\\section{Other}\n\\cite{lamport94}\n\n\\bibliography{refs}\n\\end{document}\n"
+                .to_string(),
+        )
+        .unwrap();
+        // the first section
+        let uuid = ast.node_at_path(&[0]).unwrap();
+        let mut manager = TexlaExportManager::new(directory.to_path_buf(), vec![], vec![]);
+        let url = manager
+            .export_standalone(&ast, uuid, &Default::default())
+            .unwrap();
+
+        let download = take_download(&mut manager, &url);
+        assert_eq!(download.file_name, "standalone.zip");
+        let mut zip = ZipArchive::new(fs::File::open(&download.path).unwrap()).unwrap();
+        let mut names: Vec<&str> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec!["images/image.png", "main.tex", "refs.bib"]);
+        let mut bib = String::new();
+        zip.by_name("refs.bib")
+            .unwrap()
+            .read_to_string(&mut bib)
+            .unwrap();
+        assert_eq!(
+            bib,
+            "@string{tug = \"TeX Users Group\"}\n\n@book{knuth84, title = {The {\\TeX}book}}\n\n"
+        );
+
+        fs::remove_file(download.path).unwrap();
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_export_anonymized() {
+        let directory = Path::new("test_resources/latex/anonymized_export");
+        fs::remove_dir_all(directory).ok();
+        fs::create_dir_all(directory).unwrap();
+        let mut manager = TexlaExportManager::new(directory.to_path_buf(), vec![], vec![]);
+
+        let ast = TexlaAst::from_latex(
+            "\\author{Jane Doe}\n\\begin{document}\nText\n\n\\section*{Acknowledgments}\nThanks\n\n\\end{document}\n"
+                .to_string(),
+        )
+        .unwrap();
+        let (url, report) = manager
+            .export_anonymized(&ast, &Default::default())
+            .unwrap();
+
+        assert_eq!(report.author_data, vec!["\\author{Jane Doe}"]);
+        assert_eq!(report.nodes[0].summary, "Acknowledgments");
+        let download = take_download(&mut manager, &url);
+        assert_eq!(download.file_name, "anonymized.zip");
+        let mut zip = ZipArchive::new(fs::File::open(&download.path).unwrap()).unwrap();
+        let mut latex = String::new();
+        zip.by_name("main.tex")
+            .unwrap()
+            .read_to_string(&mut latex)
+            .unwrap();
+        assert_eq!(
+            latex,
+            "\\author{Anonymous}\n\\begin{document}\nText\n\n\\end{document}\n"
+        );
+
+        fs::remove_file(download.path).unwrap();
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_export_submission() {
+        let directory = Path::new("test_resources/latex/submission_export");
+        fs::remove_dir_all(directory).ok();
+        fs::create_dir_all(directory.join("images")).unwrap();
+        fs::copy(
+            "test_resources/latex/image.png",
+            directory.join("images/image.png"),
+        )
+        .unwrap();
+        fs::write(directory.join("images/animation.gif"), "GIF89a").unwrap();
+        fs::write(directory.join("paper.bbl"), "\\begin{thebibliography}{1}\n").unwrap();
+        fs::write(directory.join("build.log"), "junk").unwrap();
+        let mut manager = TexlaExportManager::new(directory.to_path_buf(), vec![], vec![]);
+
+        let ast = TexlaAst::from_latex(
+            "\\begin{document}\n% a comment\n\\includegraphics{./images/image}\n\\end{document}\n"
+                .to_string(),
+        )
+        .unwrap();
+        let url = manager
+            .export_submission(&ast, &Default::default())
+            .unwrap();
+
+        let download = take_download(&mut manager, &url);
+        let mut zip = ZipArchive::new(fs::File::open(&download.path).unwrap()).unwrap();
+        let mut names: Vec<&str> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec!["images/image.png", "main.bbl", "main.tex"]);
+        let mut latex = String::new();
+        zip.by_name("main.tex")
+            .unwrap()
+            .read_to_string(&mut latex)
+            .unwrap();
+        assert_eq!(
+            latex,
+            "\\begin{document}\n\\includegraphics{images/image.png}\n\\end{document}\n"
+        );
+
+        for image in ["images/animation.gif", "../image.png"] {
+            let ast = TexlaAst::from_latex(format!(
+                "\\begin{{document}}\n\\includegraphics{{{image}}}\n\\end{{document}}\n"
+            ))
+            .unwrap();
+            assert!(
+                manager
+                    .export_submission(&ast, &Default::default())
+                    .is_err(),
+                "{image}"
+            );
+        }
+
+        fs::remove_file(download.path).unwrap();
+        fs::remove_dir_all(directory).unwrap();
+    }
 }
@@ -1,7 +1,23 @@
-use std::path::{PathBuf, MAIN_SEPARATOR_STR};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR_STR};
 
 const PATH_SEPARATORS: [char; 2] = ['/', '\\'];
 
+/// The directory inside the project directory where TeXLa keeps its own files (e.g. the journal).
+pub(crate) const TEXLA_DIRECTORY: &str = ".texla";
+
+/// Returns the TeXLa directory of the given project directory and creates it if necessary.
+/// The directory ignores itself, so that it is never committed.
+pub(crate) fn texla_directory(project_directory: &Path) -> io::Result<PathBuf> {
+    let directory = project_directory.join(TEXLA_DIRECTORY);
+    if !directory.is_dir() {
+        fs::create_dir_all(&directory)?;
+        fs::write(directory.join(".gitignore"), "*\n")?;
+    }
+    Ok(directory)
+}
+
 pub struct FilePath {
     pub path: PathBuf,
     pub directory: PathBuf,
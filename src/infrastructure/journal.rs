@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use ast::operation::JsonOperation;
+use ast::texla_ast::TexlaAst;
+
+use crate::infrastructure::errors::{InfrastructureError, StorageError};
+use crate::infrastructure::file_path::{texla_directory, TEXLA_DIRECTORY};
+
+const JOURNAL_FILE_NAME: &str = "journal.jsonl";
+/// The journal up to the last commit, which is only kept for debugging
+const PREVIOUS_JOURNAL_FILE_NAME: &str = "journal.previous.jsonl";
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// One line of the journal.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "record")]
+pub enum JournalRecord {
+    Operation(JournalEntry),
+    Committed { timestamp: String, session: String },
+}
+
+/// An operation which was applied to the document.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalEntry {
+    pub timestamp: String,
+    pub session: String,
+    /// Hash of the LaTeX single string the operation was applied to (see [hash_latex])
+    pub before: u64,
+    /// Paths (see `TexlaAst::node_path()`) of all nodes the operation refers to, because Uuids
+    /// change with every parse
+    pub nodes: HashMap<u64, Vec<usize>>,
+    pub operation: JsonOperation,
+}
+
+/// Appends every operation applied by a session to `.texla/journal.jsonl` in the project
+/// directory, so that operations can be recovered after a crash and replayed for debugging.
+pub struct Journal {
+    project_directory: PathBuf,
+    session: String,
+}
+
+impl Journal {
+    pub fn new(project_directory: PathBuf) -> Self {
+        Self {
+            project_directory,
+            session: format!(
+                "{}-{}",
+                Local::now().format("%Y%m%d%H%M%S"),
+                std::process::id()
+            ),
+        }
+    }
+
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.project_directory
+            .join(TEXLA_DIRECTORY)
+            .join(JOURNAL_FILE_NAME)
+    }
+
+    /// Creates the entry for `operation`, which is about to be applied to `ast`.
+    /// `latex_before` has to be the stringification of `ast`.
+    pub fn entry(
+        &self,
+        latex_before: &str,
+        ast: &TexlaAst,
+        operation: &JsonOperation,
+    ) -> JournalEntry {
+        JournalEntry {
+            timestamp: Local::now().to_rfc3339(),
+            session: self.session.clone(),
+            before: hash_latex(latex_before),
+            nodes: operation
+                .uuids()
+                .into_iter()
+                .filter_map(|uuid| Some((uuid, ast.node_path(uuid)?)))
+                .collect(),
+            operation: operation.clone(),
+        }
+    }
+
+    pub fn record_operation(&self, entry: JournalEntry) -> Result<(), InfrastructureError> {
+        self.append(&JournalRecord::Operation(entry))
+    }
+
+    /// Marks all operations so far as committed. Only the operations after the last commit are
+    /// needed for a recovery, so the journal is rotated instead of growing forever.
+    pub fn record_commit(&self) -> Result<(), InfrastructureError> {
+        let path = self.path();
+        if path.exists() {
+            fs::rename(&path, path.with_file_name(PREVIOUS_JOURNAL_FILE_NAME))?;
+        }
+        self.append(&JournalRecord::Committed {
+            timestamp: Local::now().to_rfc3339(),
+            session: self.session.clone(),
+        })
+    }
+
+    fn append(&self, record: &JournalRecord) -> Result<(), InfrastructureError> {
+        texla_directory(&self.project_directory)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path())?;
+        writeln!(file, "{}", serde_json::to_string(record).unwrap())?;
+        Ok(())
+    }
+
+    /// Reads all records of a journal file. A missing file is an empty journal.
+    pub fn read(path: &Path) -> Result<Vec<JournalRecord>, InfrastructureError> {
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        fs::read_to_string(path)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|err| {
+                    InfrastructureError::from(StorageError {
+                        message: format!("Invalid journal entry in line {}: {err}", index + 1),
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+/// Hashes the LaTeX code with line endings normalized. The journal outlives the binary, so this
+/// uses FNV-1a, whose values (unlike the ones of the `DefaultHasher`) never change.
+pub fn hash_latex(latex: &str) -> u64 {
+    latex
+        .replace("\r\n", "\n")
+        .bytes()
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::infrastructure::journal::hash_latex;
+
+    #[test]
+    fn stable_hash() {
+        assert_eq!(hash_latex(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash_latex("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash_latex("a\r\nb"), hash_latex("a\nb"));
+    }
+}
@@ -10,15 +10,22 @@ use tokio::time::sleep;
 use tracing::debug;
 
 use ast::latex_constants::*;
+use ast::merge::{CONFLICT_BEGIN_MARK, CONFLICT_END_MARK, CONFLICT_SEPARATOR_MARK};
+use ast::texla_ast::is_inside_project;
 use ast::texla_constants::*;
 
 use crate::infrastructure::dir_watcher::DirectoryWatcher;
-use crate::infrastructure::errors::InfrastructureError;
+use crate::infrastructure::errors::{InfrastructureError, StorageError};
 use crate::infrastructure::file_path::FilePath;
+use crate::infrastructure::journal::Journal;
 use crate::infrastructure::pull_timer::PullTimerManager;
 use crate::infrastructure::vcs_manager::{GitErrorHandler, GitManager, VcsManager};
 use crate::infrastructure::work_session::WorksessionManager;
 
+const GIT_CONFLICT_BEGIN: &str = "<<<<<<<";
+const GIT_CONFLICT_BASE: &str = "|||||||";
+const GIT_CONFLICT_END: &str = ">>>>>>>";
+
 #[async_trait]
 pub trait StorageManager {
     fn attach_handlers(
@@ -29,12 +36,28 @@ pub trait StorageManager {
     async fn start(this: Arc<Mutex<Self>>) -> Result<(), InfrastructureError>;
     fn remote_url(&self) -> Option<&String>;
     fn multiplex_files(&self) -> Result<String, InfrastructureError>;
+    fn demultiplex_files(&self, latex_single_string: String) -> Vec<(PathBuf, String)>;
     fn wait_for_action(&mut self);
     fn action_aborted(&mut self);
     async fn save(
         this: Arc<Mutex<Self>>,
         latex_single_string: String,
     ) -> Result<(), InfrastructureError>;
+    fn file_exists(&self, latex_path: String) -> bool;
+    fn read_file(&self, latex_path: &str) -> Result<String, InfrastructureError>;
+    async fn save_file(
+        this: Arc<Mutex<Self>>,
+        latex_path: String,
+        content: String,
+    ) -> Result<(), InfrastructureError>;
+    async fn remove_files(
+        this: Arc<Mutex<Self>>,
+        latex_paths: Vec<String>,
+    ) -> Result<(), InfrastructureError>;
+    fn commit_change(&mut self, message: String);
+    fn has_local_changes(&self) -> bool;
+    fn finish_merge(&mut self);
+    fn journal(&self) -> &Journal;
     fn end_worksession(&mut self);
     fn disassemble(&mut self);
 }
@@ -51,6 +74,7 @@ where
     worksession_manager: Option<WorksessionManager>,
     pub(crate) worksession_interval: u64,
     dir_watcher: Option<DirectoryWatcher>,
+    journal: Journal,
     pub(crate) writing: bool,
     pub(crate) waiting_for_frontend: bool,
     notify_delay: u64,
@@ -64,6 +88,7 @@ impl TexlaStorageManager<GitManager> {
         worksession_interval: u64,
         notify_delay: u64,
     ) -> Self {
+        let journal = Journal::new(main_file.directory.clone());
         Self {
             vcs_manager,
             directory_change_handler: None,
@@ -73,6 +98,7 @@ impl TexlaStorageManager<GitManager> {
             worksession_manager: None,
             worksession_interval,
             dir_watcher: None,
+            journal,
             writing: false,
             waiting_for_frontend: false,
             notify_delay,
@@ -97,6 +123,50 @@ impl TexlaStorageManager<GitManager> {
         start..end
     }
 
+    /// Replaces the conflict markers git leaves behind (which contain branch names or commit
+    /// messages) by the ones understood by the parser and drops the common ancestor's version of
+    /// diff3-style conflicts.
+    fn normalize_conflict_markers(latex: &str) -> String {
+        enum Section {
+            Outside,
+            Ours,
+            Base,
+            Theirs,
+        }
+
+        let mut section = Section::Outside;
+        let mut normalized = String::with_capacity(latex.len());
+        for line in latex.split_inclusive('\n') {
+            let marker = line.trim_end();
+            let replacement = match section {
+                Section::Outside if marker.starts_with(GIT_CONFLICT_BEGIN) => {
+                    section = Section::Ours;
+                    CONFLICT_BEGIN_MARK
+                }
+                Section::Ours if marker.starts_with(GIT_CONFLICT_BASE) => {
+                    section = Section::Base;
+                    continue;
+                }
+                Section::Ours | Section::Base if marker == CONFLICT_SEPARATOR_MARK => {
+                    section = Section::Theirs;
+                    CONFLICT_SEPARATOR_MARK
+                }
+                Section::Base => continue,
+                Section::Theirs if marker.starts_with(GIT_CONFLICT_END) => {
+                    section = Section::Outside;
+                    CONFLICT_END_MARK
+                }
+                _ => {
+                    normalized += line;
+                    continue;
+                }
+            };
+            normalized += replacement;
+            normalized.push('\n');
+        }
+        normalized
+    }
+
     fn curly_brackets_parser() -> BoxedParser<'static, char, String, Simple<char>> {
         none_of::<_, _, Simple<char>>("}")
             .repeated()
@@ -182,6 +252,25 @@ impl TexlaStorageManager<GitManager> {
         (path_abs_os, path_latex)
     }
 
+    /// Returns the absolute path of a file which is not a LaTeX file (e.g. a `.bib` file), so the
+    /// extension is kept as it is. Files outside the project directory are rejected, since their
+    /// changes would not be committed.
+    fn get_other_path(&self, latex_path: &str) -> Result<PathBuf, StorageError> {
+        if !is_inside_project(latex_path) {
+            return Err(StorageError {
+                message: format!("The file '{latex_path}' is outside of the project directory"),
+            });
+        }
+        let path = PathBuf::from(latex_path.replace(LATEX_PATH_SEPARATOR, MAIN_SEPARATOR_STR));
+        Ok(self.main_file.directory.join(path))
+    }
+
+    fn record_commit(&self) {
+        if let Err(err) = self.journal.record_commit() {
+            println!("Could not write to journal: {err}");
+        }
+    }
+
     fn pull_timer_manager(&mut self) -> &mut PullTimerManager {
         self.pull_timer_manager
             .as_mut()
@@ -263,18 +352,53 @@ impl StorageManager for TexlaStorageManager<GitManager> {
             );
         }
 
-        Ok(Self::lf(latex_single_string))
+        Ok(Self::normalize_conflict_markers(&Self::lf(
+            latex_single_string,
+        )))
+    }
+
+    // The files are split off from the back, so that the innermost files are extracted first and
+    // the main file comes last.
+    fn demultiplex_files(&self, mut latex_single_string: String) -> Vec<(PathBuf, String)> {
+        let mut files = vec![];
+
+        while let Some((path, input_byte_range, text_byte_range)) =
+            Self::find_texla_file_marks(&latex_single_string)
+        {
+            let (path_abs_os, path_rel_latex) = self.get_paths(path);
+            debug!("string length: {}", latex_single_string.len());
+            debug!("input range: {:?} bytes", input_byte_range);
+            debug!(
+                "input: {:?}",
+                &latex_single_string[input_byte_range.clone()]
+            );
+            debug!("text range: {:?} bytes", text_byte_range);
+            debug!("text: {:?}", &latex_single_string[text_byte_range.clone()]);
+
+            files.push((
+                path_abs_os,
+                latex_single_string[text_byte_range].to_string(),
+            ));
+
+            // replace '% TEXLA FILE BEGIN ... % TEXLA FILE END' in string with '\input{...}'
+            latex_single_string.replace_range(
+                input_byte_range,
+                &format!("{}{{{}}}", INPUT, path_rel_latex.to_str().unwrap()),
+            )
+        }
+
+        files.push((self.main_file.path.clone(), latex_single_string));
+        files
     }
 
-    // This method is called when either the frontend performs an operation or an export is created.
+    // This method is called when the frontend performs an operation.
     fn wait_for_action(&mut self) {
         self.waiting_for_frontend = true;
         self.pull_timer_manager().deactivate();
         self.worksession_manager().pause();
     }
 
-    // This method is called when either the frontend aborts an operation or an export has
-    // completed.
+    // This method is called when the frontend aborts an operation.
     fn action_aborted(&mut self) {
         self.waiting_for_frontend = false;
         self.pull_timer_manager().activate();
@@ -283,42 +407,24 @@ impl StorageManager for TexlaStorageManager<GitManager> {
     // note: This method could be accidentally used to perform multiple saves simultaneously.
     async fn save(
         this: Arc<Mutex<Self>>,
-        mut latex_single_string: String,
+        latex_single_string: String,
     ) -> Result<(), InfrastructureError> {
         // To further improve performance, async file I/O could be used.
         {
             this.lock().unwrap().writing = true;
 
-            loop {
-                let find_res = TexlaStorageManager::find_texla_file_marks(&latex_single_string);
-                if find_res.is_none() {
-                    break;
+            let files = this.lock().unwrap().demultiplex_files(latex_single_string);
+            for (path, content) in files {
+                debug!("writing file: {:?}", path);
+                if let Some(directory) = path.parent() {
+                    // files extracted by an operation may be placed in new directories
+                    if let Err(err) = fs::create_dir_all(directory) {
+                        this.lock().unwrap().writing = false;
+                        return Err(err.into());
+                    }
                 }
-
-                let (path, input_byte_range, text_byte_range) = find_res.unwrap();
-                let (path_abs_os, path_rel_latex) = this.lock().unwrap().get_paths(path);
-                debug!("writing file: {:?}", path_abs_os);
-                debug!("string length: {}", latex_single_string.len());
-                debug!("input range: {:?} bytes", input_byte_range);
-                debug!(
-                    "input: {:?}",
-                    &latex_single_string[input_byte_range.clone()]
-                );
-                debug!("text range: {:?} bytes", text_byte_range);
-                debug!("text: {:?}", &latex_single_string[text_byte_range.clone()]);
-
-                fs::write(path_abs_os, &latex_single_string[text_byte_range])
-                    .expect("Could not write file");
-
-                // replace '% TEXLA FILE BEGIN ... % TEXLA FILE END' in string with '\input{...}'
-                latex_single_string.replace_range(
-                    input_byte_range,
-                    &format!("{}{{{}}}", INPUT, path_rel_latex.to_str().unwrap()),
-                )
+                fs::write(path, content).expect("Could not write file");
             }
-
-            fs::write(&this.lock().unwrap().main_file.path, latex_single_string)
-                .expect("Could not write file");
         }
 
         // this is frankly needed, because notify does not pick up all changes immediately
@@ -333,6 +439,104 @@ impl StorageManager for TexlaStorageManager<GitManager> {
         Ok(())
     }
 
+    fn file_exists(&self, latex_path: String) -> bool {
+        self.get_paths(latex_path).0.exists()
+    }
+
+    fn read_file(&self, latex_path: &str) -> Result<String, InfrastructureError> {
+        Ok(fs::read_to_string(self.get_other_path(latex_path)?)?)
+    }
+
+    // Writes a single file which is not part of the ast (like a `.bib` file). The change is
+    // committed at the end of the worksession, just like the changes of operations.
+    async fn save_file(
+        this: Arc<Mutex<Self>>,
+        latex_path: String,
+        content: String,
+    ) -> Result<(), InfrastructureError> {
+        {
+            let mut sm = this.lock().unwrap();
+            let path = sm.get_other_path(&latex_path)?;
+            sm.writing = true;
+            debug!("writing file: {:?}", path);
+            if let Err(err) = fs::write(path, content) {
+                sm.writing = false;
+                return Err(err.into());
+            }
+        }
+
+        // see save()
+        let duration = Duration::from_millis(this.lock().unwrap().notify_delay);
+        sleep(duration).await;
+        this.lock().unwrap().writing = false;
+
+        let mut sm = this.lock().unwrap();
+        sm.pull_timer_manager().activate();
+        sm.worksession_manager().start_or_uphold();
+
+        Ok(())
+    }
+
+    async fn remove_files(
+        this: Arc<Mutex<Self>>,
+        latex_paths: Vec<String>,
+    ) -> Result<(), InfrastructureError> {
+        {
+            let mut sm = this.lock().unwrap();
+            sm.writing = true;
+
+            for latex_path in latex_paths {
+                // deleted files outside of the repository could not be restored
+                if !is_inside_project(&latex_path) {
+                    sm.writing = false;
+                    return Err(StorageError {
+                        message: format!(
+                            "The file '{latex_path}' is outside of the project directory"
+                        ),
+                    }
+                    .into());
+                }
+                let (path_abs_os, _) = sm.get_paths(latex_path);
+                debug!("removing file: {:?}", path_abs_os);
+                if let Err(err) = fs::remove_file(path_abs_os) {
+                    sm.writing = false;
+                    return Err(err.into());
+                }
+            }
+        }
+
+        // see save()
+        let duration = Duration::from_millis(this.lock().unwrap().notify_delay);
+        sleep(duration).await;
+        this.lock().unwrap().writing = false;
+
+        Ok(())
+    }
+
+    // This method is called when a change should not be mixed up with other changes in the same
+    // worksession, e.g. when files are created or deleted by an operation.
+    fn commit_change(&mut self, message: String) {
+        if self.vcs_manager.has_local_changes() {
+            self.vcs_manager.commit(Some(message));
+            self.record_commit();
+            self.vcs_manager.pull();
+            self.vcs_manager.push();
+        }
+    }
+
+    fn has_local_changes(&self) -> bool {
+        self.vcs_manager.has_local_changes()
+    }
+
+    // This method is called when the last conflict of a paused rebase has been resolved and saved.
+    fn finish_merge(&mut self) {
+        self.vcs_manager.continue_rebase();
+    }
+
+    fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
     fn end_worksession(&mut self) {
         // don't call save() here since all changes are already saved at end of worksession
 
@@ -340,6 +544,7 @@ impl StorageManager for TexlaStorageManager<GitManager> {
 
         if self.vcs_manager.has_local_changes() {
             self.vcs_manager.commit(None);
+            self.record_commit();
             self.vcs_manager.pull();
             self.vcs_manager.push();
         } else {
@@ -362,6 +567,7 @@ pub trait DirectoryChangeHandler: Send + Sync {
 #[cfg(test)]
 mod tests {
     use std::fs;
+    use std::path::Path;
     use std::sync::{Arc, Mutex};
 
     use ast::texla_ast::TexlaAst;
@@ -411,6 +617,49 @@ mod tests {
         TexlaAst::from_latex(correct_latex_single_string).unwrap();
     }
 
+    #[test]
+    fn normalize_conflict_markers() {
+        let latex = "Text\n<<<<<<< HEAD\nText by us\n||||||| parent of 1a2b3c4 (TeXLa)\nText\n=======\nText by them\n>>>>>>> 1a2b3c4 (TeXLa)\n";
+
+        assert_eq!(
+            TexlaStorageManager::<GitManager>::normalize_conflict_markers(latex),
+            "Text\n<<<<<<< ours\nText by us\n=======\nText by them\n>>>>>>> theirs\n"
+        );
+    }
+
+    #[test]
+    fn other_files_outside_of_project() {
+        let main_file = FilePath::from("test_resources/latex/with_inputs.tex");
+        let vcs_manager = GitManager::new(true, main_file.directory.clone());
+        let storage_manager = TexlaStorageManager::new(vcs_manager, main_file, 500, 5000, 100);
+
+        for path in [
+            "/etc/passwd",
+            "../latex/sections/section1.tex",
+            "C:\\refs.bib",
+        ] {
+            assert!(storage_manager.read_file(path).is_err(), "{path}");
+        }
+        assert!(storage_manager.read_file("sections/section1.tex").is_ok());
+    }
+
+    #[tokio::test]
+    async fn remove_files_outside_of_project() {
+        let main_file = FilePath::from("test_resources/latex/with_inputs.tex");
+        let vcs_manager = GitManager::new(true, main_file.directory.clone());
+        let storage_manager = TexlaStorageManager::new(vcs_manager, main_file, 500, 5000, 100);
+        let shared = Arc::new(Mutex::new(storage_manager));
+
+        let latex_paths = vec!["../latex/sections/section1.tex".to_string()];
+        assert!(
+            TexlaStorageManager::remove_files(shared.clone(), latex_paths)
+                .await
+                .is_err()
+        );
+        assert!(Path::new("test_resources/latex/sections/section1.tex").exists());
+        assert!(!shared.lock().unwrap().writing);
+    }
+
     #[tokio::test]
     async fn save() {
         // rebuild test directory
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+
+use crate::infrastructure::errors::InfrastructureError;
+
+/// Scaled points per PDF point (TeX has 72.27 points per inch, PDF 72)
+const SCALED_POINTS_PER_BIG_POINT: f64 = 65536.0 * 72.27 / 72.0;
+
+/// A rectangle on a page of the PDF in PDF points, measured from the top left corner of the page.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PdfLocation {
+    /// The first page is 1
+    pub page: usize,
+    pub x: f64,
+    pub y: f64,
+    #[serde(default)]
+    pub width: f64,
+    #[serde(default)]
+    pub height: f64,
+}
+
+/// One box or position the engine recorded while typesetting a line of an input file.
+#[derive(Debug, Clone, PartialEq)]
+struct Record {
+    page: usize,
+    input: u32,
+    line: usize,
+    /// Whether the record spans an area (hbox or vbox) instead of marking a position
+    is_box: bool,
+    x: f64,
+    /// The baseline
+    y: f64,
+    width: f64,
+    height: f64,
+    depth: f64,
+}
+
+impl Record {
+    fn top(&self) -> f64 {
+        self.y - self.height
+    }
+
+    fn bottom(&self) -> f64 {
+        self.y + self.depth
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        (self.x..=self.x + self.width).contains(&x) && (self.top()..=self.bottom()).contains(&y)
+    }
+
+    fn distance(&self, x: f64, y: f64) -> f64 {
+        let dx = (self.x - x).max(x - (self.x + self.width)).max(0.0);
+        let dy = (self.top() - y).max(y - self.bottom()).max(0.0);
+        (dx * dx + dy * dy).sqrt()
+    }
+}
+
+/// The content of a SyncTeX file, which relates the lines of the input files to the positions
+/// in the PDF they were typeset at.
+#[derive(Debug, Default)]
+pub struct SyncTex {
+    /// The paths of the input files by their tags, as they were passed to the engine
+    inputs: HashMap<u32, String>,
+    records: Vec<Record>,
+}
+
+impl SyncTex {
+    /// Reads a (gzip compressed) `.synctex.gz` file.
+    pub fn read(path: &Path) -> Result<Self, InfrastructureError> {
+        let mut content = String::new();
+        GzDecoder::new(File::open(path)?).read_to_string(&mut content)?;
+        Ok(Self::parse(&content))
+    }
+
+    /// Parses the uncompressed content of a SyncTeX file (lines it cannot read are skipped).
+    pub fn parse(content: &str) -> Self {
+        let mut synctex = SyncTex::default();
+        let mut unit = 1.0;
+        let mut magnification = 1.0;
+        let (mut x_offset, mut y_offset) = (0.0, 0.0);
+        let mut page = 0;
+
+        for line in content.lines() {
+            if let Some(input) = line.strip_prefix("Input:") {
+                if let Some((tag, path)) = input.split_once(':') {
+                    if let Ok(tag) = tag.parse() {
+                        synctex.inputs.insert(tag, path.to_string());
+                    }
+                }
+            } else if let Some(value) = line.strip_prefix("Unit:") {
+                unit = value.parse().unwrap_or(unit);
+            } else if let Some(value) = line.strip_prefix("Magnification:") {
+                magnification = value.parse::<f64>().map_or(magnification, |m| m / 1000.0);
+            } else if let Some(value) = line.strip_prefix("X Offset:") {
+                x_offset = value.parse().unwrap_or(x_offset);
+            } else if let Some(value) = line.strip_prefix("Y Offset:") {
+                y_offset = value.parse().unwrap_or(y_offset);
+            } else if let Some(number) = line.strip_prefix('{') {
+                page = number.parse().unwrap_or(page + 1);
+            } else if line.starts_with("Postamble:") {
+                break;
+            } else if let Some(mut record) = parse_record(line, page) {
+                let scale = |value: f64| value * unit * magnification / SCALED_POINTS_PER_BIG_POINT;
+                record.x = scale(record.x + x_offset);
+                record.y = scale(record.y + y_offset);
+                record.width = scale(record.width);
+                record.height = scale(record.height);
+                record.depth = scale(record.depth);
+                synctex.records.push(record);
+            }
+        }
+
+        synctex
+    }
+
+    /// Returns the area in which the given lines (starting at 1) of the input file were typeset
+    /// first. `is_file` decides whether an input path belongs to the file.
+    pub fn forward(
+        &self,
+        is_file: impl Fn(&str) -> bool,
+        first_line: usize,
+        last_line: usize,
+    ) -> Option<PdfLocation> {
+        let inputs: Vec<u32> = self
+            .inputs
+            .iter()
+            .filter(|(_, path)| is_file(path))
+            .map(|(tag, _)| *tag)
+            .collect();
+        let records: Vec<&Record> = self
+            .records
+            .iter()
+            .filter(|record| {
+                inputs.contains(&record.input) && (first_line..=last_line).contains(&record.line)
+            })
+            .collect();
+        let page = records.first()?.page;
+        let on_page = records.iter().filter(|record| record.page == page);
+
+        let left = on_page.clone().map(|r| r.x).fold(f64::INFINITY, f64::min);
+        let right = on_page.clone().map(|r| r.x + r.width).fold(left, f64::max);
+        let top = on_page
+            .clone()
+            .map(|r| r.top())
+            .fold(f64::INFINITY, f64::min);
+        let bottom = on_page.map(|r| r.bottom()).fold(top, f64::max);
+        Some(PdfLocation {
+            page,
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        })
+    }
+
+    /// Returns the path of the input file and the line which produced the content at the given
+    /// point: the smallest box containing it or, if there is none, the closest record.
+    pub fn backward(&self, page: usize, x: f64, y: f64) -> Option<(&str, usize)> {
+        let on_page = self.records.iter().filter(|record| record.page == page);
+        let record = on_page
+            .clone()
+            .filter(|record| record.is_box && record.contains(x, y))
+            .min_by(|a, b| (a.width * a.height).total_cmp(&(b.width * b.height)))
+            .or_else(|| on_page.min_by(|a, b| a.distance(x, y).total_cmp(&b.distance(x, y))))?;
+        let path = self.inputs.get(&record.input)?;
+        Some((path, record.line))
+    }
+}
+
+/// Parses a record like `[1,12:4736286,8799518:26673152,655360,0` (type, input tag, line,
+/// optional column, position and for boxes their dimensions).
+fn parse_record(line: &str, page: usize) -> Option<Record> {
+    let kind = line.chars().next()?;
+    let is_box = match kind {
+        '[' | '(' | 'v' | 'h' => true,
+        'x' | 'k' | 'g' | '$' => false,
+        _ => return None,
+    };
+    let mut fields = line[1..].split(':');
+    let mut link = fields.next()?.split(',');
+    let input = link.next()?.parse().ok()?;
+    let line_number = link.next()?.parse().ok()?;
+    let (x, y) = fields.next()?.split_once(',')?;
+    let dimensions: Vec<f64> = fields
+        .next()
+        .map(|dimensions| {
+            dimensions
+                .split(',')
+                .filter_map(|d| d.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    let dimension = |index: usize| {
+        // only boxes have a height and a depth
+        is_box
+            .then(|| dimensions.get(index).copied())
+            .flatten()
+            .unwrap_or_default()
+    };
+
+    Some(Record {
+        page,
+        input,
+        line: line_number,
+        is_box,
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+        width: dimensions.first().copied().unwrap_or_default(),
+        height: dimension(1),
+        depth: dimension(2),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::infrastructure::synctex::SyncTex;
+
+    // coordinates are chosen as multiples of the scale, so that they are whole PDF points
+    const SCALE: f64 = 65536.0 * 72.27 / 72.0;
+
+    fn synctex() -> SyncTex {
+        let sp = |points: f64| (points * SCALE).round() as i64;
+        let content = format!(
+            "SyncTeX Version:1\nInput:1:/home/user/paper/./main.tex\nInput:2:/home/user/paper/./intro.tex\nOutput:pdf\nMagnification:1000\nUnit:1\nX Offset:0\nY Offset:0\nContent:\n!100\n{{1\n[1,3:{},{}:{},{},0\n(2,4:{},{}:{},{},{}\nx2,4:{},{}\n)\n(1,7:{},{}:{},{},0\n)\n]\n}}1\n{{2\n(1,9:{},{}:{},{},0\n)\n}}2\nPostamble:\nCount:7\n",
+            sp(72.0), sp(672.0), sp(400.0), sp(600.0),
+            sp(72.0), sp(100.0), sp(300.0), sp(10.0), sp(2.0),
+            sp(80.0), sp(100.0),
+            sp(72.0), sp(200.0), sp(300.0), sp(10.0),
+            sp(72.0), sp(100.0), sp(300.0), sp(10.0),
+        );
+        SyncTex::parse(&content)
+    }
+
+    #[test]
+    fn forward_search() {
+        let synctex = synctex();
+
+        let location = synctex
+            .forward(|path| path.ends_with("intro.tex"), 4, 4)
+            .unwrap();
+        assert_eq!(location.page, 1);
+        assert!((location.x - 72.0).abs() < 0.01, "{location:?}");
+        assert!((location.y - 90.0).abs() < 0.01, "{location:?}");
+        assert!((location.width - 300.0).abs() < 0.01, "{location:?}");
+        assert!((location.height - 12.0).abs() < 0.01, "{location:?}");
+
+        let location = synctex
+            .forward(|path| path.ends_with("main.tex"), 8, 10)
+            .unwrap();
+        assert_eq!(location.page, 2);
+        assert!(synctex.forward(|_| true, 20, 30).is_none());
+    }
+
+    #[test]
+    fn backward_search() {
+        let synctex = synctex();
+
+        assert_eq!(
+            synctex.backward(1, 100.0, 95.0),
+            Some(("/home/user/paper/./intro.tex", 4))
+        );
+        assert_eq!(
+            synctex.backward(1, 100.0, 195.0),
+            Some(("/home/user/paper/./main.tex", 7))
+        );
+        // outside of all boxes on the page the closest record is chosen
+        assert_eq!(
+            synctex.backward(2, 10.0, 10.0),
+            Some(("/home/user/paper/./main.tex", 9))
+        );
+        assert_eq!(synctex.backward(3, 10.0, 10.0), None);
+    }
+}
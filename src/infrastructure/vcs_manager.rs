@@ -1,8 +1,13 @@
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::{Command, ExitStatus, Output};
 use std::sync::{Arc, RwLock};
 
 use chrono::Local;
+use serde::Serialize;
+
+use ast::latex_constants::LATEX_FILE_EXTENSION;
+use ast::merge::{merge_latex, MergeConflict, MergeResult, CONFLICT_BEGIN_MARK};
 
 use crate::infrastructure::errors::VcsError;
 
@@ -57,12 +62,22 @@ impl StringOutput {
     }
 }
 
+/// Conflicts in one file which could not be merged automatically.
+/// As remote changes are pulled by rebasing, "ours" are the remote and "theirs" the local changes.
+#[derive(Serialize, Debug)]
+pub struct FileConflicts {
+    pub path: String,
+    pub conflicts: Vec<MergeConflict>,
+}
+
 pub trait VcsManager: Send + Sync {
     fn attach_handler(&mut self, ge_handler: Arc<RwLock<dyn GitErrorHandler>>);
     fn pull(&self);
     fn commit(&self, message: Option<String>);
     fn push(&self);
     fn has_local_changes(&self) -> bool;
+    /// Finishes a paused rebase once all conflicts have been resolved.
+    fn continue_rebase(&self);
 }
 
 pub struct GitManager {
@@ -86,6 +101,18 @@ impl GitManager {
     const GIT_ADD: [&'static str; 2] = ["add", "--all"];
     const GIT_COMMIT: [&'static str; 2] = ["commit", "--message"];
     const GIT_PUSH: [&'static str; 1] = ["push"];
+    const GIT_SHOW_TOPLEVEL: [&'static str; 2] = ["rev-parse", "--show-toplevel"];
+    const GIT_LIST_CONFLICTS: [&'static str; 3] = ["diff", "--name-only", "--diff-filter=U"];
+    const GIT_SHOW: [&'static str; 1] = ["show"];
+    const GIT_ADD_FILE: [&'static str; 2] = ["add", "--"];
+    const GIT_REBASE_CONTINUE: [&'static str; 4] =
+        ["-c", "core.editor=true", "rebase", "--continue"];
+    const GIT_REBASE_ABORT: [&'static str; 2] = ["rebase", "--abort"];
+    const GIT_PATH: [&'static str; 2] = ["rev-parse", "--git-path"];
+    const GIT_REBASE_DIRECTORIES: [&'static str; 2] = ["rebase-merge", "rebase-apply"];
+
+    // a rebase stops at most once for every local commit
+    const MAX_REBASE_STEPS: usize = 100;
 
     pub fn new(enabled: bool, main_file_directory: PathBuf) -> Self {
         fn inactive(main_file_directory: PathBuf) -> GitManager {
@@ -159,6 +186,181 @@ impl GitManager {
     pub fn remote_url(&self) -> Option<&String> {
         self.remote_url.as_ref()
     }
+
+    /// Whether a rebase stopped because of conflicts which have not been resolved yet.
+    fn rebase_in_progress(&self) -> bool {
+        if !self.active {
+            return false;
+        }
+
+        Self::GIT_REBASE_DIRECTORIES.iter().any(|directory| {
+            let mut command = Self::GIT_PATH.to_vec();
+            command.push(directory);
+            let path = self.git(command).stdout;
+            self.main_file_directory.join(path).exists()
+        })
+    }
+
+    /// Tries to finish a rebase which stopped because of conflicting changes by merging the
+    /// conflicting LaTeX files node by node (see [merge_latex]).
+    fn merge_rebase_conflicts(&self) -> Result<(), VcsError> {
+        let toplevel = PathBuf::from(self.git(Self::GIT_SHOW_TOPLEVEL.to_vec()).stdout);
+        let conflicting_paths = Self::list_conflicts(&toplevel);
+        if conflicting_paths.is_empty() {
+            // pulling failed for another reason
+            return Err(VcsError {
+                message: "unable to pull remote changes".to_string(),
+            });
+        }
+
+        self.merge_conflicting_files(&toplevel, conflicting_paths)
+    }
+
+    /// Merges the conflicting files of every step of the rebase.
+    /// If there are conflicts which cannot be merged automatically, they are written to the files
+    /// (enclosed by conflict markers) and reported. The rebase stays paused until they have been
+    /// resolved (see [VcsManager::continue_rebase]).
+    fn merge_conflicting_files(
+        &self,
+        toplevel: &Path,
+        mut conflicting_paths: String,
+    ) -> Result<(), VcsError> {
+        for _ in 0..Self::MAX_REBASE_STEPS {
+            println!("Merging conflicting files node by node...");
+            let mut merged = vec![];
+            let mut conflicts = vec![];
+            for path in conflicting_paths.lines() {
+                match self.merge_file(toplevel, path) {
+                    Ok(result) => {
+                        if !result.conflicts.is_empty() {
+                            conflicts.push(FileConflicts {
+                                path: path.to_string(),
+                                conflicts: result.conflicts,
+                            });
+                        }
+                        merged.push((path, result.latex));
+                    }
+                    Err(message) => return Err(self.abort_rebase(toplevel, message)),
+                }
+            }
+
+            for (path, latex) in &merged {
+                if let Err(err) = fs::write(toplevel.join(path), latex) {
+                    let message = format!("unable to write merged file '{path}': {err}");
+                    return Err(self.abort_rebase(toplevel, message));
+                }
+            }
+
+            if !conflicts.is_empty() {
+                for (path, _) in &merged {
+                    if !conflicts.iter().any(|file| file.path == *path) {
+                        Self::add_file(toplevel, path);
+                    }
+                }
+
+                println!("> Rebase paused until all conflicts are resolved");
+                self.git_error_handler
+                    .as_ref()
+                    .expect("No git error handler present")
+                    .read()
+                    .unwrap()
+                    .handle_merge_conflicts(conflicts);
+                return Ok(());
+            }
+
+            for (path, _) in &merged {
+                Self::add_file(toplevel, path);
+            }
+
+            match self.continue_rebase_step(toplevel)? {
+                Some(paths) => conflicting_paths = paths, // the next local commit conflicts as well
+                None => return Ok(()),
+            }
+        }
+
+        let message = "unable to pull remote changes (too many conflicts)".to_string();
+        Err(self.abort_rebase(toplevel, message))
+    }
+
+    /// Continues the rebase after all conflicting files have been added.
+    /// Returns the conflicting files of the next step or `None` if the rebase is finished.
+    fn continue_rebase_step(&self, toplevel: &Path) -> Result<Option<String>, VcsError> {
+        let continue_output = Self::git_inside_dir(Self::GIT_REBASE_CONTINUE.to_vec(), toplevel);
+        let conflicting_paths = Self::list_conflicts(toplevel);
+        if !conflicting_paths.is_empty() {
+            return Ok(Some(conflicting_paths));
+        }
+
+        if continue_output.status.success() {
+            println!("> Merged remote changes");
+            Ok(None)
+        } else {
+            println!(
+                "Git error on 'rebase --continue':\n{}",
+                continue_output.out_err_formatted()
+            );
+            let message = "unable to continue rebasing".to_string();
+            Err(self.abort_rebase(toplevel, message))
+        }
+    }
+
+    fn list_conflicts(toplevel: &Path) -> String {
+        Self::git_inside_dir(Self::GIT_LIST_CONFLICTS.to_vec(), toplevel).stdout
+    }
+
+    fn add_file(toplevel: &Path, path: &str) {
+        let mut command = Self::GIT_ADD_FILE.to_vec();
+        command.push(path);
+        Self::git_inside_dir(command, toplevel);
+    }
+
+    fn handle_error(&self, error: VcsError) {
+        self.git_error_handler
+            .as_ref()
+            .expect("No git error handler present")
+            .read()
+            .unwrap()
+            .handle_git_error(error);
+    }
+
+    /// Merges the versions of a conflicting file stored by git.
+    fn merge_file(&self, toplevel: &Path, path: &str) -> Result<MergeResult, String> {
+        if !path.ends_with(&format!(".{LATEX_FILE_EXTENSION}")) {
+            return Err(format!(
+                "'{path}' cannot be merged, because it is no LaTeX file"
+            ));
+        }
+
+        // the stages are: 1 = common ancestor, 2 = upstream (ours), 3 = local commit (theirs)
+        let stage = |number: u8| {
+            let mut command = Self::GIT_SHOW.to_vec();
+            let object = format!(":{number}:{path}");
+            command.push(&object);
+            let output = Self::git_inside_dir(command, toplevel);
+            if output.status.success() {
+                return Ok(output.stdout + "\n");
+            }
+            // a missing stage means that the file did not exist in that version
+            let reason = match number {
+                1 => "it was added on both sides",
+                2 => "it was deleted upstream",
+                _ => "it was deleted locally",
+            };
+            Err(format!(
+                "'{path}' cannot be merged, because {reason} ({})",
+                output.stderr.trim()
+            ))
+        };
+
+        merge_latex(&stage(1)?, &stage(2)?, &stage(3)?)
+            .map_err(|err| format!("unable to merge '{path}': {err}"))
+    }
+
+    fn abort_rebase(&self, toplevel: &Path, message: String) -> VcsError {
+        println!("Aborting rebase: {message}");
+        Self::git_inside_dir(Self::GIT_REBASE_ABORT.to_vec(), toplevel);
+        VcsError { message }
+    }
 }
 
 impl VcsManager for GitManager {
@@ -167,7 +369,7 @@ impl VcsManager for GitManager {
     }
 
     fn pull(&self) {
-        if !self.active {
+        if !self.active || self.rebase_in_progress() {
             return;
         }
 
@@ -177,19 +379,14 @@ impl VcsManager for GitManager {
 
         if !pull_output.status.success() {
             println!("Git error on 'pull':\n{}", pull_output.out_err_formatted());
-            self.git_error_handler
-                .as_ref()
-                .expect("No git error handler present")
-                .read()
-                .unwrap()
-                .handle_git_error(VcsError {
-                    message: "unable to pull remote changes".to_string(),
-                });
+            if let Err(error) = self.merge_rebase_conflicts() {
+                self.handle_error(error);
+            }
         }
     }
 
     fn commit(&self, custom_message: Option<String>) {
-        if !self.active {
+        if !self.active || self.rebase_in_progress() {
             return;
         }
 
@@ -239,7 +436,7 @@ impl VcsManager for GitManager {
     }
 
     fn push(&self) {
-        if !self.active {
+        if !self.active || self.rebase_in_progress() {
             return;
         }
 
@@ -265,8 +462,42 @@ impl VcsManager for GitManager {
         !status_output.stdout.is_empty()
         // if output from 'git status --porcelain' is empty, there are no local changes
     }
+
+    fn continue_rebase(&self) {
+        if !self.rebase_in_progress() {
+            return;
+        }
+
+        let toplevel = PathBuf::from(self.git(Self::GIT_SHOW_TOPLEVEL.to_vec()).stdout);
+        let conflicting_paths = Self::list_conflicts(&toplevel);
+        for path in conflicting_paths.lines() {
+            let resolved = fs::read_to_string(toplevel.join(path))
+                .map(|content| !content.lines().any(|line| line == CONFLICT_BEGIN_MARK))
+                .unwrap_or(false);
+            if !resolved {
+                return;
+            }
+        }
+
+        for path in conflicting_paths.lines() {
+            Self::add_file(&toplevel, path);
+        }
+        println!("Continuing rebase...");
+        let result = match self.continue_rebase_step(&toplevel) {
+            Ok(Some(paths)) => self.merge_conflicting_files(&toplevel, paths),
+            Ok(None) => Ok(()),
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Ok(()) if !self.rebase_in_progress() => self.push(),
+            Ok(()) => {}
+            Err(error) => self.handle_error(error),
+        }
+    }
 }
 
 pub trait GitErrorHandler: Send + Sync {
     fn handle_git_error(&self, error: VcsError);
+    fn handle_merge_conflicts(&self, conflicts: Vec<FileConflicts>);
 }
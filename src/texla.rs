@@ -1,5 +1,7 @@
+mod background_build;
 mod core;
 pub mod errors;
+mod recovery;
 mod socket;
 pub mod start;
 mod state;
@@ -0,0 +1,203 @@
+use std::sync::{RwLock, Weak};
+use std::time::Duration;
+
+use debounced::debounced;
+use futures::StreamExt;
+use tokio::sync::mpsc::{channel, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+
+use ast::diagnostics::{Diagnostic, DiagnosticKind, Severity};
+use ast::source_map::SourceMap;
+
+use crate::infrastructure::build_manager::{
+    BuildManager, LogEntry, LogEntryKind, TexlaBuildManager,
+};
+use crate::infrastructure::file_path::FilePath;
+use crate::texla::errors::TexlaError;
+use crate::texla::state::TexlaState;
+
+/// The time without saves after which the document is built
+const BACKGROUND_BUILD_DELAY: Duration = Duration::from_millis(1000);
+const BACKGROUND_BUILD_EVENT_BUFFER_SIZE: usize = 10;
+
+/// Builds the document whenever it has been saved and reports the errors and warnings of the TeX
+/// engine as diagnostics of the nodes which produced them.
+pub(crate) struct BackgroundBuild {
+    tx: Sender<()>,
+}
+
+impl BackgroundBuild {
+    /// The build stops as soon as the state is dropped.
+    pub(crate) fn new(
+        build_manager: TexlaBuildManager,
+        main_file: FilePath,
+        state: Weak<RwLock<TexlaState>>,
+    ) -> Self {
+        let (tx, rx) = channel(BACKGROUND_BUILD_EVENT_BUFFER_SIZE);
+        let stream = ReceiverStream::new(rx);
+        let mut debounced = debounced(stream, BACKGROUND_BUILD_DELAY);
+
+        tokio::spawn(async move {
+            while debounced.next().await.is_some() {
+                let Some(state_ref) = state.upgrade() else {
+                    break;
+                };
+
+                // the files have just been written from the current ast
+                let source_map = match state_ref
+                    .read()
+                    .unwrap()
+                    .ast
+                    .source_map(&Default::default())
+                {
+                    Ok(source_map) => source_map,
+                    Err(err) => {
+                        println!("Could not map the document to its files: {err}");
+                        continue;
+                    }
+                };
+
+                let build_manager = build_manager.clone();
+                let build_result =
+                    tokio::task::spawn_blocking(move || build_manager.build(&|_| {}))
+                        .await
+                        .unwrap_or_else(|err| Err(err.into()));
+
+                let mut state = state_ref.write().unwrap();
+                match build_result {
+                    Ok(result) => {
+                        state.build_diagnostics =
+                            compilation_diagnostics(&result.entries, &source_map, &main_file);
+                        state.broadcast("build_finished", result);
+                        state.broadcast_diagnostics();
+                    }
+                    Err(err) => state.broadcast("error", TexlaError::from(err)),
+                }
+            }
+        });
+
+        Self { tx }
+    }
+
+    /// Builds the document once no further builds have been requested for a while.
+    pub(crate) fn request(&self) {
+        // a full buffer already guarantees another build
+        self.tx.try_send(()).ok();
+    }
+}
+
+/// Attaches each entry of the log to the node which produced the line it refers to.
+/// Entries without a line (or in files not written by TeXLa) are attached to the root node.
+fn compilation_diagnostics(
+    entries: &[LogEntry],
+    source_map: &SourceMap,
+    main_file: &FilePath,
+) -> Vec<Diagnostic> {
+    entries
+        .iter()
+        .map(|entry| {
+            let file = entry
+                .file
+                .as_deref()
+                .filter(|file| !is_main_file(file, main_file));
+            Diagnostic {
+                uuid: match entry.line {
+                    Some(line) => source_map.node_at(file, line),
+                    None => source_map.root(),
+                },
+                severity: match entry.kind {
+                    LogEntryKind::Error => Severity::Error,
+                    LogEntryKind::Warning => Severity::Warning,
+                    LogEntryKind::BadBox => Severity::Info,
+                },
+                kind: DiagnosticKind::Compilation,
+                message: entry.message.clone(),
+            }
+        })
+        .collect()
+}
+
+fn is_main_file(file: &str, main_file: &FilePath) -> bool {
+    file.strip_prefix("./").unwrap_or(file) == main_file.filename
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+    use std::thread;
+
+    use ast::diagnostics::Severity;
+    use ast::texla_ast::TexlaAst;
+    use ast::Ast;
+
+    use crate::infrastructure::build_manager::{
+        BuildManager, LogEntry, LogEntryKind, TexEngine, TexlaBuildManager,
+    };
+    use crate::infrastructure::file_path::FilePath;
+    use crate::texla::background_build::compilation_diagnostics;
+
+    #[test]
+    fn map_log_entries_to_nodes() {
+        let latex = "\\documentclass{article}\n\\begin{document}\nSome text\n\n% TEXLA FILE BEGIN {intro}\n\\begin{equation}\ne = mc^\n\\end{equation}\n% TEXLA FILE END {intro}\n\\end{document}\n";
+        let ast = TexlaAst::from_latex(latex.to_string()).unwrap();
+        let source_map = ast.source_map(&Default::default()).unwrap();
+        let main_file = FilePath {
+            path: PathBuf::from("project/main.tex"),
+            directory: PathBuf::from("project"),
+            filename: "main.tex".to_string(),
+        };
+        let entry = |file: &str, line| LogEntry {
+            kind: LogEntryKind::Error,
+            message: "Missing $ inserted.".to_string(),
+            file: Some(file.to_string()),
+            line,
+        };
+
+        let diagnostics = compilation_diagnostics(
+            &[
+                entry("./intro.tex", Some(2)),
+                entry("./main.tex", Some(3)),
+                entry("./main.tex", None),
+            ],
+            &source_map,
+            &main_file,
+        );
+
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        // the equation in the included file and the text in the main file
+        assert_eq!(Some(diagnostics[0].uuid), ast.node_at_path(&[1, 0]));
+        assert_eq!(Some(diagnostics[1].uuid), ast.node_at_path(&[0]));
+        assert_eq!(diagnostics[2].uuid, source_map.root());
+    }
+
+    #[test]
+    fn build_after_panicked_build() {
+        let directory = Path::new("test_resources/latex/build_after_panic");
+        fs::remove_dir_all(directory).ok();
+        fs::create_dir_all(directory).unwrap();
+        fs::write(directory.join("main.tex"), "").unwrap();
+        let build_manager = TexlaBuildManager::new(
+            FilePath::from("test_resources/latex/build_after_panic/main.tex"),
+            TexEngine::Pdflatex,
+        );
+
+        // the panic poisons the build lock
+        let panicking = build_manager.clone();
+        let panicked = thread::spawn(move || panicking.build(&|_| panic!("build failed")));
+        assert!(panicked.join().is_err());
+
+        let progress = Mutex::new(vec![]);
+        build_manager
+            .build(&|message| progress.lock().unwrap().push(message))
+            .ok();
+        let progress = progress.into_inner().unwrap();
+        assert!(
+            progress[0].starts_with("Running pdflatex (pass 1 of"),
+            "{progress:?}"
+        );
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,9 +1,12 @@
+use crate::infrastructure::build_manager::TexlaBuildManager;
 use crate::infrastructure::export_manager::TexlaExportManager;
 use crate::infrastructure::file_path::FilePath;
-use crate::texla::socket::TexlaSocket;
+use crate::texla::state::SharedTexlaState;
 
 pub struct TexlaCore {
     pub(crate) export_manager: TexlaExportManager,
+    pub(crate) build_manager: TexlaBuildManager,
+    pub(crate) background_build: bool,
     pub(crate) pull_interval: u64,
     pub(crate) worksession_interval: u64,
     pub(crate) notify_delay: u64,
@@ -12,5 +15,6 @@ pub struct TexlaCore {
     // only needed for offline version
     // (in online version the main_file would be passed from the frontend)
     pub(crate) main_file: FilePath,
-    pub(crate) socket: Option<TexlaSocket>,
+    // shared by all connected clients
+    pub(crate) state: Option<SharedTexlaState>,
 }
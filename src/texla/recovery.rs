@@ -0,0 +1,179 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use ast::texla_ast::TexlaAst;
+use ast::Ast;
+
+use crate::infrastructure::journal::{hash_latex, Journal, JournalEntry, JournalRecord};
+use crate::infrastructure::storage_manager::{StorageManager, TexlaStorageManager};
+use crate::infrastructure::vcs_manager::GitManager;
+use crate::texla::errors::TexlaError;
+
+/// Operations of earlier sessions which did not make it into the repository, e.g. after a crash.
+#[derive(Serialize, Debug)]
+pub struct Recovery {
+    /// Operations which were applied, but whose result was never saved
+    pub unsaved: Vec<JournalEntry>,
+    /// Number of operations which were saved, but never committed
+    pub uncommitted: usize,
+}
+
+/// Finds the operations of other sessions than `session` which are not reflected in `latex` (the
+/// stringified current document) or not committed yet.
+pub fn find_recovery(
+    records: &[JournalRecord],
+    latex: &str,
+    session: &str,
+    has_local_changes: bool,
+) -> Option<Recovery> {
+    let since_commit = records
+        .iter()
+        .rposition(|record| matches!(record, JournalRecord::Committed { .. }))
+        .map_or(records, |index| &records[index + 1..]);
+    let entries: Vec<&JournalEntry> = since_commit
+        .iter()
+        .filter_map(|record| match record {
+            JournalRecord::Operation(entry) if entry.session != session => Some(entry),
+            _ => None,
+        })
+        .collect();
+
+    // the document is in the state before the first unsaved operation
+    let hash = hash_latex(latex);
+    let saved = entries
+        .iter()
+        .rposition(|entry| entry.before == hash)
+        .unwrap_or(entries.len());
+
+    let recovery = Recovery {
+        unsaved: entries[saved..]
+            .iter()
+            .map(|entry| (*entry).clone())
+            .collect(),
+        uncommitted: if has_local_changes { saved } else { 0 },
+    };
+    if recovery.unsaved.is_empty() && recovery.uncommitted == 0 {
+        None
+    } else {
+        Some(recovery)
+    }
+}
+
+/// Applies the journal entries one after another to the document given by `latex`.
+/// The Uuids in the operations are translated using the recorded node paths.
+/// If `strict` is set, every entry must have been recorded for exactly the document it is applied
+/// to, otherwise a mismatch only causes a warning.
+pub fn replay(
+    latex: String,
+    entries: &[JournalEntry],
+    strict: bool,
+) -> Result<TexlaAst, TexlaError> {
+    let mut ast = TexlaAst::from_latex(latex)?;
+
+    for entry in entries {
+        println!(
+            "Replaying operation from {} (session {}): {:?}",
+            entry.timestamp, entry.session, entry.operation
+        );
+        if hash_latex(&ast.to_latex(Default::default())?) != entry.before {
+            let message = format!(
+                "The operation from {} was recorded for another state of the document",
+                entry.timestamp
+            );
+            if strict {
+                return Err(TexlaError { message });
+            }
+            println!("Warning: {message}");
+        }
+
+        let mut operation = entry.operation.clone();
+        operation.map_uuids(&mut |uuid| {
+            entry
+                .nodes
+                .get(&uuid)
+                .and_then(|path| ast.node_at_path(path))
+                .unwrap_or(uuid)
+        });
+        ast.execute(operation.to_trait_obj())?;
+        ast = TexlaAst::from_latex(ast.to_latex(Default::default())?)?;
+    }
+
+    Ok(ast)
+}
+
+/// Replays all operations of the journal at `journal_path` against the document and returns the
+/// resulting LaTeX single string. No files are modified.
+/// Replaying starts at the first operation which was recorded for the current document (or at the
+/// very first operation, if there is none).
+pub fn replay_journal_file(
+    storage_manager: &TexlaStorageManager<GitManager>,
+    journal_path: &Path,
+) -> Result<String, TexlaError> {
+    let latex = storage_manager.multiplex_files()?;
+    let normalized = TexlaAst::from_latex(latex.clone())?.to_latex(Default::default())?;
+
+    let entries: Vec<JournalEntry> = Journal::read(journal_path)?
+        .into_iter()
+        .filter_map(|record| match record {
+            JournalRecord::Operation(entry) => Some(entry),
+            JournalRecord::Committed { .. } => None,
+        })
+        .collect();
+    let start = entries
+        .iter()
+        .position(|entry| entry.before == hash_latex(&normalized))
+        .unwrap_or(0);
+
+    let ast = replay(latex, &entries[start..], false)?;
+
+    Ok(ast.to_latex(Default::default())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use ast::operation::JsonOperation;
+    use ast::texla_ast::TexlaAst;
+    use ast::Ast;
+
+    use crate::infrastructure::journal::{Journal, JournalRecord};
+    use crate::texla::recovery::{find_recovery, replay};
+
+    #[test]
+    fn recover_and_replay_unsaved_operation() {
+        let latex = fs::read_to_string("test_resources/latex/simple_for_operation_testing.tex")
+            .unwrap()
+            .replace("\r\n", "\n");
+        let mut ast = TexlaAst::from_latex(latex.clone()).unwrap();
+        let latex_before = ast.to_latex(Default::default()).unwrap();
+
+        let target = ast.node_at_path(&[0, 0]).unwrap();
+        let operation: JsonOperation = serde_json::from_str(&format!(
+            r#"{{ "type": "DeleteNode", "arguments": {{ "target": {target} }} }}"#
+        ))
+        .unwrap();
+
+        // an earlier session applied the operation, but crashed before saving
+        let journal = Journal::new(PathBuf::from("test_resources/latex"));
+        let entry = journal.entry(&latex_before, &ast, &operation);
+        ast.execute(operation.to_trait_obj()).unwrap();
+        let latex_after = ast.to_latex(Default::default()).unwrap();
+
+        let records = vec![JournalRecord::Operation(entry)];
+        let recovery = find_recovery(&records, &latex_before, "current session", false)
+            .expect("the operation should be unsaved");
+        assert_eq!(recovery.unsaved.len(), 1);
+        assert_eq!(recovery.uncommitted, 0);
+        assert!(
+            find_recovery(&records, &latex_after, "current session", false).is_none(),
+            "a saved and committed operation needs no recovery"
+        );
+
+        // the uuids of the reparsed document differ from the recorded ones
+        let replayed = replay(latex, &recovery.unsaved, true).unwrap();
+        assert_eq!(replayed.to_latex(Default::default()).unwrap(), latex_after);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::process::exit;
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
@@ -11,16 +12,26 @@ use tower::layer::util::{Identity, Stack};
 use tower::ServiceBuilder;
 use tower_http::cors::CorsLayer;
 
-use ast::operation::{JsonOperation, Operation};
+use ast::bibliography::import::{parse_entries, ImportFormat, ImportReport};
+use ast::bibliography::operation::JsonBibOperation;
+use ast::errors::AstError;
+use ast::operation::JsonOperation;
 use ast::options::StringificationOptions;
+use ast::outline::outline;
 use ast::texla_ast::TexlaAst;
-use ast::Ast;
+use ast::{Ast, Uuid};
 
-use crate::infrastructure::export_manager::ExportManager;
+use crate::infrastructure::build_manager::BuildManager;
+use crate::infrastructure::errors::InfrastructureError;
+use crate::infrastructure::export_manager::{ExportFormat, ExportManager, ExportRequest};
+use crate::infrastructure::journal::{Journal, JournalEntry};
 use crate::infrastructure::storage_manager::{StorageManager, TexlaStorageManager};
+use crate::infrastructure::synctex::PdfLocation;
 use crate::infrastructure::vcs_manager::GitManager;
+use crate::texla::background_build::BackgroundBuild;
 use crate::texla::core::TexlaCore;
 use crate::texla::errors::TexlaError;
+use crate::texla::recovery::{find_recovery, replay, Recovery};
 use crate::texla::state::{SharedTexlaState, TexlaState};
 
 const QUIT_DELAY: Duration = Duration::from_secs(1);
@@ -46,74 +57,49 @@ pub fn socket_service(
 async fn handler(socket: TexlaSocket, core: Arc<RwLock<TexlaCore>>) {
     println!("Socket connected with ID '{}'", socket.sid);
 
-    let storage_manager = {
-        let core = core.read().unwrap();
+    // all clients share the state of the project, which is created by the first one
+    let (state_ref, is_first_client) = {
+        let mut core = core.write().unwrap();
 
-        let vcs_manager = GitManager::new(core.vcs_enabled, core.main_file.directory.clone());
-        TexlaStorageManager::new(
-            vcs_manager,
-            core.main_file.clone(),
-            core.pull_interval,
-            core.worksession_interval,
-            core.notify_delay,
-        )
-    };
+        let existing_state = core.state.clone().filter(|state_ref| {
+            let mut state = state_ref.write().unwrap();
+            // a state without clients has already been disassembled
+            let is_active = !state.sockets.is_empty();
+            if is_active {
+                state.sockets.push(socket.clone());
+            }
+            is_active
+        });
 
-    let ast = match parse_ast_from_disk(&storage_manager) {
-        Ok(ast) => ast,
-        Err(err) => {
-            println!("Found invalid ast: {err}");
-            send(&socket, "error", err).ok();
-            return;
-            // this will display the error in the frontend
-            // the frontend will not receive any further messages
+        match existing_state {
+            Some(state_ref) => (state_ref, false),
+            None => match create_state(&core, &socket) {
+                Ok(state_ref) => {
+                    core.state = Some(state_ref.clone());
+                    (state_ref, true)
+                }
+                Err(err) => {
+                    println!("Found invalid ast: {err}");
+                    send(&socket, "error", err).ok();
+                    return;
+                    // this will display the error in the frontend
+                    // the frontend will not receive any further messages
+                }
+            },
         }
     };
-
-    let state = TexlaState {
-        socket: socket.clone(),
-        storage_manager: Arc::new(Mutex::new(storage_manager)),
-        ast,
-    };
-    socket.extensions.insert(Arc::new(RwLock::new(state)));
+    socket.extensions.insert(state_ref.clone());
 
     {
-        let mut core = core.write().unwrap();
-
-        if let Some(old_socket) = core.socket.clone() {
-            let err = TexlaError {
-                message: "This frontend is replaced by another one. \
-                Click the TeXLa logo to regain control."
-                    .to_string(),
-            };
-            send(&old_socket, "error", err).ok();
-            send(&old_socket, "quit", "quit").ok();
-
-            let state = extract_state(&old_socket);
-            let state = state.read().unwrap();
-            let mut sm = state.storage_manager.lock().unwrap();
-            sm.disassemble();
-            println!("Disconnected old socket");
-        }
-
-        core.socket = Some(socket.clone());
-    }
-
-    let storage_manager_handle = {
-        let state_ref = extract_state(&socket);
-        let state = state_ref.read().unwrap();
-
-        state
-            .storage_manager
-            .lock()
+        // the core is always locked before the state
+        let export_profiles = core
+            .read()
             .unwrap()
-            .attach_handlers(state_ref.clone(), state_ref.clone());
-        StorageManager::start(state.storage_manager.clone())
-    };
-
-    {
-        let state_ref = extract_state(&socket);
+            .export_manager
+            .export_profiles()
+            .to_vec();
         let state = state_ref.read().unwrap();
+        println!("{} client(s) connected", state.sockets.len());
         let remote_url = {
             let storage_manager = state.storage_manager.lock().unwrap();
             storage_manager.remote_url().map(|url| url.to_string())
@@ -121,7 +107,25 @@ async fn handler(socket: TexlaSocket, core: Arc<RwLock<TexlaCore>>) {
 
         // initial messages
         send(&socket, "remote_url", remote_url).ok();
-        send(&socket, "new_ast", &state.ast).ok();
+        send(&socket, "export_profiles", export_profiles).ok();
+        send_ast(&socket, &state);
+        send(&socket, "bibliography", &state.bibliography).ok();
+        if is_first_client {
+            match find_recovery_for(&state) {
+                Ok(Some(recovery)) => {
+                    println!(
+                        "Found {} unsaved and {} uncommitted operations in the journal",
+                        recovery.unsaved.len(),
+                        recovery.uncommitted
+                    );
+                    send(&socket, "recovery", recovery).ok();
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    send(&socket, "error", err).ok();
+                }
+            }
+        }
     }
 
     socket.on("active", |socket, _: String, _, _| async move {
@@ -135,56 +139,193 @@ async fn handler(socket: TexlaSocket, core: Arc<RwLock<TexlaCore>>) {
     socket.on("operation", |socket, json: String, _, _| async move {
         print!("Received operation: ");
 
-        let operation = serde_json::from_str::<JsonOperation>(&json)
-            .expect("Got invalid operation from frontend")
-            .to_trait_obj();
+        let operation = match serde_json::from_str::<JsonOperation>(&json) {
+            Ok(operation) => operation,
+            Err(err) => {
+                println!("Got invalid operation from frontend: {err}");
+                let err = TexlaError {
+                    message: format!("Invalid operation: {err}"),
+                };
+                send(&socket, "error", err).ok();
+                return;
+            }
+        };
         println!("{operation:?}");
 
         let state = extract_state(&socket).clone();
-        match perform_and_check_operation(state.clone(), operation).await {
-            Ok(()) => {
-                send(&socket, "new_ast", &state.read().unwrap().ast).ok();
+        match perform_and_check_operation(state.clone(), operation) {
+            Ok(new_node) => {
+                if state.write().unwrap().update_bibliography() {
+                    let state = state.read().unwrap();
+                    state.broadcast("bibliography", &state.bibliography);
+                }
+                state.read().unwrap().broadcast_ast();
+                if let Some(uuid) = new_node {
+                    send(&socket, "new_node", uuid).ok();
+                }
                 println!("Operation was okay");
                 println!("Saved changes");
             }
             Err(err) => {
                 println!("Operation was not okay: {err}");
                 send(&socket, "error", err).ok();
-                // send old ast in order to enable frontend to roll back to it
-                send(&socket, "new_ast", &state.read().unwrap().ast).ok();
+                // send old ast in order to enable frontends to roll back to it
+                state.read().unwrap().broadcast_ast();
+            }
+        }
+    });
+
+    // lets navigation views fetch the outline without waiting for the next change
+    socket.on("outline", |socket, _: String, _, _| async move {
+        let state_ref = extract_state(&socket).clone();
+        let state = state_ref.read().unwrap();
+        send(&socket, "outline", outline(&state.ast)).ok();
+    });
+
+    socket.on("bib_operation", |socket, json: String, _, _| async move {
+        println!("Received bibliography operation: {json}");
+
+        let operation = match serde_json::from_str::<JsonBibOperation>(&json) {
+            Ok(operation) => operation,
+            Err(err) => {
+                let err = TexlaError {
+                    message: format!("Invalid bibliography operation: {err}"),
+                };
+                send(&socket, "error", err).ok();
+                return;
+            }
+        };
+
+        let state = extract_state(&socket).clone();
+        match perform_bib_operation(state.clone(), operation).await {
+            Ok(()) => {
+                let state = state.read().unwrap();
+                state.broadcast("bibliography", &state.bibliography);
+                // the cited keys may have been added or removed
+                state.broadcast_diagnostics();
+                println!("Saved bibliography");
+            }
+            Err(err) => {
+                println!("Bibliography operation was not okay: {err}");
+                send(&socket, "error", err).ok();
+            }
+        }
+    });
+
+    socket.on("replay_journal", |socket, _: String, _, _| async move {
+        println!("Received replay_journal");
+        let state = extract_state(&socket).clone();
+        match recover_operations(state.clone()).await {
+            Ok(()) => {
+                state.read().unwrap().broadcast_ast();
+                println!("Recovered operations from journal");
+            }
+            Err(err) => {
+                println!("Could not recover operations: {err}");
+                send(&socket, "error", err).ok();
+                let state = state.read().unwrap();
+                state.storage_manager.lock().unwrap().action_aborted();
             }
         }
     });
 
     let core_clone = core.clone();
     socket.on("prepare_export", move |socket, json: String, _, _| {
-        let options = serde_json::from_str::<StringificationOptions>(&json)
+        let request = serde_json::from_str::<ExportRequest>(&json)
             .expect("Got invalid options from frontend");
-        handle_export(socket, options, core_clone.clone())
+        handle_export(socket, request, core_clone.clone())
+    });
+
+    let core_clone = core.clone();
+    socket.on("build", move |socket, _: String, _, _| {
+        handle_build(socket, core_clone.clone())
+    });
+
+    let core_clone = core.clone();
+    socket.on("synctex_forward", move |socket, json: String, _, _| {
+        handle_synctex_forward(socket, json, core_clone.clone())
+    });
+
+    let core_clone = core.clone();
+    socket.on("synctex_backward", move |socket, json: String, _, _| {
+        handle_synctex_backward(socket, json, core_clone.clone())
     });
 
     socket.on("quit", |socket, _: String, _, _| async move {
         println!("Received quit");
-        {
-            let storage_manager = {
-                let state_ref = extract_state(&socket);
-                let state = state_ref.read().unwrap();
-                state.storage_manager.clone()
-            };
+        let state_ref = extract_state(&socket).clone();
+        let is_last_client = state_ref.write().unwrap().remove_socket(&socket);
+        if is_last_client {
+            let storage_manager = state_ref.read().unwrap().storage_manager.clone();
             let mut storage_manager = storage_manager.lock().unwrap();
             storage_manager.end_worksession();
             storage_manager.disassemble();
-        };
-        println!("Quitting...");
+            println!("Quitting...");
+        } else {
+            // the other clients keep on editing
+            println!("Client '{}' left", socket.sid);
+        }
         send(&socket, "quit", "ok").ok();
         sleep(QUIT_DELAY).await;
         socket.disconnect().ok();
-        exit(0);
+        if is_last_client {
+            exit(0);
+        }
     });
 
-    if let Err(err) = storage_manager_handle.await {
-        send(&socket, "error", TexlaError::from(err)).ok();
+    if is_first_client {
+        let storage_manager = state_ref.read().unwrap().storage_manager.clone();
+        if let Err(err) = StorageManager::start(storage_manager).await {
+            state_ref
+                .read()
+                .unwrap()
+                .broadcast("error", TexlaError::from(err));
+        };
+    }
+}
+
+/// Creates the state for the first client and attaches it to a new storage manager.
+fn create_state(core: &TexlaCore, socket: &TexlaSocket) -> Result<SharedTexlaState, TexlaError> {
+    let vcs_manager = GitManager::new(core.vcs_enabled, core.main_file.directory.clone());
+    let storage_manager = TexlaStorageManager::new(
+        vcs_manager,
+        core.main_file.clone(),
+        core.pull_interval,
+        core.worksession_interval,
+        core.notify_delay,
+    );
+    let ast = parse_ast_from_disk(&storage_manager)?;
+
+    let mut state = TexlaState {
+        ast,
+        storage_manager: Arc::new(Mutex::new(storage_manager)),
+        sockets: vec![socket.clone()],
+        history: VecDeque::new(),
+        build_diagnostics: vec![],
+        bibliography: Default::default(),
+        background_build: None,
     };
+    state.reload_bibliography();
+    let state_ref = Arc::new(RwLock::new(state));
+    if core.background_build {
+        let background_build = BackgroundBuild::new(
+            core.build_manager.clone(),
+            core.main_file.clone(),
+            Arc::downgrade(&state_ref),
+        );
+        // report the problems of the document as it was opened
+        background_build.request();
+        state_ref.write().unwrap().background_build = Some(background_build);
+    }
+    state_ref
+        .read()
+        .unwrap()
+        .storage_manager
+        .lock()
+        .unwrap()
+        .attach_handlers(state_ref.clone(), state_ref.clone());
+
+    Ok(state_ref)
 }
 
 pub fn parse_ast_from_disk(
@@ -205,45 +346,252 @@ fn extract_state(socket: &TexlaSocket) -> Ref<SharedTexlaState> {
     socket.extensions.get::<SharedTexlaState>().unwrap()
 }
 
-async fn perform_and_check_operation(
+/// Returns the Uuid of the node created by the operation (if any) in the reparsed ast.
+/// Operations of all clients are applied one after another. A client may not have received the
+/// latest ast yet, so the Uuids of the operation are translated to the current ast first.
+fn perform_and_check_operation(
     state: SharedTexlaState,
-    operation: Box<dyn Operation<TexlaAst>>,
-) -> Result<(), TexlaError> {
-    let backup_latex = state.read().unwrap().ast.to_latex(Default::default())?;
-
-    match perform_operation(state.clone(), operation).await {
-        Ok(new_ast) => {
-            state.write().unwrap().ast = new_ast;
-            Ok(())
+    mut operation: JsonOperation,
+) -> Result<Option<Uuid>, TexlaError> {
+    let mut locked = state.write().unwrap();
+    operation.map_uuids(&mut |uuid| locked.translate_uuid(uuid));
+    let resolves_conflict = operation.resolves_conflict();
+
+    let backup_latex = locked.ast.to_latex(Default::default())?;
+    let journal_entry = locked.storage_manager.lock().unwrap().journal().entry(
+        &backup_latex,
+        &locked.ast,
+        &operation,
+    );
+
+    match perform_operation(&mut locked, operation, journal_entry) {
+        Ok((new_ast, new_node, file_changes)) => {
+            locked.replace_ast(new_ast);
+            // the paused rebase can be finished once the last conflict has been resolved
+            let finishes_merge = resolves_conflict && !locked.ast.has_conflicts();
+            drop(locked);
+            save_in_background(state, file_changes, finishes_merge);
+            Ok(new_node)
         }
         Err(err) => {
-            let mut state = state.write().unwrap();
-            state.ast = TexlaAst::from_latex(backup_latex)?;
-            state.storage_manager.lock().unwrap().action_aborted();
+            locked.replace_ast(TexlaAst::from_latex(backup_latex)?);
+            locked.storage_manager.lock().unwrap().action_aborted();
             Err(err)
         }
     }
 }
 
-async fn perform_operation(
-    state: SharedTexlaState,
-    operation: Box<dyn Operation<TexlaAst>>,
-) -> Result<TexlaAst, TexlaError> {
-    let reparsed_ast = {
-        let mut locked = state.write().unwrap();
-        locked.ast.execute(operation)?;
-        let latex_single_string = locked.ast.to_latex(Default::default())?;
-        TexlaAst::from_latex(latex_single_string)?
+/// Files added and removed by an operation
+type FileChanges = (Vec<String>, Vec<String>);
+
+fn perform_operation(
+    locked: &mut TexlaState,
+    operation: JsonOperation,
+    journal_entry: JournalEntry,
+) -> Result<(TexlaAst, Option<Uuid>, Option<FileChanges>), TexlaError> {
+    let files_before = locked.ast.file_paths();
+    let affects_files = operation.affects_files();
+    // uuids change when reparsing, so the new node is tracked by its path
+    let new_node_path = locked
+        .ast
+        .execute(operation.to_trait_obj())?
+        .and_then(|uuid| locked.ast.node_path(uuid));
+    let latex_single_string = locked.ast.to_latex(Default::default())?;
+    let reparsed_ast = TexlaAst::from_latex(latex_single_string)?;
+    let new_node = new_node_path.and_then(|path| reparsed_ast.node_at_path(&path));
+
+    let file_changes = if affects_files {
+        let files_after = reparsed_ast.file_paths();
+        let added: Vec<String> = files_after.difference(&files_before).cloned().collect();
+        let removed: Vec<String> = files_before.difference(&files_after).cloned().collect();
+
+        let storage_manager = locked.storage_manager.lock().unwrap();
+        if let Some(path) = added
+            .iter()
+            .find(|path| storage_manager.file_exists(path.to_string()))
+        {
+            return Err(TexlaError {
+                message: format!("The file '{path}' already exists."),
+            });
+        }
+        // e.g. a copied subtree without files
+        (!added.is_empty() || !removed.is_empty()).then_some((added, removed))
+    } else {
+        None
     };
+
+    // record the operation before saving, so that it can be recovered if saving fails
+    let storage_manager = locked.storage_manager.lock().unwrap();
+    if let Err(err) = storage_manager.journal().record_operation(journal_entry) {
+        println!("Could not write to journal: {err}");
+    }
+
+    Ok((reparsed_ast, new_node, file_changes))
+}
+
+fn save_in_background(
+    state: SharedTexlaState,
+    file_changes: Option<FileChanges>,
+    finishes_merge: bool,
+) {
     tokio::spawn(async move {
         if let Err(err) = stringify_and_save(state.clone(), Default::default()).await {
             println!("Error while saving: {err}");
-            let state = state.read().unwrap();
-            let socket = &state.socket;
-            send(socket, "error", err).ok();
+            state.read().unwrap().broadcast("error", err);
+            return;
+        }
+
+        if let Some((added, removed)) = file_changes {
+            if let Err(err) = commit_file_changes(state.clone(), added, removed).await {
+                println!("Error while changing files: {err}");
+                state.read().unwrap().broadcast("error", err);
+            }
+        }
+        if finishes_merge {
+            let storage_manager = state.read().unwrap().storage_manager.clone();
+            storage_manager.lock().unwrap().finish_merge();
         }
     });
-    Ok(reparsed_ast)
+}
+
+/// Deletes files that are no longer part of the document and commits the whole restructuring as
+/// one change.
+async fn commit_file_changes(
+    state: SharedTexlaState,
+    added: Vec<String>,
+    removed: Vec<String>,
+) -> Result<(), TexlaError> {
+    let storage_manager = state.read().unwrap().storage_manager.clone();
+    StorageManager::remove_files(storage_manager.clone(), removed.clone()).await?;
+
+    let message = match (added.is_empty(), removed.is_empty()) {
+        (false, true) => format!("TeXLa: add {}", added.join(", ")),
+        (true, false) => format!("TeXLa: inline {}", removed.join(", ")),
+        _ => format!(
+            "TeXLa: restructure files (added {}, removed {})",
+            added.join(", "),
+            removed.join(", ")
+        ),
+    };
+    storage_manager.lock().unwrap().commit_change(message);
+
+    Ok(())
+}
+
+/// Applies the operation to the bibliography and writes the changed `.bib` file.
+async fn perform_bib_operation(
+    state: SharedTexlaState,
+    operation: JsonBibOperation,
+) -> Result<(), TexlaError> {
+    let path = state
+        .write()
+        .unwrap()
+        .bibliography
+        .execute(operation.to_trait_obj())
+        .map_err(AstError::from)?;
+    save_bib_file(state, path).await
+}
+
+/// Merges the entries of an uploaded `.bib`, `.ris` or CSL-JSON file into the main bibliography
+/// file and sends the new bibliography to all clients.
+pub(crate) async fn import_bibliography(
+    state: SharedTexlaState,
+    file_name: &str,
+    content: &str,
+) -> Result<ImportReport, TexlaError> {
+    let format = ImportFormat::from_file_name(file_name).ok_or_else(|| TexlaError {
+        message: format!("'{file_name}' is no BibTeX, RIS or CSL-JSON file."),
+    })?;
+    let entries = parse_entries(format, content)?;
+    let report = state
+        .write()
+        .unwrap()
+        .bibliography
+        .import(entries)
+        .map_err(AstError::from)?;
+    println!(
+        "Imported {} entries from '{file_name}' ({} duplicates, {} renamed)",
+        report.added.len(),
+        report.duplicates.len(),
+        report.renamed.len()
+    );
+
+    if !report.added.is_empty() {
+        save_bib_file(state.clone(), report.file.clone()).await?;
+        let state = state.read().unwrap();
+        state.broadcast("bibliography", &state.bibliography);
+        state.broadcast_diagnostics();
+    }
+    Ok(report)
+}
+
+/// Writes a changed `.bib` file, which is committed at the end of the worksession.
+async fn save_bib_file(state: SharedTexlaState, path: String) -> Result<(), TexlaError> {
+    // the bibliography may have been reloaded by an operation of another client in the meantime
+    let content = state
+        .read()
+        .unwrap()
+        .bibliography
+        .file(&path)
+        .map(|file| file.to_bib())
+        .ok_or_else(|| TexlaError {
+            message: format!("The bibliography '{path}' has been reloaded, please try again."),
+        })?;
+
+    let storage_manager = state.read().unwrap().storage_manager.clone();
+    StorageManager::save_file(storage_manager, path, content).await?;
+    if let Some(background_build) = &state.read().unwrap().background_build {
+        background_build.request();
+    }
+
+    Ok(())
+}
+
+fn find_recovery_for(state: &TexlaState) -> Result<Option<Recovery>, TexlaError> {
+    let latex = state.ast.to_latex(Default::default())?;
+    let storage_manager = state.storage_manager.lock().unwrap();
+    let records = Journal::read(&storage_manager.journal().path())?;
+    Ok(find_recovery(
+        &records,
+        &latex,
+        storage_manager.journal().session(),
+        storage_manager.has_local_changes(),
+    ))
+}
+
+/// Replays the unsaved operations of earlier sessions and commits them together with the
+/// uncommitted ones.
+async fn recover_operations(state: SharedTexlaState) -> Result<(), TexlaError> {
+    let (removed, message) = {
+        let mut locked = state.write().unwrap();
+        locked.storage_manager.lock().unwrap().wait_for_action();
+        let recovery = find_recovery_for(&locked)?.ok_or(TexlaError {
+            message: "There are no operations to recover.".to_string(),
+        })?;
+
+        let files_before = locked.ast.file_paths();
+        let latex = locked.ast.to_latex(Default::default())?;
+        let files_after = {
+            let replayed = replay(latex, &recovery.unsaved, true)?;
+            let files_after = replayed.file_paths();
+            locked.replace_ast(replayed);
+            files_after
+        };
+        let removed: Vec<String> = files_before.difference(&files_after).cloned().collect();
+
+        let message = format!(
+            "TeXLa: recover {} operations",
+            recovery.unsaved.len() + recovery.uncommitted
+        );
+        (removed, message)
+    };
+
+    stringify_and_save(state.clone(), Default::default()).await?;
+    let storage_manager = state.read().unwrap().storage_manager.clone();
+    StorageManager::remove_files(storage_manager.clone(), removed).await?;
+    storage_manager.lock().unwrap().commit_change(message);
+
+    Ok(())
 }
 
 async fn stringify_and_save(
@@ -253,63 +601,237 @@ async fn stringify_and_save(
     let latex_single_string = state.read().unwrap().ast.to_latex(options)?;
     let storage_manager = state.read().unwrap().storage_manager.clone();
     StorageManager::save(storage_manager, latex_single_string).await?;
+    if let Some(background_build) = &state.read().unwrap().background_build {
+        background_build.request();
+    }
 
     Ok(())
 }
 
-// this function is correctly placed here, because it contains coordination and communication
 async fn handle_export(
     socket: TexlaSocket,
-    options: StringificationOptions,
+    mut request: ExportRequest,
     core: Arc<RwLock<TexlaCore>>,
 ) {
-    println!("Preparing export with options: {options:?}");
-    let state_ref = extract_state(&socket).clone();
-
-    // copy complete AST with all options enabled
-    let ast_copy = {
-        let state = state_ref.read().unwrap();
-        // freeze git actions until the original AST is reverted
-        state.storage_manager.lock().unwrap().wait_for_action();
-        state_ref.read().unwrap().ast.clone()
-    };
-
-    // apply given options to AST
-    if let Err(err) = stringify_and_save(state_ref.clone(), options).await {
-        send(&socket, "error", err).ok();
-        return;
+    println!("Preparing export: {request:?}");
+    if let Some(name) = &request.profile {
+        let core = core.read().unwrap();
+        let profile = core
+            .export_manager
+            .export_profiles()
+            .iter()
+            .find(|profile| &profile.name == name);
+        match profile {
+            Some(profile) => request.options.node_filter = profile.node_filter.clone(),
+            None => {
+                let err = TexlaError {
+                    message: format!("There is no export profile '{name}'"),
+                };
+                send(&socket, "error", err).ok();
+                return;
+            }
+        }
     }
+    match request.format {
+        ExportFormat::Zip => export_zip(&socket, request.options, core),
+        ExportFormat::Html => {
+            // the core is always locked before the state, like when a client connects
+            let mut core = core.write().unwrap();
+            let state_ref = extract_state(&socket).clone();
+            let state = state_ref.read().unwrap();
+            let html_result = core.export_manager.export_html(&state.ast);
+            send_export_result(&socket, html_result);
+        }
+        ExportFormat::Markdown => {
+            let mut core = core.write().unwrap();
+            let state_ref = extract_state(&socket).clone();
+            let state = state_ref.read().unwrap();
+            let target = request.target.map(|uuid| state.translate_uuid(uuid));
+            let markdown_result = core.export_manager.export_markdown(&state.ast, target);
+            send_export_result(&socket, markdown_result);
+        }
+        ExportFormat::Standalone => {
+            let mut core = core.write().unwrap();
+            let state_ref = extract_state(&socket).clone();
+            let state = state_ref.read().unwrap();
+            let standalone_result = match request.target {
+                Some(uuid) => core.export_manager.export_standalone(
+                    &state.ast,
+                    state.translate_uuid(uuid),
+                    &request.options,
+                ),
+                None => {
+                    let err = TexlaError {
+                        message: "Choose the part of the document to export".to_string(),
+                    };
+                    send(&socket, "error", err).ok();
+                    return;
+                }
+            };
+            send_export_result(&socket, standalone_result);
+        }
+        ExportFormat::Submission => {
+            let mut core = core.write().unwrap();
+            let state_ref = extract_state(&socket).clone();
+            let state = state_ref.read().unwrap();
+            let submission_result = core
+                .export_manager
+                .export_submission(&state.ast, &request.options.node_filter);
+            send_export_result(&socket, submission_result);
+        }
+        ExportFormat::Anonymized => {
+            let mut core = core.write().unwrap();
+            let state_ref = extract_state(&socket).clone();
+            let state = state_ref.read().unwrap();
+            let anonymized_result = core
+                .export_manager
+                .export_anonymized(&state.ast, &request.options.node_filter);
+            let url_result = anonymized_result.map(|(url, report)| {
+                send(&socket, "anonymization_report", report).ok();
+                url
+            });
+            send_export_result(&socket, url_result);
+        }
+    }
+}
 
-    // export AST to zip file
-    let zip_result = core.write().unwrap().export_manager.zip_files();
-
-    // revert original AST with all options enabled
-    extract_state(&socket).write().unwrap().ast = ast_copy;
-
-    // send message to frontend
-    match zip_result {
+fn send_export_result(socket: &TexlaSocket, result: Result<String, InfrastructureError>) {
+    match result {
         Ok(url) => {
-            send(&socket, "export_ready", url).ok();
+            send(socket, "export_ready", url).ok();
         }
         Err(err) => {
-            send(&socket, "error", TexlaError::from(err)).ok();
+            send(socket, "error", TexlaError::from(err)).ok();
         }
     }
+}
 
-    // unfreeze git actions
+/// Builds the PDF in the background and keeps all clients informed about the progress.
+async fn handle_build(socket: TexlaSocket, core: Arc<RwLock<TexlaCore>>) {
     let state_ref = extract_state(&socket).clone();
+    let build_manager = core.read().unwrap().build_manager.clone();
     state_ref
         .read()
         .unwrap()
-        .storage_manager
-        .lock()
+        .broadcast("build_started", build_manager.engine());
+
+    let progress_state = state_ref.clone();
+    let build_result = tokio::task::spawn_blocking(move || {
+        build_manager.build(&|message| {
+            progress_state
+                .read()
+                .unwrap()
+                .broadcast("build_progress", message)
+        })
+    })
+    .await
+    .unwrap_or_else(|err| Err(err.into()));
+
+    match build_result {
+        Ok(result) => state_ref
+            .read()
+            .unwrap()
+            .broadcast("build_finished", result),
+        Err(err) => state_ref
+            .read()
+            .unwrap()
+            .broadcast("error", TexlaError::from(err)),
+    }
+}
+
+async fn handle_synctex_forward(socket: TexlaSocket, json: String, core: Arc<RwLock<TexlaCore>>) {
+    match synctex_forward(&socket, &json, &core) {
+        Ok(location) => send(&socket, "synctex_location", location).ok(),
+        Err(err) => send(&socket, "error", err).ok(),
+    };
+}
+
+/// Returns the location in the PDF at which the node with the given Uuid was typeset.
+fn synctex_forward(
+    socket: &TexlaSocket,
+    json: &str,
+    core: &Arc<RwLock<TexlaCore>>,
+) -> Result<PdfLocation, TexlaError> {
+    let uuid = serde_json::from_str::<u64>(json).map_err(|err| TexlaError {
+        message: format!("Invalid Uuid: {err}"),
+    })?;
+    let build_manager = core.read().unwrap().build_manager.clone();
+    let state_ref = extract_state(socket).clone();
+    let state = state_ref.read().unwrap();
+    let uuid = state.translate_uuid(uuid);
+    let not_typeset = || TexlaError {
+        message: format!("Node {uuid} is not part of the PDF"),
+    };
+
+    // the files have been written from the current ast with the default options
+    let source_map = state.ast.source_map(&Default::default())?;
+    let (file, first_line, last_line) = source_map.lines_of(uuid).ok_or_else(not_typeset)?;
+    build_manager
+        .pdf_location(file.as_deref(), first_line, last_line)?
+        .ok_or_else(not_typeset)
+}
+
+async fn handle_synctex_backward(socket: TexlaSocket, json: String, core: Arc<RwLock<TexlaCore>>) {
+    match synctex_backward(&socket, &json, &core) {
+        Ok(uuid) => send(&socket, "synctex_node", uuid).ok(),
+        Err(err) => send(&socket, "error", err).ok(),
+    };
+}
+
+/// Returns the Uuid of the node which produced the content at the given location in the PDF.
+fn synctex_backward(
+    socket: &TexlaSocket,
+    json: &str,
+    core: &Arc<RwLock<TexlaCore>>,
+) -> Result<u64, TexlaError> {
+    let location = serde_json::from_str::<PdfLocation>(json).map_err(|err| TexlaError {
+        message: format!("Invalid location: {err}"),
+    })?;
+    let build_manager = core.read().unwrap().build_manager.clone();
+    let (file, line) = build_manager
+        .source_location(location)?
+        .ok_or_else(|| TexlaError {
+            message: format!("Nothing was typeset on page {}", location.page),
+        })?;
+
+    let state_ref = extract_state(socket).clone();
+    let source_map = state_ref
+        .read()
         .unwrap()
-        .action_aborted();
+        .ast
+        .source_map(&Default::default())?;
+    Ok(source_map.node_at(file.as_deref(), line))
+}
 
-    // save reverted AST to local files again
-    if let Err(err) = stringify_and_save(state_ref.clone(), Default::default()).await {
-        send(&socket, "error", err).ok();
-    }
+// this function is correctly placed here, because it contains coordination and communication
+fn export_zip(socket: &TexlaSocket, options: StringificationOptions, core: Arc<RwLock<TexlaCore>>) {
+    let state_ref = extract_state(socket).clone();
+
+    // the files are stringified with the given options in memory, the working tree is not touched
+    let documents = {
+        let state = state_ref.read().unwrap();
+        match state.ast.to_latex(options) {
+            Ok(latex) => state
+                .storage_manager
+                .lock()
+                .unwrap()
+                .demultiplex_files(latex),
+            Err(err) => {
+                send(socket, "error", TexlaError::from(err)).ok();
+                return;
+            }
+        }
+    };
+
+    let zip_result = core.write().unwrap().export_manager.zip_files(&documents);
+    send_export_result(socket, zip_result);
+}
+
+/// Sends the ast together with the diagnostics found in it.
+pub(crate) fn send_ast(socket: &TexlaSocket, state: &TexlaState) {
+    send(socket, "new_ast", &state.ast).ok();
+    send(socket, "outline", outline(&state.ast)).ok();
+    send(socket, "diagnostics", state.diagnostics()).ok();
 }
 
 pub(crate) fn send(socket: &TexlaSocket, event: &str, data: impl Serialize) -> Result<(), ()> {
@@ -325,10 +847,13 @@ pub(crate) fn send(socket: &TexlaSocket, event: &str, data: impl Serialize) -> R
             // make sure locks are released before doing this
             let socket = socket.clone();
             tokio::spawn(async move {
-                let state = extract_state(&socket);
-                let state = state.read().unwrap();
-                let mut sm = state.storage_manager.lock().unwrap();
-                sm.disassemble();
+                let state_ref = extract_state(&socket);
+                let mut state = state_ref.write().unwrap();
+                // the project stays open as long as any client is connected
+                if state.remove_socket(&socket) {
+                    let mut sm = state.storage_manager.lock().unwrap();
+                    sm.disassemble();
+                }
             });
         }
     }
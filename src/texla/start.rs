@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::sync::{Arc, RwLock};
 
 use clap::builder::OsStr;
@@ -5,9 +6,13 @@ use clap::Parser;
 
 use ast::latex_constants::LATEX_FILE_EXTENSION;
 
-use crate::infrastructure::export_manager::TexlaExportManager;
+use crate::infrastructure::build_manager::{TexEngine, TexlaBuildManager};
+use crate::infrastructure::export_manager::{ExportProfile, TexlaExportManager};
 use crate::infrastructure::file_path::FilePath;
+use crate::infrastructure::storage_manager::TexlaStorageManager;
+use crate::infrastructure::vcs_manager::GitManager;
 use crate::texla::core::TexlaCore;
+use crate::texla::recovery::replay_journal_file;
 use crate::texla::webserver::{start_axum, DEFAULT_PORT};
 
 // the rustdocs are put into the help message of the CLI
@@ -39,6 +44,31 @@ struct CliArguments {
     #[arg(short = 'P', long, value_name = "port number",
     default_value = OsStr::from(& DEFAULT_PORT.to_string()))]
     port: u16,
+
+    /// The local TeX engine used to build the PDF
+    #[arg(short, long, value_enum, default_value = "latexmk")]
+    engine: TexEngine,
+
+    /// Build the PDF after each save and report its errors at the nodes causing them
+    #[arg(short, long)]
+    background_build: bool,
+
+    /// Leave files matching the pattern out of ZIP exports (can be repeated). Patterns containing
+    /// a '/' are relative to the directory of the main file, e.g. 'drafts/' or '*.bak'.
+    /// Files ignored by git and LaTeX build artifacts are never exported
+    #[arg(short = 'x', long, value_name = "pattern")]
+    export_exclude: Vec<String>,
+
+    /// A named selection of the variants nodes are tagged with (in their metadata, e.g.
+    /// 'variant:long'), which exports can be restricted to (can be repeated). The variants after
+    /// the name are included, those prefixed with '-' excluded, e.g. 'short=short,-appendix'
+    #[arg(short = 'X', long, value_name = "name=variants")]
+    export_profile: Vec<ExportProfile>,
+
+    /// Replay the operations of the given journal (e.g. '.texla/journal.jsonl') against the main
+    /// file and print the resulting LaTeX instead of starting TeXLa (no files are modified)
+    #[arg(long, value_name = "path")]
+    replay: Option<String>,
 }
 
 fn verify_main_file(main_file: &FilePath) -> bool {
@@ -65,6 +95,21 @@ pub async fn start() {
         return;
     }
 
+    if let Some(journal) = args.replay {
+        let storage_manager = TexlaStorageManager::new(
+            GitManager::new(false, main_file.directory.clone()),
+            main_file,
+            args.pull_interval,
+            args.worksession_interval,
+            args.notify_delay,
+        );
+        match replay_journal_file(&storage_manager, Path::new(&journal)) {
+            Ok(latex) => println!("{latex}"),
+            Err(err) => println!("Could not replay journal: {err}"),
+        }
+        return;
+    }
+
     println!("Starting TeXLa...");
 
     let port = args.port;
@@ -78,13 +123,19 @@ pub async fn start() {
     println!("Opening file: {}", main_file.path.to_str().unwrap());
 
     let core = Arc::new(RwLock::new(TexlaCore {
-        export_manager: TexlaExportManager::new(main_file.directory.clone()),
+        export_manager: TexlaExportManager::new(
+            main_file.directory.clone(),
+            args.export_exclude,
+            args.export_profile,
+        ),
+        build_manager: TexlaBuildManager::new(main_file.clone(), args.engine),
+        background_build: args.background_build,
         pull_interval: args.pull_interval,
         worksession_interval: args.worksession_interval,
         notify_delay: args.notify_delay,
         vcs_enabled: !args.no_git, // argument is inverted!
         main_file,
-        socket: None,
+        state: None,
     }));
 
     if let Err(err) = open::that(format!("http://localhost:{port}/")) {
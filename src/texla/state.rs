@@ -1,5 +1,13 @@
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex, RwLock};
 
+use serde::Serialize;
+
+use ast::bibliography::{BibFile, Bibliography};
+use ast::diagnostics::{diagnostics, Diagnostic};
+use ast::diff::diff;
+use ast::matching::match_nodes;
+use ast::outline::outline;
 use ast::texla_ast::TexlaAst;
 use ast::Ast;
 
@@ -7,13 +15,18 @@ use crate::infrastructure::errors::VcsError;
 use crate::infrastructure::storage_manager::{
     DirectoryChangeHandler, StorageManager, TexlaStorageManager,
 };
-use crate::infrastructure::vcs_manager::{GitErrorHandler, GitManager};
+use crate::infrastructure::vcs_manager::{FileConflicts, GitErrorHandler, GitManager};
+use crate::texla::background_build::BackgroundBuild;
 use crate::texla::errors::TexlaError;
 use crate::texla::socket::{parse_ast_from_disk, send, TexlaSocket};
 
+/// The number of earlier versions of the ast whose Uuids can still be used by clients
+const MAX_AST_HISTORY: usize = 100;
+
 pub type TexlaState = State<TexlaAst, TexlaStorageManager<GitManager>>;
 pub type SharedTexlaState = Arc<RwLock<TexlaState>>;
 
+/// The state of one project, which is shared by all clients editing it.
 pub struct State<A, SM>
 where
     A: Ast,
@@ -21,22 +34,145 @@ where
 {
     pub ast: A,
     pub storage_manager: Arc<Mutex<SM>>,
-    pub socket: TexlaSocket,
+    pub sockets: Vec<TexlaSocket>,
+    /// For each earlier version of the ast: the Uuids of its nodes mapped to the Uuids of the
+    /// corresponding nodes in the next version (oldest first)
+    pub history: VecDeque<HashMap<u64, u64>>,
+    /// The errors and warnings of the last background build
+    pub build_diagnostics: Vec<Diagnostic>,
+    /// The `.bib` files used by the document
+    pub bibliography: Bibliography,
+    pub(crate) background_build: Option<BackgroundBuild>,
 }
 
-impl State<TexlaAst, TexlaStorageManager<GitManager>> {}
+impl<A, SM> State<A, SM>
+where
+    A: Ast,
+    SM: StorageManager,
+{
+    pub(crate) fn broadcast(&self, event: &str, data: impl Serialize) {
+        for socket in &self.sockets {
+            send(socket, event, &data).ok();
+        }
+    }
+
+    /// Removes the socket of a client which left. Returns whether this was the last client.
+    pub(crate) fn remove_socket(&mut self, socket: &TexlaSocket) -> bool {
+        let count_before = self.sockets.len();
+        self.sockets.retain(|other| other.sid != socket.sid);
+        count_before > 0 && self.sockets.is_empty()
+    }
+}
+
+impl State<TexlaAst, TexlaStorageManager<GitManager>> {
+    /// Sends the ast together with its outline and the diagnostics found in it to all clients.
+    pub(crate) fn broadcast_ast(&self) {
+        self.broadcast("new_ast", &self.ast);
+        self.broadcast("outline", outline(&self.ast));
+        self.broadcast_diagnostics();
+    }
+
+    pub(crate) fn broadcast_diagnostics(&self) {
+        self.broadcast("diagnostics", self.diagnostics());
+    }
+
+    /// The diagnostics found in the ast followed by the ones of the last background build.
+    pub(crate) fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut all_diagnostics = diagnostics(&self.ast, &self.bibliography);
+        all_diagnostics.extend(self.build_diagnostics.iter().cloned());
+        all_diagnostics
+    }
+
+    /// Reloads the bibliography if the ast uses other `.bib` files than the ones loaded.
+    /// Returns whether it was reloaded.
+    pub(crate) fn update_bibliography(&mut self) -> bool {
+        let loaded: Vec<&String> = self.bibliography.files.iter().map(|f| &f.path).collect();
+        match self.ast.bibliography_paths() {
+            Ok(paths) if paths.iter().collect::<Vec<_>>() != loaded => {
+                self.reload_bibliography();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Reads the `.bib` files which the ast currently uses. Missing files are skipped, since they
+    /// may be generated or not have been created yet.
+    pub(crate) fn reload_bibliography(&mut self) {
+        let paths = match self.ast.bibliography_paths() {
+            Ok(paths) => paths,
+            Err(err) => {
+                println!("Could not find the bibliography files: {err}");
+                return;
+            }
+        };
+        let storage_manager = self.storage_manager.lock().unwrap();
+        let files = paths
+            .into_iter()
+            .filter_map(|path| match storage_manager.read_file(&path) {
+                Ok(content) => Some(BibFile::parse(path, &content)),
+                Err(err) => {
+                    println!("Could not read bibliography '{path}': {err}");
+                    None
+                }
+            })
+            .collect();
+        self.bibliography = Bibliography { files };
+    }
+
+    /// Replaces the ast and remembers which nodes of the old ast correspond to which nodes of the
+    /// new one, so that operations based on the old ast can still be applied.
+    pub(crate) fn replace_ast(&mut self, ast: TexlaAst) {
+        self.history.push_back(match_nodes(&self.ast, &ast));
+        if self.history.len() > MAX_AST_HISTORY {
+            self.history.pop_front();
+        }
+        self.ast = ast;
+    }
+
+    /// Translates a Uuid of an earlier version of the ast (which a client may still be working
+    /// on) to the Uuid of the corresponding node in the current ast.
+    /// Uuids of the current ast and unknown Uuids are returned unchanged.
+    pub(crate) fn translate_uuid(&self, uuid: u64) -> u64 {
+        // Uuids are unique across all versions, so there is at most one version containing it
+        let version = match self
+            .history
+            .iter()
+            .position(|matching| matching.contains_key(&uuid))
+        {
+            Some(version) => version,
+            None => return uuid,
+        };
+        self.history
+            .iter()
+            .skip(version)
+            .try_fold(uuid, |uuid, matching| matching.get(&uuid).copied())
+            .unwrap_or(uuid) // the node was deleted in the meantime
+    }
+}
 
 impl DirectoryChangeHandler for TexlaState {
     fn handle_directory_change(&mut self) {
-        let storage_manager = self.storage_manager.lock().unwrap();
+        let ast = {
+            let storage_manager = self.storage_manager.lock().unwrap();
+            parse_ast_from_disk(&storage_manager)
+        };
 
-        match parse_ast_from_disk(&storage_manager) {
+        match ast {
             Ok(ast) => {
-                self.ast = ast;
-                send(&self.socket, "new_ast", self.ast.clone()).ok();
+                // lets the frontends show what changed before they receive the new ast
+                match diff(&self.ast, &ast) {
+                    Ok(operations) => self.broadcast("remote_changes", operations),
+                    Err(err) => println!("Could not compare the asts: {err}"),
+                }
+                self.replace_ast(ast);
+                // the `.bib` files may have changed as well
+                self.reload_bibliography();
+                self.broadcast("bibliography", &self.bibliography);
+                self.broadcast_ast();
             }
             Err(err) => {
-                send(&self.socket, "error", err).ok();
+                self.broadcast("error", err);
             }
         };
     }
@@ -44,6 +180,120 @@ impl DirectoryChangeHandler for TexlaState {
 
 impl GitErrorHandler for TexlaState {
     fn handle_git_error(&self, error: VcsError) {
-        send(&self.socket, "error", TexlaError::from(error)).ok();
+        self.broadcast("error", TexlaError::from(error));
+    }
+
+    fn handle_merge_conflicts(&self, conflicts: Vec<FileConflicts>) {
+        self.broadcast("merge_conflicts", conflicts);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    use ast::errors::AstError;
+    use ast::operation::JsonOperation;
+    use ast::outline::outline;
+    use ast::texla_ast::TexlaAst;
+    use ast::Ast;
+    use serde_json::json;
+
+    use crate::infrastructure::file_path::FilePath;
+    use crate::infrastructure::storage_manager::TexlaStorageManager;
+    use crate::infrastructure::vcs_manager::GitManager;
+    use crate::texla::state::TexlaState;
+
+    const LATEX: &str = "\\begin{document}\n\\section{Title1}\nSomething\n\n\\subsection{Subtitle}\nanother Block of text\n\n\\section{Title2}\nMore text\n\n\\end{document}\n";
+
+    fn state() -> TexlaState {
+        let main_file = FilePath::from("test_resources/latex/simple.tex");
+        let storage_manager = TexlaStorageManager::new(
+            GitManager::new(false, main_file.directory.clone()),
+            main_file,
+            500,
+            5000,
+            100,
+        );
+        TexlaState {
+            ast: TexlaAst::from_latex(LATEX.to_string()).unwrap(),
+            storage_manager: Arc::new(Mutex::new(storage_manager)),
+            sockets: vec![],
+            history: VecDeque::new(),
+            build_diagnostics: vec![],
+            bibliography: Default::default(),
+            background_build: None,
+        }
+    }
+
+    /// Applies an operation of a client like the socket does, i.e. based on the ast the client
+    /// last received.
+    fn apply(state: &mut TexlaState, operation: serde_json::Value) -> Result<(), AstError> {
+        let mut operation: JsonOperation = serde_json::from_value(operation).unwrap();
+        operation.map_uuids(&mut |uuid| state.translate_uuid(uuid));
+        state.ast.execute(operation.to_trait_obj())?;
+        let reparsed = TexlaAst::from_latex(state.ast.to_latex(Default::default())?)?;
+        state.replace_ast(reparsed);
+        Ok(())
+    }
+
+    #[test]
+    fn operations_of_two_clients_on_a_stale_ast() {
+        let mut state = state();
+        // both clients received the same ast
+        let root = state.ast.node_at_path(&[]).unwrap();
+        let entries = outline(&state.ast);
+        let (title1, subtitle, title2) = (
+            entries[0].uuid,
+            entries[0].children[0].uuid,
+            entries[1].uuid,
+        );
+
+        // the first client deletes the subsection
+        apply(
+            &mut state,
+            json!({ "type": "DeleteNode", "arguments": { "target": subtitle } }),
+        )
+        .unwrap();
+        // the second client has not received the new ast yet and renames the second section
+        apply(
+            &mut state,
+            json!({
+                "type": "EditNode",
+                "arguments": { "target": title2, "raw_latex": "\\section{Renamed}\n…" }
+            }),
+        )
+        .unwrap();
+        // the first client moves the first section behind the second one, still using the
+        // Uuids of the original ast
+        apply(
+            &mut state,
+            json!({
+                "type": "MoveNode",
+                "arguments": {
+                    "target": title1,
+                    "destination": { "parent": root, "after_sibling": title2 }
+                }
+            }),
+        )
+        .unwrap();
+
+        let headings: Vec<String> = outline(&state.ast)
+            .into_iter()
+            .map(|entry| entry.heading)
+            .collect();
+        assert_eq!(headings, vec!["Renamed", "Title1"]);
+        let latex = state.ast.to_latex(Default::default()).unwrap();
+        assert!(!latex.contains("Subtitle"));
+        assert!(latex.contains("More text"));
+
+        // the deleted node cannot be confused with a node of a later ast
+        assert!(apply(
+            &mut state,
+            json!({ "type": "DeleteNode", "arguments": { "target": subtitle } }),
+        )
+        .is_err());
+        assert_eq!(state.ast.to_latex(Default::default()).unwrap(), latex);
     }
 }
@@ -1,25 +1,37 @@
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
 use axum::body::StreamBody;
-use axum::http::{header, StatusCode};
-use axum::response::IntoResponse;
-use axum::routing::get;
-use axum::{Extension, Server};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{AppendHeaders, IntoResponse};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Server};
 use tower_http::services::{ServeDir, ServeFile};
 use tower_http::trace::TraceLayer;
 
+use crate::infrastructure::export_manager::{is_html_export, ExportManager, DOWNLOAD_URL};
 use crate::texla::core::TexlaCore;
-use crate::texla::socket::socket_service;
+use ast::bibliography::import::ImportReport;
+
+use crate::texla::socket::{import_bibliography, socket_service};
 
 const LOCALHOST_IP: [u8; 4] = [127, 0, 0, 1];
+const LOCAL_HOST_NAMES: [&str; 2] = ["localhost", "127.0.0.1"];
 pub const DEFAULT_PORT: u16 = 13814;
 const FRONTEND_SUBDIR: &str = "frontend";
+const BIBLIOGRAPHY_IMPORT_URL: &str = "/bibliography/import";
+/// Scripts of the HTML export may run, but not with the origin of the editor
+const EXPORT_CONTENT_SECURITY_POLICY: &str = "sandbox allow-scripts";
 
 pub async fn start_axum(core: Arc<RwLock<TexlaCore>>, port: u16) {
     let app = axum::Router::new()
         .fallback_service(static_files())
         .route("/user-assets/*path", get(user_assets_handler))
+        .route(&format!("{DOWNLOAD_URL}/:token"), get(download_handler))
+        .route(
+            &format!("{BIBLIOGRAPHY_IMPORT_URL}/:file_name"),
+            post(bibliography_import_handler),
+        )
         .layer(
             TraceLayer::new_for_http().on_body_chunk(()).on_eos(()), // .on_request(log_request)
         )
@@ -58,6 +70,15 @@ async fn user_assets_handler(
 ) -> Result<impl IntoResponse, StatusCode> {
     println!("Serving user asset: {path}");
 
+    // the assets are restricted to the project directory
+    let relative_path = Path::new(&path);
+    if relative_path.is_absolute()
+        || relative_path
+            .components()
+            .any(|component| component == Component::ParentDir)
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
     let main_file_directory = core.read().unwrap().main_file.directory.clone();
     let file = match tokio::fs::File::open(main_file_directory.join(&path)).await {
         Ok(file) => file,
@@ -69,14 +90,112 @@ async fn user_assets_handler(
     // convert the `Stream` into an `axum::body::HttpBody`
     let body = StreamBody::new(stream);
 
-    let content_disposition_header = format!("attachment; filename=\"{path}\"");
-    let headers = [
-        (header::CONTENT_TYPE, "text/toml; charset=utf-8"),
+    // Files which can run scripts are only shown by the browser if they belong to the HTML export.
+    // Any other HTML or SVG file of the project (e.g. pulled from a collaborator) could otherwise
+    // use the socket of the editor. Everything which is not shown is downloaded.
+    let is_html_export = is_html_export(&path);
+    let (content_type, disposition) = match path.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("html") if is_html_export => ("text/html; charset=utf-8", "inline"),
+        Some("svg") if is_html_export => ("image/svg+xml", "inline"),
+        Some("md") => ("text/plain; charset=utf-8", "inline"),
+        Some("pdf") => ("application/pdf", "inline"),
+        Some("png") => ("image/png", "inline"),
+        Some("jpg" | "jpeg") => ("image/jpeg", "inline"),
+        Some("gif") => ("image/gif", "inline"),
+        _ => ("text/toml; charset=utf-8", "attachment"),
+    };
+    let file_name = path.rsplit('/').next().unwrap_or(&path);
+    let mut headers = vec![
+        (header::CONTENT_TYPE, content_type.to_string()),
         (
             header::CONTENT_DISPOSITION,
-            content_disposition_header.as_str(),
+            format!("{disposition}; filename=\"{file_name}\""),
         ),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+    ];
+    if is_html_export {
+        // the export needs scripts to render math, but runs in an origin of its own
+        headers.push((
+            header::CONTENT_SECURITY_POLICY,
+            EXPORT_CONTENT_SECURITY_POLICY.to_string(),
+        ));
+    }
+
+    Ok((AppendHeaders(headers), body).into_response())
+}
+
+/// Streams a prepared export, which is deleted right away, so that each URL works only once.
+async fn download_handler(
+    Extension(core): Extension<Arc<RwLock<TexlaCore>>>,
+    axum::extract::Path(token): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let download = core
+        .write()
+        .unwrap()
+        .export_manager
+        .take_download(&token)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    println!("Serving download: {}", download.file_name);
+
+    let file = match tokio::fs::File::open(&download.path).await {
+        Ok(file) => file,
+        Err(_) => return Err(StatusCode::NOT_FOUND),
+    };
+    // the opened file can still be read after it has been removed
+    if let Err(err) = tokio::fs::remove_file(&download.path).await {
+        println!("Could not remove download: {err}");
+    }
+
+    let body = StreamBody::new(tokio_util::io::ReaderStream::new(file));
+    let content_disposition_header = format!("attachment; filename=\"{}\"", download.file_name);
+    let headers = [
+        (header::CONTENT_TYPE, "application/zip".to_string()),
+        (header::CONTENT_DISPOSITION, content_disposition_header),
     ];
 
     Ok((headers, body).into_response())
 }
+
+/// Merges an uploaded bibliography (the body of the request) into the bibliography of the
+/// project. The format is determined by the extension of `file_name`.
+async fn bibliography_import_handler(
+    Extension(core): Extension<Arc<RwLock<TexlaCore>>>,
+    axum::extract::Path(file_name): axum::extract::Path<String>,
+    headers: HeaderMap,
+    content: String,
+) -> Result<Json<ImportReport>, (StatusCode, String)> {
+    println!("Importing bibliography: {file_name}");
+
+    if !is_editor_request(&headers) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only the editor may import bibliographies".to_string(),
+        ));
+    }
+
+    let state = core.read().unwrap().state.clone().ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "No project is opened".to_string(),
+    ))?;
+    match import_bibliography(state, &file_name, &content).await {
+        Ok(report) => Ok(Json(report)),
+        Err(err) => Err((StatusCode::BAD_REQUEST, err.to_string())),
+    }
+}
+
+/// Whether a request was sent by the editor and not by another website open in the browser.
+/// Browsers add the `Origin` to every cross-origin POST request, and the `Host` has to be local,
+/// so that a website cannot make its own domain refer to the local server.
+fn is_editor_request(headers: &HeaderMap) -> bool {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let Some(host) = header(header::HOST) else {
+        return false;
+    };
+    let is_local = LOCAL_HOST_NAMES
+        .iter()
+        .any(|name| host == *name || host.starts_with(&format!("{name}:")));
+    match header(header::ORIGIN) {
+        Some(origin) => is_local && origin == format!("http://{host}"),
+        None => is_local,
+    }
+}